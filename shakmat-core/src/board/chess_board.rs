@@ -1,13 +1,20 @@
-use std::fmt::Display;
+use std::fmt::{Debug, Display};
+use std::hash::{Hash, Hasher};
 use std::result::Result;
+#[cfg(not(target_arch = "wasm32"))]
 use rayon::prelude::*;
+#[cfg(feature = "serde")]
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
 
-use crate::game_elements::{CastlingRights, Color, Color::*, PieceType, PieceType::*, Move, Square};
-use crate::board::BitBoard;
-use crate::fen::{read_fen, DEFAULT_FEN, fen_utils};
+use crate::game_elements::{CastlingRights, Color, Color::*, PieceType, PieceType::*, Move, MoveEffect, Square, GameResult, DrawReason};
+use crate::board::{BitBoard, MoveList};
+use crate::fen::{read_fen, DEFAULT_FEN, fen_utils, FENInfo};
 use crate::zobrist;
 use crate::magic::EP_ATTACKS;
-use super::movegen;
+use super::movegen::{self, LegalityCheck};
+
+// Squares of the board with a light color (h1, a2, ...)
+const LIGHT_SQUARES: BitBoard = BitBoard::new(0xAA55AA55AA55AA55);
 
 #[derive(Clone, Copy)]
 pub struct Board {
@@ -16,6 +23,8 @@ pub struct Board {
     fifty_move_rule_counter: u16,
     full_turns: u16,
     plies: u16,
+    moves_made: u16,
+    after_null_move: bool,
     en_passant_target: BitBoard,
     white_pieces: Pieces,
     black_pieces: Pieces,
@@ -24,12 +33,11 @@ pub struct Board {
     all_pieces: BitBoard,
     piece_on_square: [Option<PieceType>; 64],
     last_moved: u8,
-    black_attacks: BitBoard,
-    white_attacks: BitBoard,
     zobrist_key: u64,
+    pawn_key: u64,
 }
 
-#[derive(Clone, Copy, Default)]
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
 pub struct Pieces {
     pub pawns: BitBoard,
     pub rooks: BitBoard,
@@ -41,31 +49,51 @@ pub struct Pieces {
 
 impl Board {
     pub fn from_fen(fen: &str) -> Result<Self, String> {
-        let fen_info = read_fen(fen)?;
-        let plies = (fen_info.fullmoves_since_start - 1) * 2 
-            + (fen_info.turn == Black) as u16;
+        read_fen(fen).map(Self::from_fen_info)
+    }
 
-        let mut board = Self {
-            castling_rights: fen_info.castling_rights,
-            turn: fen_info.turn,
-            en_passant_target: fen_info.en_passant_square,
-            fifty_move_rule_counter: fen_info.halfmoves_since_capture,
-            full_turns: fen_info.fullmoves_since_start,
-            white_pieces: fen_info.white_pieces,
-            black_pieces: fen_info.black_pieces,
-            all_whites: BitBoard::default(),
-            all_blacks: BitBoard::default(),
-            all_pieces: BitBoard::default(),
-            piece_on_square: fen_info.piece_on_square,
-            black_attacks: BitBoard::default(),
-            white_attacks: BitBoard::default(),
-            last_moved: u8::MAX, // We don't know which piece was the last to move
-            zobrist_key: 0,
-            plies
+    // Creates a board from the piece on each square, indexed like the bitboards
+    // (h1 = 0, a1 = 7, ..., a8 = 63) and the rest of the state of the game.
+    // The position is validated like in from_fen_strict().
+    pub fn from_pieces(
+        pieces: &[Option<(Color, PieceType)>; 64],
+        turn: Color,
+        castling_rights: CastlingRights,
+        en_passant: Option<Square>,
+        halfmoves: u16,
+        fullmoves: u16,
+    ) -> Result<Self, String> {
+        let mut fen_info = FENInfo {
+            turn,
+            castling_rights,
+            en_passant_square: en_passant.map_or_else(BitBoard::default, |sq| sq.as_bitboard()),
+            halfmoves_since_capture: halfmoves,
+            fullmoves_since_start: fullmoves,
+            black_pieces: Pieces::default(),
+            white_pieces: Pieces::default(),
+            piece_on_square: [None; 64],
         };
 
-        board.update_aux_bitboards();
-        board.create_zobrist_key();
+        for (square, (color, piece)) in pieces.iter().enumerate().filter_map(|(sq, p)| p.map(|p| (sq, p))) {
+            let player_pieces = match color {
+                White => &mut fen_info.white_pieces,
+                Black => &mut fen_info.black_pieces,
+            };
+
+            *player_pieces.get_pieces_of_type_mut(piece) |= BitBoard::from_square(square as u8);
+            fen_info.piece_on_square[square] = Some(piece);
+        }
+
+        let board = Self::from_fen_info(fen_info);
+        board.validate_position()?;
+        Ok(board)
+    }
+
+    // Same as from_fen(), but also rejects positions that cannot happen in a
+    // legal game, which is useful to validate positions provided by users
+    pub fn from_fen_strict(fen: &str) -> Result<Self, String> {
+        let board = Self::from_fen(fen)?;
+        board.validate_position()?;
         Ok(board)
     }
 
@@ -119,9 +147,36 @@ impl Board {
 
         new_board.update_aux_bitboards();
         new_board.plies += 1;
+        new_board.moves_made += 1;
+        new_board.after_null_move = false;
         new_board
     }
 
+    // Same as make_move(), but the move is only made if it's legal,
+    // otherwise an error is returned and the board isn't modified.
+    // Returns what the move did, such as the piece it captured.
+    pub fn make_move_checked(&mut self, movement: &Move) -> Result<MoveEffect, String> {
+        if !self.is_legal_move(movement) {
+            return Err(format!("Illegal move: {movement}"));
+        }
+
+        let next_board = self.make_move(movement);
+        let effect = MoveEffect::new(self, movement, &next_board);
+        *self = next_board;
+        Ok(effect)
+    }
+
+    // Whether a legal move checks the enemy king, directly or by uncovering
+    // an attack from another piece, without modifying this board.
+    // For now, the move is made on a copy of the board. It could be cheaper to
+    // look at the squares from which each piece would attack the enemy king and
+    // at the pieces that block our sliders from reaching it, which would also
+    // avoid copying the board, but then castling, promotions and e.p. captures
+    // would need special care, so that's left for when the search needs it.
+    pub fn move_gives_check(&self, movement: &Move) -> bool {
+        self.make_move(movement).is_check(!self.turn_color())
+    }
+
     // Performs a null move, which means passing
     pub fn make_null_move(&self) -> Self {
         // Copy the current board and make the changes on it
@@ -137,6 +192,7 @@ impl Board {
         new_board.turn = !self.turn;
         new_board.zobrist_key ^= zobrist::get_key_white_turn();
         new_board.plies += 1;
+        new_board.after_null_move = true;
 
         if new_board.turn == White {
             new_board.full_turns += 1;
@@ -146,49 +202,174 @@ impl Board {
     }
 
     pub fn pseudolegal_moves(&self) -> Vec<Move> {
-        if self.is_draw() {
-            vec![]
-        } else {
-            movegen::get_pseudolegal_moves(self, self.turn_color())
+        self.pseudolegal_move_list().to_vec()
+    }
+
+    // Same as pseudolegal_moves(), but without allocating memory
+    pub fn pseudolegal_move_list(&self) -> MoveList {
+        let mut moves = MoveList::new();
+        if !self.is_draw() {
+            self.pseudolegal_moves_ignoring_draws(&mut moves);
         }
+        moves
+    }
+
+    // Pseudolegal moves that may get the side to move out of check: capturing or
+    // blocking the checker, or moving the king to a square that isn't attacked
+    pub fn evasion_moves(&self) -> Vec<Move> {
+        let mut moves = MoveList::new();
+        movegen::get_evasions(self, &mut moves);
+        moves.to_vec()
     }
 
     pub fn pseudolegal_caps(&self) -> Vec<Move> {
-        if self.is_draw() {
-            vec![]
-        } else {
-            movegen::get_pseudolegal_caps_proms(self)
+        self.pseudolegal_caps_list().to_vec()
+    }
+
+    // Same as pseudolegal_caps(), but without allocating memory
+    pub fn pseudolegal_caps_list(&self) -> MoveList {
+        let mut moves = MoveList::new();
+        if !self.is_draw() {
+            movegen::get_pseudolegal_caps_proms(self, &mut moves);
         }
+        moves
     }
 
     pub fn legal_moves(&self) -> Vec<Move> {
-        self.pseudolegal_moves().into_iter()
-            .filter(|mv| matches!(mv, Move::ShortCastle | Move::LongCastle) ||
-                         !self.make_move(mv).is_check(self.turn_color())
-            )
-            .collect()
+        self.legal_move_list().to_vec()
+    }
+
+    // Same as legal_moves(), but without allocating memory
+    pub fn legal_move_list(&self) -> MoveList {
+        let legality = LegalityCheck::new(self);
+        let mut moves = self.pseudolegal_move_list();
+        moves.retain(|mv| legality.is_legal(mv));
+        moves
     }
 
+    // Same as legal_move_list(), but also when the position is a draw by the fifty
+    // move rule or insufficient material, since the game goes on unless it's claimed
+    pub(crate) fn legal_moves_ignoring_draws(&self) -> MoveList {
+        let legality = LegalityCheck::new(self);
+        let mut moves = MoveList::new();
+        self.pseudolegal_moves_ignoring_draws(&mut moves);
+        moves.retain(|mv| legality.is_legal(mv));
+        moves
+    }
+
+    // Looks for enemy pieces that attack the king from its square, instead of
+    // keeping the squares attacked by each side up to date after every move
     pub fn is_check(&self, color: Color) -> bool {
-        match color {
-            White => (self.white_pieces.king & self.black_attacks).is_not_empty(),
-            Black => (self.black_pieces.king & self.white_attacks).is_not_empty()
+        self.get_pieces(color).king.piece_indices()
+            .any(|king| self.is_square_attacked(king, !color))
+    }
+
+    // Whether a square is attacked by any piece of a color. Cheaper than
+    // looking at the attackers of the square when they're not needed
+    pub fn is_square_attacked(&self, square: u8, color: Color) -> bool {
+        movegen::is_attacked(self, square, color, self.all_pieces)
+    }
+
+    // Returns the pieces of a color that attack a square
+    pub fn attackers_of(&self, square: u8, color: Color) -> BitBoard {
+        movegen::get_attackers(self, square, color, self.all_pieces)
+    }
+
+    // Returns the pieces of a color that can't leave the line between
+    // their king and an enemy slider without exposing the king
+    pub fn pinned_pieces(&self, color: Color) -> BitBoard {
+        movegen::get_pinned(self, color)
+    }
+
+    pub fn is_checkmate(&self) -> bool {
+        self.is_check(self.turn_color()) && !self.has_legal_moves()
+    }
+
+    pub fn is_stalemate(&self) -> bool {
+        !self.is_check(self.turn_color()) && !self.has_legal_moves()
+    }
+
+    // Returns the result of the game if it has ended in this position. Note that
+    // draws by repetition are not considered, since they depend on the history
+    pub fn game_result(&self) -> Option<GameResult> {
+        // Checkmate takes precedence over the fifty move rule, so we check
+        // for available moves directly without considering draws
+        if !self.has_legal_moves() {
+            if self.is_check(self.turn_color()) {
+                Some(GameResult::win_for(!self.turn_color()))
+            } else {
+                Some(GameResult::Draw(DrawReason::Stalemate))
+            }
+        } else if self.is_draw_by_material() {
+            Some(GameResult::Draw(DrawReason::InsufficientMaterial))
+        } else if self.is_seventyfive_move_draw() {
+            Some(GameResult::Draw(DrawReason::SeventyFiveMoveRule))
+        } else if self.is_fifty_move_draw() {
+            Some(GameResult::Draw(DrawReason::FiftyMoveRule))
+        } else {
+            None
         }
     }
 
-    // A position is a draw by insufficient material if both sides have either
-    // only K, KB or KN
+    // Same as game_result(), but also considers fivefold repetitions, which are
+    // automatic draws, given the zobrist keys of the positions played so far
+    pub fn game_result_with_history(&self, history: &[u64]) -> Option<GameResult> {
+        self.game_result().or_else(|| {
+            self.is_fivefold_repetition(history).then_some(GameResult::Draw(DrawReason::FivefoldRepetition))
+        })
+    }
+
+    // Returns whether 50 moves have been played by each side without any captures or pawn moves
+    pub fn is_fifty_move_draw(&self) -> bool {
+        self.fifty_move_rule_counter() >= 100
+    }
+
+    // Unlike the fifty move rule, which must be claimed by a player,
+    // after 75 moves the game is drawn automatically
+    pub fn is_seventyfive_move_draw(&self) -> bool {
+        self.fifty_move_rule_counter() >= 150
+    }
+
+    // Returns whether the current position has occurred three times in the game,
+    // given the zobrist keys of the positions played so far
+    pub fn is_threefold_repetition(&self, history: &[u64]) -> bool {
+        self.repetition_count(history) >= 3
+    }
+
+    // Same as is_threefold_repetition(), but five repetitions
+    // are an automatic draw instead of one that must be claimed
+    pub fn is_fivefold_repetition(&self, history: &[u64]) -> bool {
+        self.repetition_count(history) >= 5
+    }
+
+    // A position is a draw by insufficient material if no checkmate is possible
+    // by any sequence of moves. This is the case for K vs K, K + minor piece vs K,
+    // and any number of bishops with all of them on squares of the same color.
     pub fn is_draw_by_material(&self) -> bool {
         // Return false if the current position is a check, since otherwise
         // we would return an empty list of available moves in a position that is
         // a check, which would be interpreted as a checkmate
-        let is_check = self.is_check(self.turn_color());
+        if self.is_check(self.turn_color()) {
+            return false;
+        }
 
-        let n_whites = self.all_whites.count();
-        let n_blacks = self.all_blacks.count();
+        let (whites, blacks) = (&self.white_pieces, &self.black_pieces);
+        let heavy_or_pawns = whites.pawns | whites.rooks | whites.queens | blacks.pawns | blacks.rooks | blacks.queens;
+        if heavy_or_pawns.is_not_empty() {
+            return false;
+        }
 
-        !is_check && (n_whites == 1 || n_whites == 2 && (self.white_pieces.bishops.count() == 1 || self.white_pieces.knights.count() == 1)) 
-                  && (n_blacks == 1 || n_blacks == 2 && (self.black_pieces.bishops.count() == 1 || self.black_pieces.knights.count() == 1)) 
+        let knights = whites.knights | blacks.knights;
+        let bishops = whites.bishops | blacks.bishops;
+
+        // A single minor piece can never checkmate
+        if (knights | bishops).count() <= 1 {
+            return true;
+        }
+
+        // With only bishops on the board, checkmate is impossible if all of them
+        // move on squares of the same color
+        knights.is_empty() && ((bishops & LIGHT_SQUARES).is_empty() || (bishops & !LIGHT_SQUARES).is_empty())
     }
 
     // Returns whether the current position only has pawns, or if it has
@@ -205,6 +386,19 @@ impl Board {
         piece_count == self.black_pieces.pawns.count() + self.white_pieces.pawns.count() + 2
     }
 
+    // Returns how far the game is into the endgame according to the non-pawn material,
+    // from 0 with all of it on the board to 256 with only kings and pawns. Extra pieces
+    // from promotions don't make it go below 0.
+    pub fn game_phase(&self) -> u16 {
+        let (whites, blacks) = (&self.white_pieces, &self.black_pieces);
+        let minors = whites.knights.count() + whites.bishops.count() + blacks.knights.count() + blacks.bishops.count();
+        let rooks = whites.rooks.count() + blacks.rooks.count();
+        let queens = whites.queens.count() + blacks.queens.count();
+
+        let phase = 24u32.saturating_sub(minors + 2 * rooks + 4 * queens);
+        ((phase * 256 + 12) / 24) as u16
+    }
+
     pub fn ep_square(&self) -> BitBoard {
         self.en_passant_target
     }
@@ -225,10 +419,29 @@ impl Board {
         self.zobrist_key
     }
 
+    // Zobrist key that only takes the pawns into account, which
+    // the evaluation uses to cache the pawn structure scores
+    pub fn pawn_key(&self) -> u64 {
+        self.pawn_key
+    }
+
     pub fn current_ply(&self) -> u16 {
         self.plies
     }
 
+    // Number of moves made since the board was created, not counting null moves.
+    // Unlike current_ply(), it doesn't depend on the move number of the FEN.
+    // The moves themselves aren't stored, since boards are copied to make them,
+    // so they must be kept along with the board if they are needed.
+    pub fn move_count(&self) -> usize {
+        self.moves_made as usize
+    }
+
+    // Whether the last thing done to reach this board was passing the turn
+    pub fn is_after_null_move(&self) -> bool {
+        self.after_null_move
+    }
+
     pub fn fifty_move_rule_counter(&self) -> u16 {
         self.fifty_move_rule_counter
     }
@@ -255,11 +468,10 @@ impl Board {
         self.all_pieces
     }
 
+    // The squares attacked by a side are calculated every time, since
+    // they aren't needed to make moves
     pub fn get_attack_bitboard(&self, color: Color) -> BitBoard {
-        match color {
-            White => self.white_attacks,
-            Black => self.black_attacks,
-        }
+        movegen::get_controlled_squares(self, color)
     }
 
     pub fn last_moved(&self) -> u8 {
@@ -271,9 +483,38 @@ impl Board {
     }
 
     pub fn perft(&self, depth: usize) -> u64 {
+        if depth == 0 {
+            return 1;
+        }
+
         self._perft(depth, true)
     }
 
+    // Same as perft(), but without splitting the work between threads
+    pub fn perft_single_thread(&self, depth: usize) -> u64 {
+        if depth == 0 {
+            return 1;
+        }
+
+        self._perft(depth, false)
+    }
+
+    // Returns the perft count of the subtree of each legal move, which is
+    // very useful to find movegen bugs by comparing it against other engines
+    pub fn perft_divide(&self, depth: usize) -> Vec<(Move, u64)> {
+        let divide = |mv: Move| {
+            let count = if depth <= 1 { 1 } else { self.make_move(&mv)._perft(depth - 1, false) };
+            (mv, count)
+        };
+
+        // Threads are not available in wasm, so it's always single-threaded there
+        #[cfg(not(target_arch = "wasm32"))]
+        return self.legal_moves().into_par_iter().map(divide).collect();
+
+        #[cfg(target_arch = "wasm32")]
+        self.legal_moves().into_iter().map(divide).collect()
+    }
+
     ///////////////////////////////////////////////////////////////////////////
     /// Private auxiliary functions
     
@@ -314,6 +555,7 @@ impl Board {
             captured_piece = Some(Pawn);
             // Update the zobrist key removing the captured pawn
             self.zobrist_key ^= zobrist::get_key_for_piece(Pawn, enemy_color, target_ep);
+            self.pawn_key ^= zobrist::get_key_for_piece(Pawn, enemy_color, target_ep);
             
         // Not an en-passant, just a normal capture
         } else if (enemy_pieces & to_bb).is_not_empty() {
@@ -321,10 +563,16 @@ impl Board {
             captured_piece = *self.piece_on(movement.to());
             // Update the zobrist key (no need to update piece_on_square since it'll be overwritten)
             self.zobrist_key ^= zobrist::get_key_for_piece(captured_piece.unwrap(), enemy_color, movement.to());
+            if captured_piece == Some(Pawn) {
+                self.pawn_key ^= zobrist::get_key_for_piece(Pawn, enemy_color, movement.to());
+            }
         }
 
         // Move the piece, depending on whether this is a pawn promotion or not
         self.zobrist_key ^= zobrist::get_key_for_piece(piece_moving, moving_color, movement.from());
+        if piece_moving == Pawn {
+            self.pawn_key ^= zobrist::get_key_for_piece(Pawn, moving_color, movement.from());
+        }
         *self.piece_on_mut(movement.from()) = None;
         let our_pieces = self.get_pieces_mut(moving_color);
 
@@ -336,6 +584,9 @@ impl Board {
         } else {
             *our_pieces.get_pieces_of_type_mut(piece_moving) ^= from_bb | to_bb;
             self.zobrist_key ^= zobrist::get_key_for_piece(piece_moving, moving_color, movement.to());
+            if piece_moving == Pawn {
+                self.pawn_key ^= zobrist::get_key_for_piece(Pawn, moving_color, movement.to());
+            }
             *self.piece_on_mut(movement.to()) = Some(piece_moving);
         }
 
@@ -447,9 +698,6 @@ impl Board {
         self.all_blacks = blacks.pawns | blacks.rooks | blacks.knights | blacks.bishops | blacks.queens | blacks.king;
         self.all_whites = whites.pawns | whites.rooks | whites.knights | whites.bishops | whites.queens | whites.king;
         self.all_pieces = self.all_blacks | self.all_whites;
-
-        self.white_attacks = movegen::get_controlled_squares(self, White);
-        self.black_attacks = movegen::get_controlled_squares(self, Black);
     }
 
     fn create_zobrist_key(&mut self) {
@@ -465,6 +713,10 @@ impl Board {
                     .piece_indices()
                     .for_each(|sq| self.zobrist_key ^= zobrist::get_key_for_piece(piece_type, color, sq));
             }
+
+            self.get_pieces(color).pawns
+                .piece_indices()
+                .for_each(|sq| self.pawn_key ^= zobrist::get_key_for_piece(Pawn, color, sq));
         }
 
         // Then, castling rights
@@ -492,6 +744,114 @@ impl Board {
         &mut self.piece_on_square[square as usize]
     }
 
+    // Builds the board from the parsed FEN fields, without validating them
+    fn from_fen_info(fen_info: FENInfo) -> Self {
+        let plies = fen_info.fullmoves_since_start.saturating_sub(1) * 2 
+            + (fen_info.turn == Black) as u16;
+
+        let mut board = Self {
+            castling_rights: fen_info.castling_rights,
+            turn: fen_info.turn,
+            en_passant_target: fen_info.en_passant_square,
+            fifty_move_rule_counter: fen_info.halfmoves_since_capture,
+            full_turns: fen_info.fullmoves_since_start,
+            white_pieces: fen_info.white_pieces,
+            black_pieces: fen_info.black_pieces,
+            all_whites: BitBoard::default(),
+            all_blacks: BitBoard::default(),
+            all_pieces: BitBoard::default(),
+            piece_on_square: fen_info.piece_on_square,
+            last_moved: u8::MAX, // We don't know which piece was the last to move
+            zobrist_key: 0,
+            pawn_key: 0,
+            plies,
+            moves_made: 0,
+            after_null_move: false,
+        };
+
+        board.update_aux_bitboards();
+        board.create_zobrist_key();
+        board
+    }
+
+    // Checks that the position could happen in a legal game, returning
+    // a message with the reason if it can't
+    fn validate_position(&self) -> Result<(), String> {
+        let back_ranks = BitBoard::new(0xFF000000000000FF);
+
+        for color in [White, Black] {
+            let pieces = self.get_pieces(color);
+            let kings = pieces.king.count();
+            if kings != 1 {
+                return Err(format!("{color} must have exactly one king, found {kings}"));
+            }
+
+            if (pieces.pawns & back_ranks).is_not_empty() {
+                return Err(format!("{color} has pawns on the first or last rank"));
+            }
+
+            // The king and the rooks must be on their initial squares to castle
+            let (king_square, rook_squares) = match color {
+                White => (3, [0, 7]),
+                Black => (59, [56, 63]),
+            };
+
+            let kingside = self.castling_rights.can_castle_kingside(color);
+            let queenside = self.castling_rights.can_castle_queenside(color);
+            if (kingside || queenside) && (pieces.king & BitBoard::from_square(king_square)).is_empty() {
+                return Err(format!("{color} can castle, but the king is not on its initial square"));
+            }
+
+            for (can_castle, rook_square, side) in [(kingside, rook_squares[0], "kingside"), (queenside, rook_squares[1], "queenside")] {
+                if can_castle && (pieces.rooks & BitBoard::from_square(rook_square)).is_empty() {
+                    return Err(format!("{color} can castle {side}, but the rook is not on its initial square"));
+                }
+            }
+        }
+
+        if self.is_check(!self.turn_color()) {
+            return Err(format!("{} is in check, but it's not their turn", !self.turn_color()));
+        }
+
+        // The e.p. square must be right behind a pawn that just moved two squares,
+        // and both it and the square the pawn came from must be empty
+        if self.ep_square().is_not_empty() {
+            let ep = self.ep_square().first_piece_index();
+            let inconsistent_ep = || Err(format!("The en passant square {} is not consistent with the position", Square::new(ep)));
+            let expected_rank = match self.turn_color() {
+                White => 5,
+                Black => 2,
+            };
+
+            // The rank is checked first, so that the squares next to it are on the board
+            if ep / 8 != expected_rank {
+                return inconsistent_ep();
+            }
+
+            let (pawn_square, from_square) = match self.turn_color() {
+                White => (ep - 8, ep + 8),
+                Black => (ep + 8, ep - 8),
+            };
+
+            let enemy_pawns = self.get_pieces(!self.turn_color()).pawns;
+            if (enemy_pawns & BitBoard::from_square(pawn_square)).is_empty()
+                || (self.all_pieces & (self.ep_square() | BitBoard::from_square(from_square))).is_not_empty() {
+                return inconsistent_ep();
+            }
+        }
+
+        if self.full_turns == 0 {
+            return Err("The full move number must be at least 1".to_owned());
+        }
+
+        Ok(())
+    }
+
+    // The e.p. square, only if a pawn can actually capture on it
+    pub(crate) fn capturable_ep_square(&self) -> BitBoard {
+        if self.update_ep_zobrist(self.turn_color()) { self.ep_square() } else { BitBoard::default() }
+    }
+
     fn update_ep_zobrist(&self, color_capturing: Color) -> bool {
         // Returns whether the current board should have the zobrist
         // flag for an active e.p. square on. This is only true if
@@ -503,36 +863,67 @@ impl Board {
         ).is_empty()
     }
 
+    fn has_legal_moves(&self) -> bool {
+        // Unlike legal_moves(), this ignores whether the position is a draw
+        let legality = LegalityCheck::new(self);
+        let mut moves = MoveList::new();
+        self.pseudolegal_moves_ignoring_draws(&mut moves);
+        moves.iter().any(|mv| legality.is_legal(mv))
+    }
+
+    // When in check, only the moves that may get out of it are generated
+    fn pseudolegal_moves_ignoring_draws(&self, moves: &mut MoveList) {
+        if self.is_check(self.turn_color()) {
+            movegen::get_evasions(self, moves);
+        } else {
+            movegen::get_pseudolegal_moves(self, self.turn_color(), moves);
+        }
+    }
+
+    // Counts the times the current position has occurred in a game. The history
+    // may or may not include the current position as its last element.
+    fn repetition_count(&self, history: &[u64]) -> usize {
+        // Positions before the last irreversible move can't be repeated
+        let start = history.len().saturating_sub(self.fifty_move_rule_counter() as usize + 1);
+        let mut reversible = &history[start..];
+
+        if reversible.last() == Some(&self.zobrist_key()) {
+            reversible = &reversible[..reversible.len() - 1];
+        }
+
+        // Only every other position has the same side to move as the current one
+        1 + reversible.iter().rev().skip(1).step_by(2)
+            .filter(|&&zobrist| zobrist == self.zobrist_key())
+            .count()
+    }
+
     fn is_draw(&self) -> bool {
-        self.fifty_move_rule_counter() >= 100 || self.is_draw_by_material()
+        self.is_fifty_move_draw() || self.is_draw_by_material()
     }
 
+    #[cfg_attr(target_arch = "wasm32", allow(unused_variables))]
     fn _perft(&self, depth: usize, multithread: bool) -> u64 {
         if depth == 1 {
-            return self.legal_moves().len() as u64
+            return self.legal_move_list().len() as u64
         }
 
-        let pseudo_moves = self.pseudolegal_moves();
+        let count_move = |mv: Move| {
+            let new_board = self.make_move(&mv);
+            if matches!(mv, Move::LongCastle | Move::ShortCastle) || !new_board.is_check(self.turn_color()) {
+                Some(new_board._perft(depth - 1, false))
+            } else {
+                None
+            }
+        };
+
+        let pseudo_moves = self.pseudolegal_move_list();
 
+        #[cfg(not(target_arch = "wasm32"))]
         if multithread {
-            pseudo_moves.into_par_iter().filter_map(|mv| {
-                let new_board = self.make_move(&mv);
-                if matches!(mv, Move::LongCastle | Move::ShortCastle) || !new_board.is_check(self.turn_color()) {
-                    Some(new_board._perft(depth - 1, false))
-                } else {
-                    None
-                }
-            }).sum()
-        } else {
-            pseudo_moves.into_iter().filter_map(|mv| {
-                let new_board = self.make_move(&mv);
-                if matches!(mv, Move::LongCastle | Move::ShortCastle) || !new_board.is_check(self.turn_color()) {
-                    Some(new_board._perft(depth - 1, false))
-                } else {
-                    None
-                }
-            }).sum()
+            return pseudo_moves.par_iter().copied().filter_map(count_move).sum();
         }
+
+        pseudo_moves.into_iter().filter_map(count_move).sum()
     }
 }
 
@@ -543,6 +934,54 @@ impl Default for Board {
     }
 }
 
+// Two boards are equal if they represent the same position, in the same sense
+// used for threefold repetition: the same pieces on the same squares, the same
+// side to move, castling rights and e.p. captures available. The move counters,
+// including the one for the fifty-move rule, are not compared.
+impl PartialEq for Board {
+    fn eq(&self, other: &Self) -> bool {
+        self.white_pieces == other.white_pieces && self.black_pieces == other.black_pieces
+            && self.turn == other.turn && self.castling_rights == other.castling_rights
+            && self.capturable_ep_square() == other.capturable_ep_square()
+    }
+}
+
+impl Eq for Board {}
+
+// The zobrist key depends on exactly the same things as the equality
+impl Hash for Board {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.zobrist_key.hash(state);
+    }
+}
+
+// Boards are serialized as their FEN string. The move history is not part
+// of the board, GameRecord stores it next to the board when it's needed.
+#[cfg(feature = "serde")]
+impl Serialize for Board {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.fen())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> Deserialize<'de> for Board {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let fen = String::deserialize(deserializer)?;
+        Self::from_fen(&fen).map_err(de::Error::custom)
+    }
+}
+
+// Much more compact than Display, which draws the whole board
+impl Debug for Board {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("Board")
+            .field("fen", &self.fen())
+            .field("zobrist_key", &format_args!("{:#018x}", self.zobrist_key))
+            .finish()
+    }
+}
+
 impl Display for Board {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         // Dump the pieces from the bitboards into an 8x8 array
@@ -0,0 +1,31 @@
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::{Board, Move};
+
+// A position together with the zobrist keys of the positions played before,
+// which is all that is needed to continue a game, including the detection of
+// draws by repetition. It is serialized as { "fen": ..., "history": [...] }
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct GameRecord {
+    #[cfg_attr(feature = "serde", serde(rename = "fen"))]
+    pub board: Board,
+    pub history: Vec<u64>,
+}
+
+impl GameRecord {
+    pub fn new(board: Board) -> Self {
+        Self { history: vec![board.zobrist_key()], board }
+    }
+
+    // Plays a move, adding the new position to the history
+    pub fn make_move(&mut self, mv: &Move) {
+        self.board = self.board.make_move(mv);
+        self.history.push(self.board.zobrist_key());
+    }
+
+    pub fn is_threefold_repetition(&self) -> bool {
+        self.board.is_threefold_repetition(&self.history)
+    }
+}
@@ -1,6 +1,10 @@
 mod chess_board;
+mod game_record;
 mod bitboard;
 mod movegen;
+mod move_list;
 
 pub use chess_board::{Board, Pieces};
-pub use bitboard::BitBoard;
\ No newline at end of file
+pub use bitboard::BitBoard;
+pub use game_record::GameRecord;
+pub use move_list::{MoveList, MAX_MOVES};
\ No newline at end of file
@@ -0,0 +1,74 @@
+use std::ops::Deref;
+
+use crate::game_elements::Move;
+
+// Upper bound for the number of moves in a position. The most known in a
+// legal position is 218, so this leaves room for pseudolegal ones too.
+pub const MAX_MOVES: usize = 256;
+
+// A list of moves backed by a fixed-size array, so that generating them
+// doesn't need to allocate memory. It dereferences to a slice of the moves.
+#[derive(Clone, Copy)]
+pub struct MoveList {
+    moves: [Move; MAX_MOVES],
+    len: usize,
+}
+
+impl MoveList {
+    pub fn new() -> Self {
+        Self { moves: [Move::ShortCastle; MAX_MOVES], len: 0 }
+    }
+
+    pub fn push(&mut self, mv: Move) {
+        self.moves[self.len] = mv;
+        self.len += 1;
+    }
+
+    // Keeps only the moves that satisfy the predicate, in the same order
+    pub fn retain<F: FnMut(&Move) -> bool>(&mut self, mut keep: F) {
+        let mut kept = 0;
+        for i in 0..self.len {
+            if keep(&self.moves[i]) {
+                self.moves[kept] = self.moves[i];
+                kept += 1;
+            }
+        }
+        self.len = kept;
+    }
+
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+}
+
+impl Default for MoveList {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Deref for MoveList {
+    type Target = [Move];
+
+    fn deref(&self) -> &[Move] {
+        &self.moves[..self.len]
+    }
+}
+
+impl IntoIterator for MoveList {
+    type Item = Move;
+    type IntoIter = std::iter::Take<std::array::IntoIter<Move, MAX_MOVES>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.moves.into_iter().take(self.len)
+    }
+}
+
+impl<'a> IntoIterator for &'a MoveList {
+    type Item = &'a Move;
+    type IntoIter = std::slice::Iter<'a, Move>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
@@ -1,9 +1,19 @@
-use crate::board::{Board, BitBoard};
+use crate::board::{Board, BitBoard, MoveList};
 use crate::game_elements::{Color, Color::*, PieceType::*, Move};
 use crate::magic;
 
 use super::Pieces;
 
+// Information about the position of the side to move that allows us to tell
+// whether a pseudolegal move leaves its king in check, without making it
+pub struct LegalityCheck<'a> {
+    board: &'a Board,
+    king: u8,
+    pinned: BitBoard,
+    check_targets: BitBoard, // Squares that stop the current check, if any
+    double_check: bool,
+}
+
 // Bitboards that have 1's in the required spaces to castle for
 // both colors, and those that must not be in check to castle
 const WHITE_SHORT_CASTLE_BB: BitBoard = BitBoard::new(6);
@@ -21,13 +31,44 @@ const WHITE_PROMOTION_RANK: BitBoard = BitBoard::new(0xFF00000000000000);
 const BLACK_PROMOTION_RANK: BitBoard = BitBoard::new(0x00000000000000FF);
 
 // Generates all pseudolegal moves
-pub fn get_pseudolegal_moves(board: &Board, color: Color) -> Vec<Move> {
+pub fn get_pseudolegal_moves(board: &Board, color: Color, moves: &mut MoveList) {
+    generate_moves(board, color, BitBoard::ones(), BitBoard::ones(), true, moves);
+}
+
+// Generates the moves that may get the king of the side to move out of check:
+// capturing the checker or blocking it with a single checker, and moving the
+// king to a square that isn't attacked. Pins are not considered, like in the
+// rest of pseudolegal moves.
+pub fn get_evasions(board: &Board, moves: &mut MoveList) {
+    let color = board.turn_color();
+    let king = board.get_pieces(color).king.first_piece_index();
+    let checkers = get_attackers(board, king, !color, board.get_all_bitboard());
+
+    // In double check, only the king can move
+    let targets = match checkers.count() {
+        1 => checkers | squares_between(king, checkers.first_piece_index()),
+        _ => BitBoard::default(),
+    };
+
+    // The king is removed from the board, since it can't block
+    // the sliders that are attacking it
+    let occupied = board.get_all_bitboard() & !BitBoard::from_square(king);
+    let king_targets = magic::king_moves(king as usize).piece_indices()
+        .filter(|&to| get_attackers(board, to, !color, occupied).is_empty())
+        .fold(BitBoard::default(), |bb, to| bb | BitBoard::from_square(to));
+
+    generate_moves(board, color, targets, king_targets, false, moves);
+}
+
+// Generates pseudolegal moves whose destination is in `targets`, or `king_targets`
+// for the king. Castling moves are only generated if `castling` is true.
+fn generate_moves(board: &Board, color: Color, targets: BitBoard, king_targets: BitBoard, castling: bool, moves: &mut MoveList) {
     let pieces = board.get_pieces(color);
     let enemy_pieces = board.get_color_bitboard(!color);
     let friendly_pieces_mask = !board.get_color_bitboard(color);
     let all_pieces = board.get_all_bitboard();
 
-    let mut moves = generate_normal_moves(pieces, all_pieces, friendly_pieces_mask);
+    generate_normal_moves(pieces, all_pieces, friendly_pieces_mask & targets, friendly_pieces_mask & king_targets, moves);
 
     // Next, castling. Legality check of castling is done here too
     let (short_bb, long_bb, short_checks, long_checks) = match color {
@@ -37,27 +78,29 @@ pub fn get_pseudolegal_moves(board: &Board, color: Color) -> Vec<Move> {
                   BLACK_SHORT_CASTLE_CHECKS, BLACK_LONG_CASTLE_CHECKS),
     };
 
-    let attackers = board.get_attack_bitboard(!color);
+    // Attacks on the squares the king goes through are only looked up
+    // if castling is otherwise possible, since it's the expensive part
+    let is_attacked = |squares: BitBoard| squares.piece_indices()
+        .any(|sq| is_attacked(board, sq, !color, all_pieces));
 
-    if board.castling_info().can_castle_kingside(color) && (all_pieces & short_bb).is_empty()
-        && (attackers & short_checks).is_empty()  {
+    if castling && board.castling_info().can_castle_kingside(color) && (all_pieces & short_bb).is_empty()
+        && !is_attacked(short_checks)  {
         moves.push(Move::ShortCastle);
     }
 
-    if board.castling_info().can_castle_queenside(color) && (all_pieces & long_bb).is_empty()
-        && (attackers & long_checks).is_empty() {
+    if castling && board.castling_info().can_castle_queenside(color) && (all_pieces & long_bb).is_empty()
+        && !is_attacked(long_checks) {
         moves.push(Move::LongCastle);
     }
 
     // Finally, pawns. The funniest of pieces.
-    // We need an aux vec to later transform the moves that end up in the
-    // last rank to promotion moves
-    let mut pawn_moves = Vec::with_capacity(50);
     let ep_square = board.ep_square();
     pieces.pawns.piece_indices().for_each(|from| {
-        // Captures, which must target either an enemy piece or the e.p. square
-        let cap_bb = magic::pawn_attacks(from as usize, color) & (enemy_pieces | ep_square);
-        pawn_moves.extend(cap_bb.piece_indices().map(|to| Move::Normal { from, to }));
+        // Captures, which must target either an enemy piece or the e.p. square.
+        // E.p. captures are kept even if the square isn't a target, since
+        // the pawn they capture may be the one giving check.
+        let cap_bb = magic::pawn_attacks(from as usize, color) & ((enemy_pieces & targets) | ep_square);
+        cap_bb.piece_indices().for_each(|to| push_pawn_move(moves, from, to, color));
 
         // Next, pushes. Going one step forward is always an option, if nothing is
         // in the way
@@ -71,28 +114,15 @@ pub fn get_pseudolegal_moves(board: &Board, color: Color) -> Vec<Move> {
             push_bb &= !((all_pieces & SIXTH_RANK_MASK) >> 8);
         }
 
-        pawn_moves.extend(push_bb.piece_indices().map(|to| Move::Normal { from, to }));
+        // The double push is disabled above using all pieces, so we
+        // can't filter the targets before that
+        push_bb &= targets;
+        push_bb.piece_indices().for_each(|to| push_pawn_move(moves, from, to, color));
     });
-
-    // Transform the pawn moves into promotions if needed
-    moves.extend(pawn_moves.into_iter().flat_map(|mv| {
-        if in_promotion_rank(mv.to(), color) {
-            vec![
-                Move::PawnPromotion { from: mv.from(), to: mv.to(), promote_to: Queen },
-                Move::PawnPromotion { from: mv.from(), to: mv.to(), promote_to: Rook },
-                Move::PawnPromotion { from: mv.from(), to: mv.to(), promote_to: Bishop },
-                Move::PawnPromotion { from: mv.from(), to: mv.to(), promote_to: Knight }
-            ].into_iter()
-        } else {
-            vec![mv].into_iter()
-        }
-    }));
-
-    moves
 }
 
 // Generates pseudolegal captures and promotions only
-pub fn get_pseudolegal_caps_proms(board: &Board) -> Vec<Move> {
+pub fn get_pseudolegal_caps_proms(board: &Board, moves: &mut MoveList) {
     let color = board.turn_color();
     let pieces = board.get_pieces(color);
     let enemy_pieces = board.get_color_bitboard(!color);
@@ -100,7 +130,7 @@ pub fn get_pseudolegal_caps_proms(board: &Board) -> Vec<Move> {
 
     // Generate only captures by providing the location of enemy pieces
     // as a mask
-    let mut moves = generate_normal_moves(pieces, all_pieces, enemy_pieces);
+    generate_normal_moves(pieces, all_pieces, enemy_pieces, enemy_pieces, moves);
 
     // Generate capturing pawn moves and promotions
     let prom_rank = match color {
@@ -108,34 +138,27 @@ pub fn get_pseudolegal_caps_proms(board: &Board) -> Vec<Move> {
         Black => BLACK_PROMOTION_RANK,
     };
 
-    let mut pawn_moves = Vec::with_capacity(50);
-
     pieces.pawns.piece_indices().for_each(|from| {
         // Captures, which must target either an enemy piece or the e.p. square
         let cap_bb = magic::pawn_attacks(from as usize, color) & (enemy_pieces | board.ep_square());
-        pawn_moves.extend(cap_bb.piece_indices().map(|to| Move::Normal { from, to }));
+        cap_bb.piece_indices().for_each(|to| push_pawn_move(moves, from, to, color));
 
         // Pushes that end up in the promotion rank
         let push_bb = magic::pawn_pushes(from as usize, color) & !all_pieces & prom_rank;
-        pawn_moves.extend(push_bb.piece_indices().map(|to| Move::Normal { from, to }));
+        push_bb.piece_indices().for_each(|to| push_pawn_move(moves, from, to, color));
     });
+}
 
-    // Transform the pawn moves into promotions if needed
-    moves.extend(pawn_moves.into_iter().flat_map(|mv| {
-        if in_promotion_rank(mv.to(), color) {
-            vec![
-                Move::PawnPromotion { from: mv.from(), to: mv.to(), promote_to: Queen },
-                Move::PawnPromotion { from: mv.from(), to: mv.to(), promote_to: Rook },
-                Move::PawnPromotion { from: mv.from(), to: mv.to(), promote_to: Bishop },
-                Move::PawnPromotion { from: mv.from(), to: mv.to(), promote_to: Knight }
-            ].into_iter()
-        } else {
-            vec![mv].into_iter()
+// Adds a pawn move, turning it into the four possible promotions
+// if it ends up in the last rank
+fn push_pawn_move(moves: &mut MoveList, from: u8, to: u8, color: Color) {
+    if in_promotion_rank(to, color) {
+        for promote_to in [Queen, Rook, Bishop, Knight] {
+            moves.push(Move::PawnPromotion { from, to, promote_to });
         }
-    }));
-
-
-    moves
+    } else {
+        moves.push(Move::Normal { from, to });
+    }
 }
 
 pub fn get_controlled_squares(board: &Board, color: Color) -> BitBoard {
@@ -153,43 +176,186 @@ pub fn get_controlled_squares(board: &Board, color: Color) -> BitBoard {
     controlled
 }
 
+impl<'a> LegalityCheck<'a> {
+    pub fn new(board: &'a Board) -> Self {
+        let color = board.turn_color();
+        let king = board.get_pieces(color).king.first_piece_index();
+        let checkers = get_attackers(board, king, !color, board.get_all_bitboard());
+
+        // With a single checker, the other pieces must either capture or block it
+        let check_targets = match checkers.count() {
+            0 => BitBoard::ones(),
+            _ => checkers | squares_between(king, checkers.first_piece_index()),
+        };
+
+        Self { board, king, pinned: get_pinned(board, color), check_targets, double_check: checkers.count() > 1 }
+    }
+
+    pub fn is_legal(&self, mv: &Move) -> bool {
+        let (from, to) = match mv {
+            Move::Normal { from, to } | Move::PawnPromotion { from, to, .. } => (*from, *to),
+            // Castling through or out of check is already ruled out when generating it
+            Move::ShortCastle | Move::LongCastle => return true,
+        };
+
+        let color = self.board.turn_color();
+        let to_bb = BitBoard::from_square(to);
+
+        // The king must move to a square that isn't attacked, and it's removed from
+        // the board first, since it can't block the sliders that are attacking it
+        if from == self.king {
+            let occupied = self.board.get_all_bitboard() & !BitBoard::from_square(from);
+            return get_attackers(self.board, to, !color, occupied).is_empty();
+        }
+
+        // In double check, only the king can move
+        if self.double_check {
+            return false;
+        }
+
+        // En passant removes two pieces from the same rank, which may uncover the
+        // king in ways that pins don't account for, so we just make it and see
+        if (to_bb & self.board.ep_square()).is_not_empty() && mv.piece_moving(self.board) == Pawn {
+            return !self.board.make_move(mv).is_check(color);
+        }
+
+        // Pinned pieces can still move along the line between the king and the pinner
+        (to_bb & self.check_targets).is_not_empty()
+            && ((BitBoard::from_square(from) & self.pinned).is_empty() || (line_through(self.king, from) & to_bb).is_not_empty())
+    }
+}
+
+// Pieces of a color that attack a square, given the occupancy of the board.
+// Pieces are found by looking from the square with their own movement, except
+// pawns, which are the diagonal neighbours of the square on the rank behind it.
+// The pawn attack tables can't be used for them, since they are empty for pawns
+// on their own first rank, which are needed to find attacks on the last rank.
+pub fn get_attackers(board: &Board, square: u8, color: Color, occupied: BitBoard) -> BitBoard {
+    let sq = square as usize;
+    let pieces = board.get_pieces(color);
+    let diagonal_sliders = pieces.bishops | pieces.queens;
+    let orthogonal_sliders = pieces.rooks | pieces.queens;
+
+    let rank = square / 8;
+    let pawn_rank = match color {
+        White if rank > 0 => BitBoard::new(0xFF << ((rank - 1) * 8)),
+        Black if rank < 7 => BitBoard::new(0xFF << ((rank + 1) * 8)),
+        _ => BitBoard::default(),
+    };
+
+    (magic::bishop_moves(sq, BitBoard::ones()) & pawn_rank & pieces.pawns)
+        | (magic::knight_moves(sq) & pieces.knights)
+        | (magic::king_moves(sq) & pieces.king)
+        | (magic::bishop_moves(sq, occupied) & diagonal_sliders)
+        | (magic::rook_moves(sq, occupied) & orthogonal_sliders)
+}
+
+// Whether any piece of a color attacks a square. It's the same as looking for
+// attackers, but it stops at the first kind of piece found, and the sliders,
+// which are the most expensive to look up, are left for last.
+pub fn is_attacked(board: &Board, square: u8, color: Color, occupied: BitBoard) -> bool {
+    let sq = square as usize;
+    let pieces = board.get_pieces(color);
+
+    if (magic::knight_moves(sq) & pieces.knights).is_not_empty()
+        || (magic::king_moves(sq) & pieces.king).is_not_empty() {
+        return true;
+    }
+
+    let rank = square / 8;
+    let pawn_rank = match color {
+        White if rank > 0 => BitBoard::new(0xFF << ((rank - 1) * 8)),
+        Black if rank < 7 => BitBoard::new(0xFF << ((rank + 1) * 8)),
+        _ => BitBoard::default(),
+    };
+
+    let diagonal_sliders = pieces.bishops | pieces.queens;
+    let orthogonal_sliders = pieces.rooks | pieces.queens;
+
+    (magic::bishop_moves(sq, BitBoard::ones()) & pawn_rank & pieces.pawns).is_not_empty()
+        || (diagonal_sliders.is_not_empty() && (magic::bishop_moves(sq, occupied) & diagonal_sliders).is_not_empty())
+        || (orthogonal_sliders.is_not_empty() && (magic::rook_moves(sq, occupied) & orthogonal_sliders).is_not_empty())
+}
+
+// Pieces of a color that are pinned to their king by an enemy slider. The sliders
+// that would attack the king if only enemy pieces were on the board are the
+// candidates, and the pin exists if there is exactly one friendly piece between them.
+pub fn get_pinned(board: &Board, color: Color) -> BitBoard {
+    let king = board.get_pieces(color).king.first_piece_index();
+    let enemies = board.get_pieces(!color);
+    let enemy_pieces = board.get_color_bitboard(!color);
+    let friendly_pieces = board.get_color_bitboard(color);
+
+    let orthogonal = magic::rook_moves(king as usize, enemy_pieces) & (enemies.rooks | enemies.queens);
+    let diagonal = magic::bishop_moves(king as usize, enemy_pieces) & (enemies.bishops | enemies.queens);
+
+    (orthogonal | diagonal).piece_indices()
+        .map(|from| squares_between(king, from) & friendly_pieces)
+        .filter(|blockers| blockers.count() == 1)
+        .fold(BitBoard::default(), |pinned, blockers| pinned | blockers)
+}
+
+// Squares strictly between two squares, if they are in the same line
+fn squares_between(from: u8, to: u8) -> BitBoard {
+    // They are the squares where the rays from both of them meet
+    let (from_bb, to_bb) = (BitBoard::from_square(from), BitBoard::from_square(to));
+    let from_rook = magic::rook_moves(from as usize, to_bb);
+    let from_bishop = magic::bishop_moves(from as usize, to_bb);
+
+    if (from_rook & to_bb).is_not_empty() {
+        from_rook & magic::rook_moves(to as usize, from_bb)
+    } else if (from_bishop & to_bb).is_not_empty() {
+        from_bishop & magic::bishop_moves(to as usize, from_bb)
+    } else {
+        BitBoard::default()
+    }
+}
+
+// All the squares in the line that goes through two squares, except for them,
+// assuming that they are either in the same rank, file or diagonal
+fn line_through(a: u8, b: u8) -> BitBoard {
+    let empty = BitBoard::default();
+    if a / 8 == b / 8 || a % 8 == b % 8 {
+        magic::rook_moves(a as usize, empty) & magic::rook_moves(b as usize, empty)
+    } else {
+        magic::bishop_moves(a as usize, empty) & magic::bishop_moves(b as usize, empty)
+    }
+}
+
 // Generates moves for all pieces except pawns and castling moves using a mask
 // This filter will be the inverse of the position of friendly pieces in normal
 // move generation (to avoid capturing them), or the position of enemy pieces
-// when generating only capture moves
-fn generate_normal_moves(pieces: &Pieces, all_pieces: BitBoard, mask: BitBoard) -> Vec<Move> {
+// when generating only capture moves. The king uses its own mask, since it
+// escapes checks differently than the rest of pieces.
+fn generate_normal_moves(pieces: &Pieces, all_pieces: BitBoard, mask: BitBoard, king_mask: BitBoard, moves: &mut MoveList) {
+    let mut add_moves = |from: u8, move_bb: BitBoard| {
+        move_bb.piece_indices().for_each(|to| moves.push(Move::Normal { from, to }));
+    };
+
     // Queen
-    let queen_moves = pieces.queens.piece_indices().flat_map(|from| {
-        let move_bb = magic::queen_moves(from as usize, all_pieces) & mask;
-        move_bb.piece_indices().map(move |to| Move::Normal { from, to })
+    pieces.queens.piece_indices().for_each(|from| {
+        add_moves(from, magic::queen_moves(from as usize, all_pieces) & mask);
     });
 
-    // Rook
-    let rook_moves = pieces.rooks.piece_indices().flat_map(|from| {
-        let move_bb = magic::rook_moves(from as usize, all_pieces) & mask;
-        move_bb.piece_indices().map(move |to| Move::Normal { from, to })
+    // Bishop
+    pieces.bishops.piece_indices().for_each(|from| {
+        add_moves(from, magic::bishop_moves(from as usize, all_pieces) & mask);
     });
 
-    // Bishop
-    let bishop_moves = pieces.bishops.piece_indices().flat_map(|from| {
-        let move_bb = magic::bishop_moves(from as usize, all_pieces) & mask;
-        move_bb.piece_indices().map(move |to| Move::Normal { from, to })
+    // Rook
+    pieces.rooks.piece_indices().for_each(|from| {
+        add_moves(from, magic::rook_moves(from as usize, all_pieces) & mask);
     });
 
     // Horsey
-    let knight_moves = pieces.knights.piece_indices().flat_map(|from| {
-        let move_bb = magic::knight_moves(from as usize) & mask;
-        move_bb.piece_indices().map(move |to| Move::Normal { from, to })
+    pieces.knights.piece_indices().for_each(|from| {
+        add_moves(from, magic::knight_moves(from as usize) & mask);
     });
 
     // King
-    let king_moves = pieces.king.piece_indices().flat_map(|from| {
-        let move_bb = magic::king_moves(from as usize) & mask;
-        move_bb.piece_indices().map(move |to| Move::Normal { from, to })
+    pieces.king.piece_indices().for_each(|from| {
+        add_moves(from, magic::king_moves(from as usize) & king_mask);
     });
-
-    queen_moves.chain(bishop_moves).chain(rook_moves)
-        .chain(knight_moves).chain(king_moves).collect()
 }
 
 fn in_promotion_rank(pos: u8, color: Color) -> bool {
@@ -60,14 +60,60 @@ pub fn read_fen(fen: &str) -> Result<FENInfo, String> {
     Ok(fen_info)
 }
 
+// Creates the FEN of a Chess960 starting position from its number, from 0 to 959,
+// as in https://en.wikipedia.org/wiki/Fischer_random_chess_numbering_scheme.
+// Number 518 is the standard starting position.
+// Castling is only implemented for kings and rooks on their usual squares, so
+// the castling rights are only given for the rooks on a1/h1 if the king is on e1.
+pub fn chess960_fen(number: u16) -> Result<String, String> {
+    if number >= 960 {
+        return Err(format!("Invalid Chess960 position number: {number}"));
+    }
+
+    let mut rank: [Option<char>; 8] = [None; 8];
+    let mut n = number as usize;
+
+    // The bishops go on the light (b, d, f, h) and dark (a, c, e, g) squares,
+    // then the queen and the two knights on the remaining empty squares
+    rank[n % 4 * 2 + 1] = Some('B');
+    n /= 4;
+    rank[n % 4 * 2] = Some('B');
+    n /= 4;
+    place_on_empty(&mut rank, n % 6, 'Q');
+    n /= 6;
+
+    let (first, second) = [(0, 1), (0, 2), (0, 3), (0, 4), (1, 2), (1, 3), (1, 4), (2, 3), (2, 4), (3, 4)][n];
+    // The second knight is placed first, so that the first one doesn't shift it
+    place_on_empty(&mut rank, second, 'N');
+    place_on_empty(&mut rank, first, 'N');
+
+    // The king always ends up between the rooks
+    for piece in ['R', 'K', 'R'] {
+        place_on_empty(&mut rank, 0, piece);
+    }
+
+    let white: String = rank.iter().map(|piece| piece.unwrap()).collect();
+    let castling: String = [(7, 'K'), (0, 'Q')].into_iter()
+        .filter(|&(file, _)| rank[4] == Some('K') && rank[file] == Some('R'))
+        .map(|(_, right)| right).collect();
+    let castling = match castling.is_empty() {
+        true => "-".to_owned(),
+        false => format!("{castling}{}", castling.to_lowercase()),
+    };
+
+    Ok(format!("{}/pppppppp/8/8/8/8/PPPPPPPP/{white} w {castling} - 0 1", white.to_lowercase()))
+}
+
 pub fn create_fen(board: &Board) -> String {
     let pos = (0..8).rev().map(|rank| get_rank_fen(board, rank)).collect::<Vec<_>>().join("/");
     let turn = if board.turn_color() == White { "w" } else { "b" };
     let castling = save_castling(board.castling_info());
-    let ep = if board.ep_square().is_empty() {
+    // Like in the zobrist key, the e.p. square is only relevant if it can be captured
+    let ep_square = board.capturable_ep_square();
+    let ep = if ep_square.is_empty() {
         "-".to_owned()
     } else {
-        Square::new(board.ep_square().first_piece_index()).to_string()
+        Square::new(ep_square.first_piece_index()).to_string()
     };
 
     let fifty_rule = board.fifty_move_rule_counter().to_string();
@@ -203,4 +249,10 @@ fn save_castling(castling_info: &CastlingRights) -> String {
     }
 
     res
-}
\ No newline at end of file
+}
+
+// Places a piece on the n-th empty square of a rank, counting from 0
+fn place_on_empty(rank: &mut [Option<char>; 8], n: usize, piece: char) {
+    let square = rank.iter_mut().filter(|sq| sq.is_none()).nth(n).unwrap();
+    *square = Some(piece);
+}
@@ -1,3 +1,3 @@
 pub mod fen_utils;
 
-pub use fen_utils::{DEFAULT_FEN, read_fen};
\ No newline at end of file
+pub use fen_utils::{DEFAULT_FEN, read_fen, chess960_fen, FENInfo};
\ No newline at end of file
@@ -1,6 +1,6 @@
 use super::Color;
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
 pub struct CastlingRights {
     // We use the last 4 bits of an u8: XXXXABCD
     // A -> White kingside
@@ -1,8 +1,10 @@
 use std::fmt::Display;
 use std::ops::Not;
+#[cfg(feature = "serde")]
 use serde::Serialize;
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
 pub enum Color {
     White,
     Black,
@@ -0,0 +1,35 @@
+use super::Color;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameResult {
+    WhiteWins,
+    BlackWins,
+    Draw(DrawReason),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DrawReason {
+    Stalemate,
+    InsufficientMaterial,
+    FiftyMoveRule,
+    SeventyFiveMoveRule,
+    FivefoldRepetition,
+}
+
+impl GameResult {
+    pub fn win_for(color: Color) -> Self {
+        match color {
+            Color::White => Self::WhiteWins,
+            Color::Black => Self::BlackWins,
+        }
+    }
+
+    // Result of the game as used in PGN files
+    pub fn as_pgn_str(&self) -> &'static str {
+        match self {
+            Self::WhiteWins => "1-0",
+            Self::BlackWins => "0-1",
+            Self::Draw(_) => "1/2-1/2",
+        }
+    }
+}
@@ -3,9 +3,13 @@ pub mod movement;
 pub mod position;
 pub mod piece_type;
 pub mod castling;
+pub mod game_result;
+pub mod move_effect;
 
 pub use color::Color;
 pub use movement::Move;
 pub use position::Square;
 pub use piece_type::PieceType;
-pub use castling::CastlingRights;
\ No newline at end of file
+pub use castling::CastlingRights;
+pub use game_result::{GameResult, DrawReason};
+pub use move_effect::MoveEffect;
\ No newline at end of file
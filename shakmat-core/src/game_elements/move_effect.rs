@@ -0,0 +1,29 @@
+use super::{Move, PieceType, PieceType::*};
+use crate::board::{Board, BitBoard};
+
+// What happened on the board when a move was made, which is useful for
+// front-ends that animate the captures or annotate the moves
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MoveEffect {
+    pub captured: Option<PieceType>, // Including the pawn captured e.p.
+    pub was_castle: bool,
+    pub was_en_passant: bool,
+    pub gives_check: bool,
+}
+
+impl MoveEffect {
+    // Effects of a legal move, given the boards before and after making it
+    pub(crate) fn new(board: &Board, movement: &Move, next_board: &Board) -> Self {
+        let was_castle = matches!(movement, Move::ShortCastle | Move::LongCastle);
+        // The e.p. square is always empty, so the captured pawn isn't on it
+        let was_en_passant = matches!(movement, Move::Normal { to, .. }
+            if (BitBoard::from_square(*to) & board.ep_square()).is_not_empty() && movement.piece_moving(board) == Pawn);
+
+        Self {
+            captured: if was_en_passant { Some(Pawn) } else { movement.piece_captured(board) },
+            was_castle,
+            was_en_passant,
+            gives_check: next_board.is_check(next_board.turn_color()),
+        }
+    }
+}
@@ -1,13 +1,14 @@
-use std::fmt::{Display, Formatter};
+use std::fmt::{Debug, Display, Formatter};
+#[cfg(feature = "serde")]
 use serde::{Serialize, Serializer};
 
-use super::{PieceType, Square, PieceType::*};
+use super::{Color, PieceType, Square, PieceType::*};
 use crate::board::{Board, BitBoard};
 
 // Avoid clashes between the core Result and the formatter Result
 type FmtResult = std::fmt::Result;
 
-#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[derive(Clone, Copy, PartialEq, Eq)]
 pub enum Move {
     Normal { from: u8, to: u8 },
     PawnPromotion { from: u8, to: u8, promote_to: PieceType },
@@ -48,6 +49,22 @@ impl Move {
         }
     }
 
+    // Quiet moves neither capture anything nor promote a pawn
+    pub fn is_quiet(&self, board: &Board) -> bool {
+        !self.is_capture(board) && self.promotion_piece().is_none()
+    }
+
+    pub fn promotion_piece(&self) -> Option<PieceType> {
+        match self {
+            Self::PawnPromotion { promote_to, .. } => Some(*promote_to),
+            _ => None
+        }
+    }
+
+    pub fn is_underpromotion(&self) -> bool {
+        matches!(self.promotion_piece(), Some(piece) if piece != Queen)
+    }
+
     pub fn piece_moving(&self, board: &Board) -> PieceType {
         match self {
             Self::Normal {from, ..} => board.piece_on(*from).unwrap(),
@@ -89,6 +106,139 @@ impl Move {
             _ => Err("Invalid move".to_owned()),
         }
     }
+
+    // Parses a move in Standard Algebraic Notation (Nf3, exd5, O-O-O, e8=Q...)
+    // SAN only contains the information needed to tell moves apart in a
+    // given position, so we need the board to find which piece is moving
+    pub fn from_san(san: &str, board: &Board) -> Result<Self, String> {
+        // Check and mate markers, as well as annotations, are not needed
+        let san = san.trim().trim_end_matches(['+', '#', '!', '?']);
+
+        match san {
+            "O-O" | "0-0" => return find_san_match(board, san, |mv| matches!(mv, Self::ShortCastle)),
+            "O-O-O" | "0-0-0" => return find_san_match(board, san, |mv| matches!(mv, Self::LongCastle)),
+            _ => {}
+        };
+
+        if !san.is_ascii() {
+            return Err(format!("Invalid SAN move: {san}"));
+        }
+
+        // Split the promotion piece, if any. Both "e8=Q" and "e8Q" are accepted
+        let (body, promote_to) = match san.rsplit_once('=') {
+            Some((body, piece)) => (body, Some(san_promotion_piece(piece, san)?)),
+            None if san.len() > 2 && san.ends_with(['Q', 'R', 'B', 'N']) => {
+                (&san[..san.len() - 1], Some(san_promotion_piece(&san[san.len() - 1..], san)?))
+            },
+            None => (san, None),
+        };
+
+        // The piece letter is optional, pawn moves don't have one
+        let (piece, body) = match body.chars().next() {
+            Some('N') => (Knight, &body[1..]),
+            Some('B') => (Bishop, &body[1..]),
+            Some('R') => (Rook, &body[1..]),
+            Some('Q') => (Queen, &body[1..]),
+            Some('K') => (King, &body[1..]),
+            _ => (Pawn, body),
+        };
+
+        // The last two chars are the destination square, whatever comes
+        // before them (except the capture marker) disambiguates the origin
+        if body.len() < 2 {
+            return Err(format!("Invalid SAN move: {san}"));
+        }
+
+        let to = Square::from_notation(&body[body.len() - 2..])?.square();
+        let mut from_file = None;
+        let mut from_rank = None;
+
+        for ch in body[..body.len() - 2].chars().filter(|&ch| ch != 'x') {
+            match ch {
+                'a'..='h' => from_file = Some(ch as u8 - b'a'),
+                '1'..='8' => from_rank = Some(ch as u8 - b'1'),
+                _ => return Err(format!("Invalid SAN move: {san}")),
+            }
+        }
+
+        if piece != Pawn && promote_to.is_some() {
+            return Err(format!("Only pawns can promote: {san}"));
+        }
+
+        find_san_match(board, san, |mv| {
+            let (mv_from, mv_to, mv_promotion) = match mv {
+                Self::Normal { from, to } => (*from, *to, None),
+                Self::PawnPromotion { from, to, promote_to } => (*from, *to, Some(*promote_to)),
+                _ => return false,
+            };
+
+            let from_square = Square::new(mv_from);
+            mv_to == to && mv_promotion == promote_to && mv.piece_moving(board) == piece
+                && from_file.is_none_or(|file| file == from_square.file())
+                && from_rank.is_none_or(|rank| rank == from_square.rank())
+        })
+    }
+
+    // Creates the Standard Algebraic Notation for this move, which is
+    // assumed to be legal in the provided board
+    pub fn to_san(&self, board: &Board) -> String {
+        let mut san = match self {
+            Self::ShortCastle => "O-O".to_owned(),
+            Self::LongCastle => "O-O-O".to_owned(),
+            Self::Normal { from, to } | Self::PawnPromotion { from, to, .. } => {
+                let piece = self.piece_moving(board);
+                let from_square = Square::new(*from);
+                let capture = if self.is_capture(board) { "x" } else { "" };
+                let mut res = String::new();
+
+                if piece == Pawn {
+                    // Pawn captures are identified by the file they come from
+                    if self.is_capture(board) {
+                        res.push_str(&from_square.to_string()[..1]);
+                    }
+                } else {
+                    res.push(piece.as_fen_char(Color::White));
+
+                    // Other pieces of the same type that can move to the same square
+                    let others: Vec<Square> = board.legal_moves_ignoring_draws().into_iter()
+                        .filter(|mv| matches!(mv, Self::Normal { .. }) && mv.to() == *to
+                                     && mv.from() != *from && mv.piece_moving(board) == piece)
+                        .map(|mv| Square::new(mv.from()))
+                        .collect();
+
+                    // Disambiguate using the file if possible, then the rank, then both
+                    if !others.is_empty() {
+                        let from_str = from_square.to_string();
+                        if others.iter().all(|sq| sq.file() != from_square.file()) {
+                            res.push_str(&from_str[..1]);
+                        } else if others.iter().all(|sq| sq.rank() != from_square.rank()) {
+                            res.push_str(&from_str[1..]);
+                        } else {
+                            res.push_str(&from_str);
+                        }
+                    }
+                }
+
+                res.push_str(capture);
+                res.push_str(&Square::new(*to).to_string());
+
+                if let Self::PawnPromotion { promote_to, .. } = self {
+                    res.push('=');
+                    res.push(promote_to.as_fen_char(Color::White));
+                }
+
+                res
+            }
+        };
+
+        // Finally, add the check or checkmate marker
+        let next_board = board.make_move(self);
+        if next_board.is_check(next_board.turn_color()) {
+            san.push(if next_board.is_checkmate() { '#' } else { '+' });
+        }
+
+        san
+    }
 }
 
 impl Display for Move {
@@ -111,7 +261,49 @@ impl Display for Move {
     }
 }
 
+// Same as the derived implementation, but with the names of the squares
+impl Debug for Move {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        let square = |sq: &u8| Square::new(*sq);
+        match self {
+            Self::Normal { from, to } => f.debug_struct("Normal")
+                .field("from", &format_args!("{}", square(from)))
+                .field("to", &format_args!("{}", square(to)))
+                .finish(),
+            Self::PawnPromotion { from, to, promote_to } => f.debug_struct("PawnPromotion")
+                .field("from", &format_args!("{}", square(from)))
+                .field("to", &format_args!("{}", square(to)))
+                .field("promote_to", promote_to)
+                .finish(),
+            Self::ShortCastle => write!(f, "ShortCastle"),
+            Self::LongCastle => write!(f, "LongCastle"),
+        }
+    }
+}
+
+// Looks for the only legal move in the position that matches a parsed SAN move
+fn find_san_match<F: Fn(&Move) -> bool>(board: &Board, san: &str, matches_san: F) -> Result<Move, String> {
+    let mut candidates = board.legal_moves_ignoring_draws().into_iter().filter(matches_san);
+
+    match (candidates.next(), candidates.next()) {
+        (Some(mv), None) => Ok(mv),
+        (Some(_), Some(_)) => Err(format!("Ambiguous move: {san}")),
+        (None, _) => Err(format!("Illegal move: {san}")),
+    }
+}
+
+fn san_promotion_piece(piece: &str, san: &str) -> Result<PieceType, String> {
+    match piece {
+        "Q" => Ok(Queen),
+        "R" => Ok(Rook),
+        "B" => Ok(Bishop),
+        "N" => Ok(Knight),
+        _ => Err(format!("Invalid promotion piece in move: {san}")),
+    }
+}
+
 // Custom serialization and deserialization, following the previous formatting
+#[cfg(feature = "serde")]
 impl Serialize for Move {
     fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
         serializer.serialize_str(&self.to_string())
@@ -2,9 +2,11 @@ mod board;
 mod fen;
 mod game_elements; 
 pub mod magic;
+mod pgn;
 mod zobrist;
 
-pub use board::{Board, BitBoard, Pieces};
-pub use fen::DEFAULT_FEN;
-pub use game_elements::{Move, Color, PieceType, Square};
+pub use board::{Board, BitBoard, GameRecord, Pieces, MoveList, MAX_MOVES};
+pub use fen::{DEFAULT_FEN, chess960_fen};
+pub use pgn::{create_pgn, read_pgn};
+pub use game_elements::{Move, Color, PieceType, Square, GameResult, DrawReason, CastlingRights, MoveEffect};
 pub use magic as move_gen;
\ No newline at end of file
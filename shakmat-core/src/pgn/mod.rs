@@ -0,0 +1,5 @@
+pub mod pgn_reader;
+pub mod pgn_utils;
+
+pub use pgn_reader::read_pgn;
+pub use pgn_utils::create_pgn;
@@ -0,0 +1,116 @@
+use crate::board::Board;
+use crate::game_elements::Move;
+
+// A token from the PGN text, comments and variations are already left out
+enum Token<'a> {
+    Tag(&'a str),
+    Word(&'a str),
+}
+
+// Reads all the games in a PGN text, returning the initial position of each
+// one along with the moves that were played. Only the main line is considered,
+// comments, variations and annotations are skipped.
+pub fn read_pgn(pgn: &str) -> Result<Vec<(Board, Vec<Move>)>, String> {
+    let mut games = vec![];
+    let mut fen = None;
+    let mut game: Option<(Board, Board, Vec<Move>)> = None; // Initial board, current board, moves
+
+    for token in tokenize(pgn)? {
+        match token {
+            Token::Tag(tag) => {
+                // A tag after some moves means that the previous game had no result
+                if let Some((initial, _, moves)) = game.take() {
+                    games.push((initial, moves));
+                }
+
+                let (name, value) = tag.split_once(char::is_whitespace).unwrap_or((tag, ""));
+                if name == "FEN" {
+                    fen = Some(value.trim().trim_matches('"').to_owned());
+                }
+            },
+            Token::Word("1-0" | "0-1" | "1/2-1/2" | "*") => {
+                let (initial, _, moves) = match game.take() {
+                    Some(game) => game,
+                    None => {
+                        let initial = initial_board(fen.as_deref())?;
+                        (initial, initial, vec![])
+                    }
+                };
+
+                games.push((initial, moves));
+                fen = None;
+            },
+            Token::Word(word) if word.starts_with('$') => {}, // Numeric annotation glyph
+            Token::Word(word) => {
+                // Move numbers may be glued to the move itself, like "12.e4"
+                let san = word.rsplit('.').next().unwrap();
+                if san.is_empty() {
+                    continue;
+                }
+
+                if game.is_none() {
+                    let initial = initial_board(fen.take().as_deref())?;
+                    game = Some((initial, initial, vec![]));
+                }
+
+                let (_, board, moves) = game.as_mut().unwrap();
+                let mv = Move::from_san(san, board).map_err(|err| format!("Game {}: {err}", games.len() + 1))?;
+                *board = board.make_move(&mv);
+                moves.push(mv);
+            },
+        }
+    }
+
+    if let Some((initial, _, moves)) = game {
+        games.push((initial, moves));
+    }
+
+    Ok(games)
+}
+
+fn initial_board(fen: Option<&str>) -> Result<Board, String> {
+    fen.map_or_else(|| Ok(Board::default()), Board::from_fen)
+}
+
+// Splits the PGN text into tags and movetext words
+fn tokenize(pgn: &str) -> Result<Vec<Token<'_>>, String> {
+    let mut tokens = vec![];
+    let mut variation_depth = 0;
+    let mut rest = pgn;
+
+    while let Some(ch) = rest.chars().next() {
+        // Comments may appear anywhere, even inside variations
+        let skip_until = match ch {
+            '{' => Some('}'),
+            ';' => Some('\n'),
+            _ => None,
+        };
+
+        if let Some(end) = skip_until {
+            rest = rest.split_once(end).map_or("", |(_, after)| after);
+            continue;
+        }
+
+        match ch {
+            '(' => variation_depth += 1,
+            ')' => variation_depth -= 1,
+            _ if variation_depth > 0 || ch.is_whitespace() => {},
+            '[' => {
+                let (tag, after) = rest[1..].split_once(']').ok_or("Unterminated PGN tag")?;
+                tokens.push(Token::Tag(tag.trim()));
+                rest = after;
+                continue;
+            },
+            _ => {
+                let len = rest.find(|c: char| c.is_whitespace() || "{;()[".contains(c)).unwrap_or(rest.len());
+                tokens.push(Token::Word(&rest[..len]));
+                rest = &rest[len..];
+                continue;
+            },
+        }
+
+        rest = &rest[ch.len_utf8()..];
+    }
+
+    Ok(tokens)
+}
@@ -0,0 +1,68 @@
+use crate::board::Board;
+use crate::fen::DEFAULT_FEN;
+use crate::game_elements::{Color::*, Move};
+
+// Maximum length of the lines in the movetext section, as recommended
+// by the PGN export format
+const MAX_LINE_LENGTH: usize = 80;
+
+// Creates the PGN of a game that started in the provided board and
+// continued with the given moves, which are assumed to be legal
+pub fn create_pgn(initial_board: &Board, moves: &[Move]) -> String {
+    let mut board = *initial_board;
+    let mut tokens = Vec::with_capacity(moves.len() * 3 / 2 + 1);
+
+    for (i, mv) in moves.iter().enumerate() {
+        // White moves are always preceded by the move number, black moves
+        // only if they are the first move in the game
+        if board.turn_color() == White {
+            tokens.push(format!("{}.", board.turn_number()));
+        } else if i == 0 {
+            tokens.push(format!("{}...", board.turn_number()));
+        }
+
+        tokens.push(mv.to_san(&board));
+        board = board.make_move(mv);
+    }
+
+    let result = board.game_result().map_or("*", |res| res.as_pgn_str());
+    tokens.push(result.to_owned());
+
+    // The seven tag roster, plus the initial position if it's not the default one
+    let mut pgn = String::new();
+    for (tag, value) in [("Event", "?"), ("Site", "?"), ("Date", "????.??.??"), ("Round", "?"),
+                         ("White", "?"), ("Black", "?"), ("Result", result)] {
+        pgn.push_str(&format!("[{tag} \"{value}\"]\n"));
+    }
+
+    let initial_fen = initial_board.fen();
+    if initial_fen != DEFAULT_FEN {
+        pgn.push_str(&format!("[SetUp \"1\"]\n[FEN \"{initial_fen}\"]\n"));
+    }
+
+    pgn.push('\n');
+    pgn.push_str(&wrap_movetext(&tokens));
+    pgn.push('\n');
+    pgn
+}
+
+// Joins the movetext tokens, splitting them in lines of bounded length
+fn wrap_movetext(tokens: &[String]) -> String {
+    let mut res = String::new();
+    let mut line_length = 0;
+
+    for token in tokens {
+        if line_length != 0 && line_length + token.len() + 1 > MAX_LINE_LENGTH {
+            res.push('\n');
+            line_length = 0;
+        } else if line_length != 0 {
+            res.push(' ');
+            line_length += 1;
+        }
+
+        res.push_str(token);
+        line_length += token.len();
+    }
+
+    res
+}
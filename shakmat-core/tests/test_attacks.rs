@@ -0,0 +1,110 @@
+use shakmat_core::{BitBoard, Board, Color::*, Move, Square};
+
+fn squares(names: &[&str]) -> BitBoard {
+    names.iter().fold(BitBoard::default(), |bb, name| bb | Square::from_notation(name).unwrap().as_bitboard())
+}
+
+fn square(name: &str) -> u8 {
+    Square::from_notation(name).unwrap().square()
+}
+
+#[test]
+fn attackers_of() {
+    let board = Board::from_fen("4k3/8/2n5/3p1b2/1Q2P3/2N2R2/6B1/4K3 w - - 0 1").unwrap();
+
+    // The bishop on g2 is blocked by the rook, and the queen doesn't reach d5
+    assert!(board.attackers_of(square("d5"), White) == squares(&["e4", "c3"]));
+    assert!(board.attackers_of(square("e4"), White) == squares(&["c3", "b4"]));
+    assert!(board.attackers_of(square("e4"), Black) == squares(&["d5", "f5"]));
+    assert!(board.attackers_of(square("e2"), White) == squares(&["e1", "c3"]));
+    assert!(board.attackers_of(square("b4"), Black) == squares(&["c6"]));
+    assert!(board.attackers_of(square("a8"), White).is_empty());
+}
+
+#[test]
+fn pinned_pieces() {
+    // Two pieces between the king and the slider means that none of them is pinned
+    let board = Board::from_fen("4r2k/8/8/8/8/4P3/4N3/4K3 w - - 0 1").unwrap();
+    assert!(board.pinned_pieces(White).is_empty());
+
+    let board = Board::from_fen("4r2k/8/8/b7/8/2N2Br1/4NP2/4K3 w - - 0 1").unwrap();
+    assert!(board.pinned_pieces(White) == squares(&["e2", "c3"]));
+    assert!(board.pinned_pieces(Black).is_empty());
+
+    // Pieces of the opponent in the line prevent the pin
+    let board = Board::from_fen("4r2k/8/8/b7/1p6/2N5/4N3/4K3 w - - 0 1").unwrap();
+    assert!(board.pinned_pieces(White) == squares(&["e2"]));
+
+    // Black pieces can also be pinned, including by queens
+    let board = Board::from_fen("3qk3/3pn3/8/1B6/8/8/4Q3/4K3 b - - 0 1").unwrap();
+    assert!(board.pinned_pieces(Black) == squares(&["d7", "e7"]));
+    assert!(board.pinned_pieces(White).is_empty());
+}
+
+#[test]
+fn castling_through_attacked_squares() {
+    // The bishop on a6 covers f1, while the knight on e4 covers d2 but no square the king crosses
+    let board = Board::from_fen("4k3/8/b7/8/4n3/8/8/R3K2R w KQ - 0 1").unwrap();
+    let moves = board.legal_moves();
+    assert!(!moves.contains(&Move::ShortCastle));
+    assert!(moves.contains(&Move::LongCastle));
+
+    // Attacks on b1 don't matter, since the king doesn't go through it
+    let board = Board::from_fen("4k3/8/8/8/8/n7/8/R3K2R w KQ - 0 1").unwrap();
+    let moves = board.legal_moves();
+    assert!(moves.contains(&Move::ShortCastle));
+    assert!(moves.contains(&Move::LongCastle));
+    assert!(!board.is_check(White));
+}
+
+#[test]
+fn is_square_attacked() {
+    let fens = [
+        "4k3/8/2n5/3p1b2/1Q2P3/2N2R2/6B1/4K3 w - - 0 1",
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+    ];
+
+    // It must agree with the attackers of every square, for both colors
+    for fen in fens {
+        let board = Board::from_fen(fen).unwrap();
+        for sq in 0..64 {
+            for color in [White, Black] {
+                assert_eq!(board.is_square_attacked(sq, color), board.attackers_of(sq, color).is_not_empty());
+            }
+        }
+    }
+}
+
+#[test]
+fn move_gives_check() {
+    let gives_check = |fen: &str, mv: &str| {
+        let board = Board::from_fen(fen).unwrap();
+        let mv = Move::from_notation(mv).unwrap();
+        assert!(board.legal_moves().contains(&mv), "{mv} is illegal in {fen}");
+        let before = board;
+        let result = board.move_gives_check(&mv);
+        assert!(board == before && board.zobrist_key() == before.zobrist_key());
+        result
+    };
+
+    // Direct checks, including by castling and promoting
+    assert!(gives_check("4k3/8/8/8/4N3/8/P7/4K3 w - - 0 1", "e4f6"));
+    assert!(!gives_check("4k3/8/8/8/4N3/8/P7/4K3 w - - 0 1", "e4g5"));
+    assert!(gives_check("5k2/8/8/8/8/8/8/4K2R w K - 0 1", "O-O"));
+    assert!(gives_check("k7/4P3/8/8/8/8/8/4K3 w - - 0 1", "e7e8q"));
+    assert!(!gives_check("k7/4P3/8/8/8/8/8/4K3 w - - 0 1", "e7e8n"));
+
+    // Discovered checks, by any move of the piece in the line of the rook
+    assert!(gives_check("4k3/8/8/8/4N3/8/8/4R1K1 w - - 0 1", "e4c5"));
+    assert!(gives_check("4k3/8/8/8/4N3/8/8/4R1K1 w - - 0 1", "e4g3"));
+    assert!(!gives_check("4k3/8/8/8/4N3/8/8/4R1K1 w - - 0 1", "e1e2"));
+
+    // An e.p. capture empties the squares of both pawns, so it may uncover
+    // an attack along the rank, through the square of the captured pawn or
+    // through the one the capturing pawn leaves
+    assert!(gives_check("8/8/8/k2pP2R/8/8/8/4K3 w - d6 0 1", "e5d6"));
+    assert!(!gives_check("8/8/8/kp1pP2R/8/8/8/4K3 w - d6 0 1", "e5d6"));
+    assert!(gives_check("6k1/8/8/3pP3/8/8/B7/4K3 w - d6 0 1", "e5d6"));
+    assert!(gives_check("7k/8/8/3pP3/8/8/1B6/4K3 w - d6 0 1", "e5d6"));
+}
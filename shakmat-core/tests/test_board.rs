@@ -1,12 +1,19 @@
-use shakmat_core::{Board, DEFAULT_FEN};
+use shakmat_core::{Board, Move, MoveEffect, PieceType::*, DEFAULT_FEN};
 
 // Perft positions and results obtained from: https://www.chessprogramming.org/Perft_Results
 ///////////////////////////////////////////////////////////////////////////////
 
+// The single-threaded path is also checked at the depths that are quick enough
+// for it, which also covers the builds where threads aren't available
+const SINGLE_THREAD_MAX_NODES: u64 = 5_000_000;
+
 fn test_perft(fen: &str, expected: &[u64]) {
     let board = Board::from_fen(fen).unwrap();
     for (i, expected) in expected.iter().copied().enumerate() {
-        assert_eq!(board.perft(i + 1), expected);
+        if expected <= SINGLE_THREAD_MAX_NODES {
+            assert_eq!(board.perft_single_thread(i + 1), expected, "{fen} at depth {}", i + 1);
+        }
+        assert_eq!(board.perft(i + 1), expected, "{fen} at depth {}", i + 1);
     }
 }
 
@@ -66,4 +73,175 @@ fn pos6() {
         "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10",
         &[46, 2_079, 89_890, 3_894_594, 164_075_551, 6_923_051_137]
     )
-}
\ No newline at end of file
+}
+
+#[test]
+fn perft_divide() {
+    let board = Board::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1").unwrap();
+    let divide = board.perft_divide(3);
+
+    assert_eq!(divide.len(), 48);
+    assert_eq!(divide.iter().map(|(_, count)| count).sum::<u64>(), 97_862);
+    assert!(divide.iter().any(|(mv, count)| mv.to_string() == "e2a6" && *count == 1_907));
+}
+
+// The legal move generation, which relies on pins and checkers, must give the
+// same moves as making every pseudolegal move and discarding the ones that
+// leave the king in check, for every position in the first plies of the tree
+#[test]
+fn legal_moves_match_make_move_filter() {
+    fn compare(board: &Board, depth: usize) {
+        let legal = board.legal_moves();
+        let expected: Vec<_> = board.pseudolegal_moves().into_iter().filter(|mv| {
+            matches!(mv, Move::ShortCastle | Move::LongCastle) || !board.make_move(mv).is_check(board.turn_color())
+        }).collect();
+
+        assert_eq!(legal, expected, "{}", board.fen());
+        if depth > 1 {
+            legal.iter().for_each(|mv| compare(&board.make_move(mv), depth - 1));
+        }
+    }
+
+    let fens = [
+        DEFAULT_FEN,
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+        "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+        "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+        "8/8/8/KPp4r/8/8/8/7k w - c6 0 2",
+        "8/8/8/K2pP2q/8/8/8/7k w - d6 0 1",
+        "4k3/8/8/8/1b6/8/3P4/r3K2R w K - 0 1",
+    ];
+
+    for fen in fens {
+        compare(&Board::from_fen(fen).unwrap(), 3);
+    }
+}
+
+#[test]
+fn evasion_moves() {
+    let sorted_moves = |fen: &str| {
+        let board = Board::from_fen(fen).unwrap();
+        let mut moves: Vec<_> = board.evasion_moves().iter().map(|mv| mv.to_string()).collect();
+        moves.sort();
+        moves
+    };
+
+    // Checks by a knight can't be blocked, the queen must capture it
+    assert_eq!(sorted_moves("4k3/8/8/8/8/Q2n4/8/4K3 w - - 0 1"), ["a3d3", "e1d1", "e1d2", "e1e2", "e1f1"]);
+    // Sliders can be blocked, and the king can't stay on the line of the checker
+    assert_eq!(sorted_moves("6k1/8/8/b7/8/5N2/8/4K3 w - - 0 1"), ["e1d1", "e1e2", "e1f1", "e1f2", "f3d2"]);
+    assert_eq!(sorted_moves("4r1k1/8/8/8/8/8/3N4/4K3 w - - 0 1"), ["d2e4", "e1d1", "e1f1", "e1f2"]);
+    // In double check, only the king can move
+    assert_eq!(sorted_moves("4r1k1/8/8/8/8/3n4/3N4/Q3K3 w - - 0 1"), ["e1d1", "e1f1"]);
+}
+
+#[test]
+fn en_passant_discovered_check() {
+    // Capturing e.p. empties both squares between the king and the enemy slider
+    // on the same rank, so the capture is pseudolegal but never legal
+    let fens = [
+        ("8/8/8/K2pP2q/8/8/8/7k w - d6 0 1", "e5d6"),
+        ("7K/8/8/8/R2pP2k/8/8/8 b - e3 0 1", "d4e3"),
+    ];
+
+    for (fen, ep) in fens {
+        let board = Board::from_fen(fen).unwrap();
+        let ep = Move::from_notation(ep).unwrap();
+        let legal = board.legal_moves();
+
+        assert!(board.pseudolegal_moves().contains(&ep));
+        assert!(board.make_move(&ep).is_check(board.turn_color()));
+        assert!(!legal.contains(&ep));
+        assert!(!board.is_legal_move(&ep));
+        assert_eq!(board.legal_move_list().to_vec(), legal);
+        assert_eq!(board.perft(1), legal.len() as u64);
+        assert_eq!(board.perft_divide(1).len(), legal.len());
+    }
+
+    // Capturing the pawn that just gave check by moving two squares is fine
+    let board = Board::from_fen("7k/8/8/3pP3/4K3/8/8/8 w - d6 0 1").unwrap();
+    assert!(board.evasion_moves().contains(&Move::from_notation("e5d6").unwrap()));
+    assert!(board.legal_moves().contains(&Move::from_notation("e5d6").unwrap()));
+}
+
+#[test]
+fn move_lists() {
+    let board = Board::from_fen("r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1").unwrap();
+    assert_eq!(board.legal_move_list().len(), 6);
+    assert_eq!(board.legal_move_list().to_vec(), board.legal_moves());
+    assert_eq!(board.pseudolegal_move_list().iter().count(), board.pseudolegal_moves().len());
+    assert_eq!(board.pseudolegal_caps_list().into_iter().collect::<Vec<_>>(), board.pseudolegal_caps());
+}
+
+#[test]
+fn game_phase() {
+    let phase = |fen| Board::from_fen(fen).unwrap().game_phase();
+    assert_eq!(phase(DEFAULT_FEN), 0);
+    assert_eq!(phase("4k3/pppppppp/8/8/8/8/PPPPPPPP/4K3 w - - 0 1"), 256);
+    // Minor pieces are worth 1 point out of 24, rooks 2 and queens 4
+    assert_eq!(phase("2b1k3/pppp1ppp/8/8/8/8/PPPP1PPP/R3K3 w - - 0 1"), 224);
+    assert_eq!(phase("1r2k1n1/pppp1ppp/8/8/8/8/PPPP1PPP/R3K1N1 w - - 0 1"), 192);
+    // Promoted pieces don't go past the middlegame
+    assert_eq!(phase("QQQQkQQQ/8/8/8/8/8/8/4K3 w - - 0 1"), 0);
+}
+
+#[test]
+fn make_move_checked() {
+    let mut board = Board::default();
+    assert!(board.make_move_checked(&Move::from_notation("e2e4").unwrap()).is_ok());
+    assert!(board == Board::default().make_move(&Move::from_notation("e2e4").unwrap()));
+
+    // Illegal moves leave the board as it was
+    let before = board;
+    let result = board.make_move_checked(&Move::from_notation("e4e5").unwrap());
+    assert_eq!(result.err().as_deref(), Some("Illegal move: e4e5"));
+    assert!(board == before && board.fen() == before.fen());
+}
+
+#[test]
+fn move_effects() {
+    let effect = |fen: &str, mv: &str| {
+        Board::from_fen(fen).unwrap().make_move_checked(&Move::from_notation(mv).unwrap()).unwrap()
+    };
+    let quiet = MoveEffect { captured: None, was_castle: false, was_en_passant: false, gives_check: false };
+
+    assert_eq!(effect(DEFAULT_FEN, "e2e4"), quiet);
+    assert_eq!(effect("4k3/8/8/8/4q3/8/8/4RK2 w - - 0 1", "e1e4"), MoveEffect { captured: Some(Queen), gives_check: true, ..quiet });
+    assert_eq!(effect("4k3/8/8/8/8/8/8/4K2R w K - 0 1", "O-O"), MoveEffect { was_castle: true, ..quiet });
+    // The pawn captured e.p. isn't on the destination square
+    assert_eq!(
+        effect("8/2k5/8/3pP3/8/8/8/4K3 w - d6 0 1", "e5d6"),
+        MoveEffect { captured: Some(Pawn), was_en_passant: true, gives_check: true, ..quiet }
+    );
+    assert_eq!(effect("8/2k5/8/3pP3/8/8/8/4K3 w - d6 0 1", "e5e6"), quiet);
+}
+
+#[test]
+fn move_count() {
+    let board = Board::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 10").unwrap();
+    assert_eq!(board.move_count(), 0);
+    assert_eq!(board.current_ply(), 18);
+
+    let board = board.make_move(&Move::ShortCastle).make_move(&Move::from_notation("b4c3").unwrap());
+    assert_eq!(board.move_count(), 2);
+    assert_eq!(board.current_ply(), 20);
+    assert!(!board.is_after_null_move());
+
+    // Null moves count as plies, but not as moves
+    let board = board.make_null_move();
+    assert!(board.is_after_null_move());
+    assert_eq!(board.move_count(), 2);
+    assert_eq!(board.current_ply(), 21);
+    assert!(!board.make_move(&Move::ShortCastle).is_after_null_move());
+}
+
+#[test]
+fn debug_output() {
+    let board = Board::from_fen("4k3/8/8/8/8/8/8/4K2R w K - 0 1").unwrap();
+    assert_eq!(format!("{board:?}"), format!("Board {{ fen: \"4k3/8/8/8/8/8/8/4K2R w K - 0 1\", zobrist_key: {:#018x} }}", board.zobrist_key()));
+
+    assert_eq!(format!("{:?}", Move::from_notation("e2e4").unwrap()), "Normal { from: e2, to: e4 }");
+    assert_eq!(format!("{:?}", Move::from_notation("a7a8n").unwrap()), "PawnPromotion { from: a7, to: a8, promote_to: Knight }");
+    assert_eq!(format!("{:?}", Move::LongCastle), "LongCastle");
+}
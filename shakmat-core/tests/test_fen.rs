@@ -1,4 +1,4 @@
-use shakmat_core::{Board, DEFAULT_FEN, Move};
+use shakmat_core::{chess960_fen, Board, CastlingRights, Color, DEFAULT_FEN, Move, PieceType, Square};
 
 // Tests the FEN generation by comparing the known ones against
 // the expected output from the position
@@ -23,11 +23,14 @@ fn test_known_fens() {
 // Tests the generated FENS when some moves are done from the initial position
 #[test]
 fn test_dynamic_fens() {
-    let moves = ["e2e4", "c7c5", "g1f3"];
+    // The e.p. square is only present when a pawn can capture on it
+    let moves = ["e2e4", "c7c5", "e4e5", "d7d5", "g1f3"];
     let fens = [
-        "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1",
-        "rnbqkbnr/pp1ppppp/8/2p5/4P3/8/PPPP1PPP/RNBQKBNR w KQkq c6 0 2",
-        "rnbqkbnr/pp1ppppp/8/2p5/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 1 2"
+        "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1",
+        "rnbqkbnr/pp1ppppp/8/2p5/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 0 2",
+        "rnbqkbnr/pp1ppppp/8/2p1P3/8/8/PPPP1PPP/RNBQKBNR b KQkq - 0 2",
+        "rnbqkbnr/pp2pppp/8/2ppP3/8/8/PPPP1PPP/RNBQKBNR w KQkq d6 0 3",
+        "rnbqkbnr/pp2pppp/8/2ppP3/8/5N2/PPPP1PPP/RNBQKB1R b KQkq - 1 3"
     ];
 
     let mut board = Board::default();
@@ -37,4 +40,114 @@ fn test_dynamic_fens() {
         board = board.make_move(&mv);
         assert_eq!(fen, board.fen());
     }
-}
\ No newline at end of file
+}
+
+// Parsing a FEN and generating it back must produce the same string
+#[test]
+fn test_fen_round_trip() {
+    let fens = [
+        DEFAULT_FEN,
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+        "rnbqkbnr/pppp1ppp/8/8/3Pp3/5N2/PPP1PPPP/RNBQKB1R b KQkq d3 0 3",
+        "8/2k5/8/8/8/8/5K2/8 b - - 37 84",
+        "4k2r/8/8/8/8/8/8/R3K3 w Qk - 12 40",
+    ];
+
+    for fen in fens {
+        assert_eq!(fen, Board::from_fen(fen).unwrap().fen());
+    }
+
+    // A non-capturable e.p. square is dropped, same as in the zobrist key
+    let board = Board::from_fen("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1").unwrap();
+    assert_eq!("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1", board.fen());
+}
+
+// The strict parser must reject positions that cannot happen in a game
+#[test]
+fn test_strict_fen() {
+    let valid = [
+        DEFAULT_FEN,
+        "rnbqkbnr/pp1ppppp/8/2p5/4P3/8/PPPP1PPP/RNBQKBNR w KQkq c6 0 2",
+        "r3k2r/8/8/8/8/8/8/4K3 b kq - 0 1",
+    ];
+
+    for fen in valid {
+        assert!(Board::from_fen_strict(fen).is_ok(), "{fen}");
+    }
+
+    let invalid = [
+        ("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQ1BNR w kq - 0 1", "White must have a king!"),
+        ("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBKKBNR w kq - 0 1", "White must have exactly one king, found 2"),
+        ("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNP w - - 0 1", "White has pawns on the first or last rank"),
+        ("4k3/8/8/8/8/8/8/4K3 w K - 0 1", "White can castle kingside, but the rook is not on its initial square"),
+        ("r3k2r/8/8/8/8/8/8/3K4 b kqK - 0 1", "White can castle, but the king is not on its initial square"),
+        ("r2k4/8/8/8/8/8/8/4K3 b q - 0 1", "Black can castle, but the king is not on its initial square"),
+        ("4k3/8/8/8/8/8/8/4R1K1 w - - 0 1", "Black is in check, but it's not their turn"),
+        ("4k3/8/8/8/4P3/8/8/4K3 w - e3 0 1", "The en passant square e3 is not consistent with the position"),
+        ("4k3/8/8/8/8/8/8/4K3 w - h1 0 1", "The en passant square h1 is not consistent with the position"),
+        ("4k3/8/8/8/8/8/8/4K3 b - a1 0 1", "The en passant square a1 is not consistent with the position"),
+        ("4k3/8/8/8/8/8/8/4K3 b - h8 0 1", "The en passant square h8 is not consistent with the position"),
+        ("4k3/8/8/8/8/8/8/4K3 w - - 0 0", "The full move number must be at least 1"),
+    ];
+
+    for (fen, error) in invalid {
+        assert_eq!(Board::from_fen_strict(fen).err().as_deref(), Some(error), "{fen}");
+    }
+}
+
+#[test]
+fn test_from_pieces() {
+    let square = |name| Square::from_notation(name).unwrap().square() as usize;
+    let mut pieces = [None; 64];
+    pieces[square("e1")] = Some((Color::White, PieceType::King));
+    pieces[square("h1")] = Some((Color::White, PieceType::Rook));
+    pieces[square("e4")] = Some((Color::White, PieceType::Pawn));
+    pieces[square("d4")] = Some((Color::Black, PieceType::Pawn));
+    pieces[square("e8")] = Some((Color::Black, PieceType::King));
+
+    let castling = CastlingRights::new(true, false, false, false);
+    let ep = Square::from_notation("e3").ok();
+    let board = Board::from_pieces(&pieces, Color::Black, castling, ep, 0, 12).unwrap();
+    let from_fen = Board::from_fen("4k3/8/8/8/3pP3/8/8/4K2R b K e3 0 12").unwrap();
+    assert_eq!(board.fen(), from_fen.fen());
+    assert_eq!(board.zobrist_key(), from_fen.zobrist_key());
+    assert_eq!(board.legal_moves().len(), from_fen.legal_moves().len());
+
+    // The same validation as in strict FENs applies
+    let error = Board::from_pieces(&pieces, Color::White, castling, ep, 0, 12).err();
+    assert_eq!(error.as_deref(), Some("The en passant square e3 is not consistent with the position"));
+    let error = Board::from_pieces(&pieces, Color::Black, castling, Square::from_notation("h1").ok(), 0, 12).err();
+    assert_eq!(error.as_deref(), Some("The en passant square h1 is not consistent with the position"));
+    pieces[square("e8")] = None;
+    let error = Board::from_pieces(&pieces, Color::Black, castling, None, 0, 12).err();
+    assert_eq!(error.as_deref(), Some("Black must have exactly one king, found 0"));
+}
+
+#[test]
+fn test_chess960_fens() {
+    assert_eq!(chess960_fen(518).unwrap(), DEFAULT_FEN);
+    assert_eq!(chess960_fen(0).unwrap(), "bbqnnrkr/pppppppp/8/8/8/8/PPPPPPPP/BBQNNRKR w - - 0 1");
+    assert_eq!(chess960_fen(959).unwrap(), "rkrnnqbb/pppppppp/8/8/8/8/PPPPPPPP/RKRNNQBB w - - 0 1");
+    // Castling is only allowed with the rooks in the corners next to a king on e1
+    assert_eq!(chess960_fen(519).unwrap(), "rnbqknrb/pppppppp/8/8/8/8/PPPPPPPP/RNBQKNRB w Qq - 0 1");
+    assert_eq!(chess960_fen(226).unwrap(), "bnrqkbnr/pppppppp/8/8/8/8/PPPPPPPP/BNRQKBNR w Kk - 0 1");
+    assert!(chess960_fen(960).is_err());
+
+    let mut back_ranks: Vec<_> = (0..960).map(|n| {
+        let fen = chess960_fen(n).unwrap();
+        let board = Board::from_fen_strict(&fen).unwrap();
+        assert_eq!(fen, board.fen());
+
+        // Bishops on squares of different colors, and the king between the rooks
+        let back_rank = fen.split('/').next_back().unwrap()[..8].to_owned();
+        let files = |piece| back_rank.match_indices(piece).map(|(i, _)| i).collect::<Vec<_>>();
+        assert_eq!(files('B').iter().map(|f| f % 2).sum::<usize>(), 1);
+        assert!(files('R')[0] < files('K')[0] && files('K')[0] < files('R')[1]);
+        back_rank
+    }).collect();
+
+    back_ranks.sort();
+    back_ranks.dedup();
+    assert_eq!(back_ranks.len(), 960);
+}
@@ -0,0 +1,123 @@
+use shakmat_core::{Board, DrawReason, GameResult, Move, DEFAULT_FEN};
+
+fn result(fen: &str) -> Option<GameResult> {
+    Board::from_fen(fen).unwrap().game_result()
+}
+
+#[test]
+fn checkmate() {
+    let board = Board::from_fen("rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 1 3").unwrap();
+    assert!(board.is_checkmate());
+    assert!(!board.is_stalemate());
+    assert_eq!(board.game_result(), Some(GameResult::BlackWins));
+
+    assert_eq!(result("6rk/5Npp/8/8/8/8/8/6K1 b - - 0 1"), Some(GameResult::WhiteWins));
+}
+
+#[test]
+fn stalemate() {
+    let board = Board::from_fen("7k/5Q2/8/8/8/8/8/6K1 b - - 0 1").unwrap();
+    assert!(board.is_stalemate());
+    assert!(!board.is_checkmate());
+    assert_eq!(board.game_result(), Some(GameResult::Draw(DrawReason::Stalemate)));
+}
+
+#[test]
+fn draws() {
+    assert_eq!(result("8/8/4k3/8/8/3K4/8/8 w - - 0 1"), Some(GameResult::Draw(DrawReason::InsufficientMaterial)));
+    assert_eq!(result("8/8/4k3/8/8/3K4/4R3/8 b - - 100 80"), Some(GameResult::Draw(DrawReason::FiftyMoveRule)));
+}
+
+#[test]
+fn fifty_move_counter() {
+    let board = Board::from_fen("8/8/4k3/8/8/3K4/4R2P/8 w - - 98 80").unwrap();
+    assert!(!board.is_fifty_move_draw());
+
+    // Boards are copied when making moves, so the original keeps its counter
+    let next = board.make_move(&Move::from_notation("e2e1").unwrap());
+    assert_eq!(board.fifty_move_rule_counter(), 98);
+    assert_eq!(next.fifty_move_rule_counter(), 99);
+
+    let next = next.make_move(&Move::from_notation("e6d5").unwrap());
+    assert!(next.is_fifty_move_draw());
+    assert!(next.legal_moves().is_empty());
+    assert_eq!(next.game_result(), Some(GameResult::Draw(DrawReason::FiftyMoveRule)));
+
+    // Pawn moves reset the counter
+    let reset = board.make_move(&Move::from_notation("h2h4").unwrap());
+    assert_eq!(reset.fifty_move_rule_counter(), 0);
+    assert!(!reset.is_fifty_move_draw());
+}
+
+#[test]
+fn checkmate_takes_precedence_over_fifty_moves() {
+    assert_eq!(result("6rk/5Npp/8/8/8/8/8/6K1 b - - 100 80"), Some(GameResult::WhiteWins));
+}
+
+#[test]
+fn ongoing_game() {
+    assert_eq!(result(DEFAULT_FEN), None);
+    assert!(!Board::default().is_checkmate());
+    assert!(!Board::default().is_stalemate());
+}
+
+#[test]
+fn threefold_repetition() {
+    let mut board = Board::default();
+    let mut history = vec![board.zobrist_key()];
+
+    for (i, mv) in ["g1f3", "g8f6", "f3g1", "f6g8", "g1f3", "g8f6", "f3g1", "f6g8"].iter().enumerate() {
+        board = board.make_move(&Move::from_notation(mv).unwrap());
+        history.push(board.zobrist_key());
+
+        // The initial position only repeats for the third time after the last move
+        assert_eq!(board.is_threefold_repetition(&history), i == 7);
+        // The history may also not include the current position
+        assert_eq!(board.is_threefold_repetition(&history[..history.len() - 1]), i == 7);
+    }
+
+    // An irreversible move makes previous positions unreachable
+    let board = Board::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 5").unwrap();
+    assert!(!board.is_threefold_repetition(&history));
+}
+
+#[test]
+fn automatic_draws() {
+    assert_eq!(result("8/8/4k3/8/8/3K4/4R3/8 b - - 149 100"), Some(GameResult::Draw(DrawReason::FiftyMoveRule)));
+    assert_eq!(result("8/8/4k3/8/8/3K4/4R3/8 b - - 150 100"), Some(GameResult::Draw(DrawReason::SeventyFiveMoveRule)));
+
+    let mut board = Board::default();
+    let mut history = vec![board.zobrist_key()];
+    let moves = ["g1f3", "g8f6", "f3g1", "f6g8"];
+
+    for (i, mv) in moves.iter().cycle().take(16).enumerate() {
+        board = board.make_move(&Move::from_notation(mv).unwrap());
+        history.push(board.zobrist_key());
+
+        // The initial position repeats for the fifth time after the last move
+        assert_eq!(board.is_fivefold_repetition(&history), i == 15);
+        assert_eq!(board.is_threefold_repetition(&history), i >= 7);
+    }
+
+    assert_eq!(board.game_result(), None);
+    assert_eq!(board.game_result_with_history(&history), Some(GameResult::Draw(DrawReason::FivefoldRepetition)));
+}
+
+#[test]
+fn insufficient_material() {
+    let is_draw = |fen: &str| Board::from_fen(fen).unwrap().is_draw_by_material();
+
+    // Draws: K vs K, K + minor vs K, bishops on the same color complex
+    assert!(is_draw("8/8/4k3/8/8/3K4/8/8 w - - 0 1"));
+    assert!(is_draw("8/8/4k3/8/8/3K4/3N4/8 w - - 0 1"));
+    assert!(is_draw("8/8/4k3/8/8/3K4/3B4/8 b - - 0 1"));
+    assert!(is_draw("8/2b5/4k3/8/8/3K4/3B4/8 w - - 0 1"));
+    assert!(is_draw("8/2b5/4k3/8/8/3KB3/3B4/8 w - - 0 1"));
+
+    // Not draws: KNN vs K, KN vs KN, KB vs KN, opposite-colored bishops, pawns
+    assert!(!is_draw("8/8/4k3/8/8/3K4/3NN3/8 w - - 0 1"));
+    assert!(!is_draw("8/3n4/4k3/8/8/3K4/3N4/8 w - - 0 1"));
+    assert!(!is_draw("8/3n4/4k3/8/8/3K4/3B4/8 w - - 0 1"));
+    assert!(!is_draw("8/3b4/4k3/8/8/3K4/3B4/8 w - - 0 1"));
+    assert!(!is_draw("8/8/4k3/8/8/3K4/3P4/8 w - - 0 1"));
+}
@@ -0,0 +1,74 @@
+use shakmat_core::{Board, Move, create_pgn, read_pgn};
+
+fn play(board: &Board, moves: &[&str]) -> Vec<Move> {
+    let mut board = *board;
+    moves.iter().map(|san| {
+        let mv = Move::from_san(san, &board).unwrap();
+        board = board.make_move(&mv);
+        mv
+    }).collect()
+}
+
+#[test]
+fn finished_game() {
+    let board = Board::default();
+    let moves = play(&board, &["e4", "e5", "Bc4", "Nc6", "Qh5", "Nf6", "Qxf7#"]);
+    let pgn = create_pgn(&board, &moves);
+
+    assert!(pgn.contains("[Result \"1-0\"]"));
+    assert!(!pgn.contains("[FEN"));
+    assert!(pgn.ends_with("1. e4 e5 2. Bc4 Nc6 3. Qh5 Nf6 4. Qxf7# 1-0\n"));
+}
+
+#[test]
+fn custom_start_position() {
+    let fen = "k7/8/8/8/8/8/8/KN3N2 b - - 0 30";
+    let board = Board::from_fen(fen).unwrap();
+    let moves = play(&board, &["Kb7", "Nbd2"]);
+    let pgn = create_pgn(&board, &moves);
+
+    assert!(pgn.contains("[Result \"*\"]"));
+    assert!(pgn.contains(&format!("[SetUp \"1\"]\n[FEN \"{fen}\"]")));
+    assert!(pgn.ends_with("30... Kb7 31. Nbd2 *\n"));
+}
+
+#[test]
+fn read_games() {
+    let pgn = r#"[Event "Casual game"]
+[Result "1-0"]
+
+1. e4 e5 2. Bc4 {A comment (with parentheses)} Nc6 3.Qh5 Nf6?? (3... g6 4. Qf3) 4. Qxf7# 1-0
+
+[Event "Another one"]
+[SetUp "1"]
+[FEN "k7/8/8/8/8/8/8/KN3N2 b - - 0 30"]
+
+30... Kb7 $1 ; Rest of the line
+31. Nbd2 *
+"#;
+
+    let games = read_pgn(pgn).unwrap();
+    assert_eq!(2, games.len());
+
+    let (board, moves) = &games[0];
+    assert_eq!(Board::default().fen(), board.fen());
+    assert_eq!(play(board, &["e4", "e5", "Bc4", "Nc6", "Qh5", "Nf6", "Qxf7#"]), *moves);
+
+    let (board, moves) = &games[1];
+    assert_eq!("k7/8/8/8/8/8/8/KN3N2 b - - 0 30", board.fen());
+    assert_eq!(play(board, &["Kb7", "Nbd2"]), *moves);
+
+    // The moves after the fifty move rule applies are read too
+    let pgn = "[SetUp \"1\"]\n[FEN \"4k3/8/8/8/8/8/8/R3K3 w - - 99 80\"]\n\n80. Ra2 Kd8 81. Ra8+ *\n";
+    let (board, moves) = &read_pgn(pgn).unwrap()[0];
+    assert_eq!(play(board, &["Ra2", "Kd8", "Ra8+"]), *moves);
+}
+
+#[test]
+fn read_created_pgn() {
+    let board = Board::default();
+    let moves = play(&board, &["d4", "d5", "c4", "dxc4", "e3", "b5", "a4", "c6", "axb5", "cxb5", "Qf3"]);
+    let games = read_pgn(&create_pgn(&board, &moves)).unwrap();
+    assert_eq!(1, games.len());
+    assert_eq!(moves, games[0].1);
+}
@@ -0,0 +1,103 @@
+use shakmat_core::{Board, Move, PieceType::*};
+
+// Tests that SAN moves are resolved to the expected coordinate moves
+fn run_test(fen: &str, san: &str, expected: &str) {
+    let board = Board::from_fen(fen).unwrap();
+    let mv = Move::from_san(san, &board).unwrap();
+    assert_eq!(expected, mv.to_string());
+}
+
+#[test]
+fn piece_and_pawn_moves() {
+    let fen = "rnbqkbnr/ppp1pppp/8/3p4/4P3/8/PPPP1PPP/RNBQKBNR w KQkq d6 0 2";
+    run_test(fen, "Nf3", "g1f3");
+    run_test(fen, "exd5", "e4d5");
+    run_test(fen, "e5", "e4e5");
+    run_test(fen, "Bb5+", "f1b5");
+}
+
+#[test]
+fn castling() {
+    let fen = "r3k2r/pppq1ppp/2npbn2/2b1p3/2B1P3/2NPBN2/PPPQ1PPP/R3K2R w KQkq - 0 1";
+    let board = Board::from_fen(fen).unwrap();
+    assert_eq!(Move::from_san("O-O", &board), Ok(Move::ShortCastle));
+    assert_eq!(Move::from_san("O-O-O", &board), Ok(Move::LongCastle));
+    assert_eq!(Move::from_san("0-0-0", &board), Ok(Move::LongCastle));
+}
+
+#[test]
+fn promotions_and_en_passant() {
+    let board = Board::from_fen("3r3k/4P3/8/3pP3/8/8/8/K7 w - d6 0 1").unwrap();
+    let promotion = Move::from_san("e8=Q+", &board).unwrap();
+    assert!(matches!(promotion, Move::PawnPromotion { promote_to: Queen, .. }));
+    assert_eq!("e7e8q", promotion.to_string());
+    assert_eq!("e7d8n", Move::from_san("exd8N", &board).unwrap().to_string());
+    assert_eq!("e5d6", Move::from_san("exd6", &board).unwrap().to_string());
+}
+
+#[test]
+fn disambiguation() {
+    let fen = "k7/8/8/8/8/8/8/KN3N2 w - - 0 1";
+    run_test(fen, "Nbd2", "b1d2");
+    run_test(fen, "Nfd2", "f1d2");
+    run_test("k7/8/8/R7/8/8/8/R6K w - - 0 1", "R1a3", "a1a3");
+
+    let board = Board::from_fen(fen).unwrap();
+    assert!(Move::from_san("Nd2", &board).unwrap_err().contains("Ambiguous"));
+}
+
+#[test]
+fn illegal_or_invalid() {
+    let board = Board::default();
+    assert!(Move::from_san("Nf6", &board).unwrap_err().contains("Illegal"));
+    assert!(Move::from_san("O-O", &board).unwrap_err().contains("Illegal"));
+    assert!(Move::from_san("e5", &board).is_err());
+    assert!(Move::from_san("Zz9", &board).is_err());
+    assert!(Move::from_san("", &board).is_err());
+}
+
+#[test]
+fn san_generation() {
+    let board = Board::from_fen("1k6/4P3/8/R7/8/8/8/R3K2R w KQ - 0 1").unwrap();
+    let to_san = |mv: &str| Move::from_notation(mv).unwrap().to_san(&board);
+
+    assert_eq!("R1a3", to_san("a1a3"));
+    assert_eq!("Rh8+", to_san("h1h8"));
+    assert_eq!("e8=Q+", to_san("e7e8q"));
+    assert_eq!("O-O-O", to_san("O-O-O"));
+    assert_eq!("Kf2", to_san("e1f2"));
+
+    // Every SAN generated for a legal move must parse back to the same move
+    for mv in board.legal_moves() {
+        assert_eq!(Ok(mv), Move::from_san(&mv.to_san(&board), &board));
+    }
+}
+
+#[test]
+fn move_classification() {
+    let board = Board::from_fen("1n2k3/P7/8/3pP3/8/8/8/4K2R w K d6 0 2").unwrap();
+    let mv = |notation: &str| Move::from_notation(notation).unwrap();
+
+    assert!(mv("O-O").is_quiet(&board) && mv("h1h5").is_quiet(&board));
+    assert!(!mv("e5d6").is_quiet(&board)); // En passant
+    assert!(!mv("a7a8q").is_quiet(&board) && !mv("a7b8n").is_quiet(&board));
+
+    assert_eq!(mv("a7a8q").promotion_piece(), Some(Queen));
+    assert_eq!(mv("a7b8n").promotion_piece(), Some(Knight));
+    assert_eq!(mv("e5e6").promotion_piece(), None);
+
+    assert!(mv("a7a8r").is_underpromotion() && mv("a7b8b").is_underpromotion());
+    assert!(!mv("a7a8q").is_underpromotion() && !mv("e5e6").is_underpromotion());
+}
+
+#[test]
+fn drawn_positions() {
+    // The game goes on after 100 half-moves or with insufficient material
+    // if no draw is claimed, so those moves must still be read
+    run_test("4k3/8/8/8/8/8/8/R3K3 w - - 100 80", "Ra2", "a1a2");
+    run_test("4k3/8/8/8/8/8/8/4KN2 w - - 0 1", "Ng3", "f1g3");
+
+    let board = Board::from_fen("k7/8/8/8/8/8/8/KN3N2 w - - 100 80").unwrap();
+    assert_eq!("Nbd2", Move::from_notation("b1d2").unwrap().to_san(&board));
+    assert!(Move::from_san("Nd2", &board).unwrap_err().contains("Ambiguous"));
+}
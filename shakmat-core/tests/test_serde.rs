@@ -0,0 +1,44 @@
+use shakmat_core::{Board, GameRecord, Move};
+
+// Boards are serialized as their FEN, and must be the same after a round trip
+#[test]
+fn test_serde_round_trip() {
+    let mut board = Board::default();
+    for mv in ["e2e4", "c7c5", "e4e5", "d7d5"] {
+        board = board.make_move(&Move::from_notation(mv).unwrap());
+    }
+
+    let json = serde_json::to_string(&board).unwrap();
+    assert_eq!(format!("\"{}\"", board.fen()), json);
+
+    let restored: Board = serde_json::from_str(&json).unwrap();
+    assert_eq!(board.zobrist_key(), restored.zobrist_key());
+    assert_eq!(board.legal_moves(), restored.legal_moves());
+    assert!(serde_json::from_str::<Board>("\"not a fen\"").is_err());
+}
+
+// Game records also keep the previous positions, so
+// repetitions are still detected after a round trip
+#[test]
+fn test_game_record_round_trip() {
+    let mut record = GameRecord::new(Board::default());
+    for mv in ["g1f3", "g8f6", "f3g1", "f6g8", "g1f3", "g8f6", "f3g1"] {
+        record.make_move(&Move::from_notation(mv).unwrap());
+    }
+
+    let json = serde_json::to_string(&record).unwrap();
+    assert!(json.starts_with(&format!("{{\"fen\":\"{}\",\"history\":[", record.board.fen())));
+
+    let mut restored: GameRecord = serde_json::from_str(&json).unwrap();
+    assert_eq!(record, restored);
+    assert_eq!(record.board.zobrist_key(), restored.board.zobrist_key());
+    assert!(!restored.is_threefold_repetition());
+
+    // The next move repeats the initial position for the third time
+    restored.make_move(&Move::from_notation("f6g8").unwrap());
+    assert!(restored.is_threefold_repetition());
+    let restored: GameRecord = serde_json::from_str(&serde_json::to_string(&restored).unwrap()).unwrap();
+    assert!(restored.is_threefold_repetition());
+
+    assert!(serde_json::from_str::<GameRecord>("{\"fen\":\"not a fen\",\"history\":[]}").is_err());
+}
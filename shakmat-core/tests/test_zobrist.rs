@@ -1,3 +1,4 @@
+use std::collections::HashSet;
 use shakmat_core::{Board, Move, DEFAULT_FEN};
 
 // Tests that the zobrist keys are calculated correctly w.r.t. the random
@@ -18,6 +19,7 @@ fn run_test(moves: &[&str], fen: &str, zobrist_hex: &str) {
     let board_fen = Board::from_fen(fen).unwrap();
     assert_eq!(zobrist_hex, format!("{:#x}", board_fen.zobrist_key()));
     assert_eq!(zobrist_hex, format!("{:#x}", board.zobrist_key()));
+    assert_eq!(board_fen.pawn_key(), board.pawn_key());
 }
 
 #[test]
@@ -95,4 +97,101 @@ fn pos8() {
         "rnbqkbnr/p1pppppp/8/8/P6P/R1p5/1P1PPPP1/1NBQKBNR b Kkq - 0 4", 
         "0x5c3f9b829b279560"
     );
-}
\ No newline at end of file
+}
+
+#[test]
+fn uncapturable_ep_square() {
+    // Polyglot only hashes the e.p. square if it can actually be captured
+    let with_ep = Board::from_fen("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1").unwrap();
+    let without_ep = Board::from_fen("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1").unwrap();
+    assert_eq!(with_ep.zobrist_key(), without_ep.zobrist_key());
+}
+
+#[test]
+fn board_identity() {
+    let play = |moves: &[&str]| moves.iter()
+        .fold(Board::default(), |board, mv| board.make_move(&Move::from_notation(mv).unwrap()));
+
+    // Same position reached through different move orders and with different counters
+    let board1 = play(&["g1f3", "g8f6", "b1c3", "b8c6"]);
+    let board2 = play(&["b1c3", "b8c6", "g1f3", "g8f6"]);
+    let board3 = Board::from_fen("r1bqkb1r/pppppppp/2n2n2/8/8/2N2N2/PPPPPPPP/R1BQKB1R w KQkq - 0 1").unwrap();
+    assert!(board1 == board2 && board2 == board3);
+
+    // An e.p. square that no pawn can capture doesn't make a difference
+    let with_ep = Board::from_fen("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1").unwrap();
+    let without_ep = Board::from_fen("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1").unwrap();
+    assert!(with_ep == without_ep);
+    assert!(board1 != play(&["g1f3", "g8f6", "b1c3"]));
+
+    let positions: HashSet<Board> = [board1, board2, board3, with_ep, without_ep].into_iter().collect();
+    assert_eq!(2, positions.len());
+}
+
+#[test]
+fn incremental_keys() {
+    // Plays random games, checking that the keys updated on every move match
+    // the ones calculated from scratch for the same position. The starting
+    // positions have lots of pawns, so that e.p. captures come up often.
+    let fens = [
+        DEFAULT_FEN,
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        "4k3/pppppppp/8/8/8/8/PPPPPPPP/4K3 w - - 0 1",
+        "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+    ];
+
+    // Xorshift, to get the same games every time without any dependencies
+    let mut state: u64 = 0x9E3779B97F4A7C15;
+    let mut random = move |max: usize| {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state as usize % max
+    };
+
+    for fen in fens {
+        for _ in 0..50 {
+            let mut board = Board::from_fen(fen).unwrap();
+            for _ in 0..80 {
+                let moves = board.legal_moves();
+                if moves.is_empty() {
+                    break;
+                }
+
+                // Null moves also update the key, including the e.p. square
+                board = if random(10) == 0 && !board.is_check(board.turn_color()) {
+                    board.make_null_move()
+                } else {
+                    board.make_move(&moves[random(moves.len())])
+                };
+
+                let from_scratch = Board::from_fen(&board.fen()).unwrap();
+                assert_eq!(board.zobrist_key(), from_scratch.zobrist_key(), "{}", board.fen());
+                assert_eq!(board.pawn_key(), from_scratch.pawn_key(), "{}", board.fen());
+            }
+        }
+    }
+}
+
+#[test]
+fn rook_captures_update_castling_key() {
+    // Capturing a rook on its initial square removes the castling rights of
+    // its side, which must be reflected in the key as well
+    let cases = [
+        ("r3k2r/8/8/8/8/8/1b6/R3K2R b KQkq - 0 1", "b2a1", "Kkq"),
+        ("r3k2r/8/8/8/8/8/6b1/R3K2R b KQkq - 0 1", "g2h1", "Qkq"),
+        ("r3k2r/1B6/8/8/8/8/8/R3K2R w KQkq - 0 1", "b7a8", "KQk"),
+        ("r3k2r/6B1/8/8/8/8/8/R3K2R w KQkq - 0 1", "g7h8", "KQq"),
+        // Both sides lose a right if the rook captures the other one
+        ("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1", "a1a8", "Kk"),
+    ];
+
+    for (fen, mv, castling) in cases {
+        let board = Board::from_fen(fen).unwrap().make_move(&Move::from_notation(mv).unwrap());
+        assert_eq!(board.fen().split(' ').nth(2), Some(castling));
+
+        let from_scratch = Board::from_fen(&board.fen()).unwrap();
+        assert_eq!(board.zobrist_key(), from_scratch.zobrist_key());
+        assert!(board.zobrist_key() != Board::from_fen(&board.fen().replace(castling, "KQkq")).unwrap().zobrist_key());
+    }
+}
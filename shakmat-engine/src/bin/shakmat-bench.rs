@@ -0,0 +1,23 @@
+// Searches a fixed set of positions and prints the total nodes and NPS, which
+// is a signature of the search to compare between versions of the engine.
+// Usage: shakmat-bench [depth]
+use std::env::args;
+use std::process::exit;
+use shakmat_engine::{init_evaluation, ShakmatEngine, DEFAULT_BENCH_DEPTH};
+
+fn main() {
+    let depth = match args().nth(1).map(|s| s.parse()) {
+        None => DEFAULT_BENCH_DEPTH,
+        Some(Ok(depth)) if depth > 0 => depth,
+        Some(_) => {
+            eprintln!("The depth must be a positive number");
+            exit(1);
+        }
+    };
+
+    init_evaluation();
+    let result = ShakmatEngine::default().bench(depth);
+    println!("Nodes searched: {}", result.nodes);
+    println!("Time (ms): {}", result.elapsed_ms);
+    println!("Nodes/second: {}", result.nps());
+}
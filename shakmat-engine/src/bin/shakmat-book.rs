@@ -0,0 +1,28 @@
+// Creates a polyglot opening book from a collection of games in PGN format.
+// Usage: shakmat-book <input.pgn> <output.bin> [max plies per game]
+use std::env::args;
+use std::fs;
+use std::process::exit;
+
+const DEFAULT_MAX_PLIES: usize = 20;
+
+fn main() {
+    let args: Vec<String> = args().collect();
+    if args.len() < 3 {
+        eprintln!("Usage: {} <input.pgn> <output.bin> [max plies per game]", args[0]);
+        exit(1);
+    }
+
+    let max_plies = args.get(3).map(|s| s.parse().unwrap_or(DEFAULT_MAX_PLIES)).unwrap_or(DEFAULT_MAX_PLIES);
+    let result = fs::read_to_string(&args[1]).map_err(|err| format!("Cannot read {}: {err}", args[1]))
+        .and_then(|pgn| shakmat_engine::create_book(&pgn, max_plies))
+        .and_then(|book| fs::write(&args[2], &book).map(|_| book.len() / 16).map_err(|err| format!("Cannot write {}: {err}", args[2])));
+
+    match result {
+        Ok(entries) => println!("Created a book with {entries} entries"),
+        Err(err) => {
+            eprintln!("{err}");
+            exit(1);
+        }
+    }
+}
@@ -0,0 +1,37 @@
+// Tunes the evaluation parameters using a dataset of positions and game results.
+// Usage: shakmat-tune <dataset> <output file> [iterations] [initial parameters file]
+use std::env::args;
+use std::fs;
+use std::process::exit;
+use shakmat_engine::{init_evaluation, read_tuning_dataset, set_eval_params, tune, tuning_loss, EvalParams};
+
+const DEFAULT_ITERATIONS: usize = 10;
+
+fn main() {
+    let args: Vec<String> = args().collect();
+    if args.len() < 3 {
+        eprintln!("Usage: {} <dataset> <output file> [iterations] [initial parameters file]", args[0]);
+        exit(1);
+    }
+
+    if let Err(err) = run(&args) {
+        eprintln!("{err}");
+        exit(1);
+    }
+}
+
+fn run(args: &[String]) -> Result<(), String> {
+    init_evaluation();
+    let iterations = args.get(3).map(|s| s.parse().unwrap_or(DEFAULT_ITERATIONS)).unwrap_or(DEFAULT_ITERATIONS);
+    if let Some(path) = args.get(4) {
+        set_eval_params(EvalParams::load(path)?);
+    }
+
+    let text = fs::read_to_string(&args[1]).map_err(|err| format!("Cannot read {}: {err}", args[1]))?;
+    let dataset = read_tuning_dataset(&text)?;
+    println!("Loaded {} positions, initial loss: {:.6}", dataset.len(), tuning_loss(&dataset));
+
+    let params = tune(&dataset, iterations);
+    println!("Final loss: {:.6}", tuning_loss(&dataset));
+    fs::write(&args[2], params.to_string()).map_err(|err| format!("Cannot write {}: {err}", args[2]))
+}
@@ -1,53 +1,329 @@
-use shakmat_core::Board;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::thread;
+use shakmat_core::{Board, Move};
 
-use crate::evaluation::Evaluation;
+use crate::evaluation::{Evaluation, EvalMode, Network};
 use crate::polyglot::OpeningBook;
-use crate::search::{SearchResult, SearchOptions, Search};
+use crate::search::{SearchResult, SearchOptions, SearchInfo, Search};
+use crate::syzygy::Tablebase;
+use crate::trasposition::{TTable, MAX_TABLE_MB};
+
+// Default size of the trasposition table, in MB (2^22 entries)
+const DEFAULT_HASH_MB: usize = 64;
 
 pub struct ShakmatEngine {
     book: OpeningBook,
     config: EngineConfig,
+    network: Option<Network>,
+    tablebase: Option<Tablebase>,
+    tt: TTable,
 }
 
+// Settings of the engine. The defaults are those of EngineConfig::default(),
+// and any of them can be changed using EngineConfig::builder()
 pub struct EngineConfig {
-    pub use_opening_book: bool,
-    pub only_best_book_moves: bool,
+    pub use_opening_book: bool, // true by default
+    pub only_best_book_moves: bool, // Always play the most common book move, true by default
+    pub book_path: Option<String>, // Polyglot book to use instead of the bundled one, none by default
+    pub nnue_path: Option<String>, // NNUE network to use instead of the classical evaluation, none by default
+    pub syzygy_path: Option<String>, // Directory with the Syzygy tablebases to probe during the search, none by default
+    pub eval_mode: EvalMode, // Classical evaluation to use if there is no NNUE network, EvalMode::Full by default
+    pub contempt: i16, // Centipawns that a draw is worth less than an equal position, 0 by default
+    pub threads: usize, // Number of threads to search with, 1 by default
+    pub hash_mb: usize, // Size of the trasposition table in MB, rounded down to a power of 2 number of entries. 64 by default, 4096 at most
+}
+
+// Builds an EngineConfig starting from the default one, so that only
+// the settings that differ from it have to be provided
+pub struct EngineConfigBuilder {
+    config: EngineConfig,
 }
 
 impl ShakmatEngine {
-    pub fn new(config: EngineConfig) -> Self {
-        Self { config, book: OpeningBook::load() }
+    // Fails if any of the settings is invalid, or if any of the
+    // files in the config can't be loaded
+    pub fn new(config: EngineConfig) -> Result<Self, String> {
+        check_hash_size(config.hash_mb)?;
+        let network = load_network(&config)?;
+        let tablebase = load_tablebase(&config)?;
+        let book = load_book(&config)?;
+        let tt = new_ttable(config.hash_mb);
+        Ok(Self { config, network, tablebase, tt, book })
+    }
+
+    // Forgets everything learned in previous searches, so that the
+    // results of a new game don't depend on the ones played before
+    pub fn new_game(&mut self) {
+        self.tt.clear();
     }
 
     pub fn find_best_move(&self, board: &Board, past_positions: &[u64], options: SearchOptions) -> SearchResult {
+        self.find_best_move_with_info(board, past_positions, options, |_| {})
+    }
+
+    // Same as find_best_move(), but `on_info` is called with the
+    // results of every iteration of the search as they are completed
+    pub fn find_best_move_with_info<F: FnMut(SearchInfo)>(
+        &self,
+        board: &Board,
+        past_positions: &[u64],
+        options: SearchOptions,
+        on_info: F,
+    ) -> SearchResult {
         if self.config.use_opening_book {
             // Query our opening book to get a move for this position
-            if let Some(mv) = self.book.get_move(board, self.config.only_best_book_moves) {
-                // We know this opening line, play the move from the book
-                println!("Book move");
-                return SearchResult { best_move: Some(mv), score: Evaluation::new(0) }
+            let only_best = options.only_best_book_moves.unwrap_or(self.config.only_best_book_moves);
+            if let Some(mv) = self.book.get_move(board, only_best, options.min_book_weight) {
+                // We know this opening line, play the move from the book.
+                // The position isn't evaluated, so the score is just 0.
+                return SearchResult {
+                    best_move: Some(mv),
+                    score: Evaluation::new(0),
+                    pv: vec![mv],
+                    nodes: 0,
+                    depth_reached: 0,
+                    elapsed_ms: 0,
+                    from_book: true,
+                }
             }
         }
 
         // Otherwise do a normal search for the best move
-        let result = Search::from_config(options, past_positions).find_best(board);
-        println!("Evaluation: {}", result.score);
-        result
+        self.analyze_with_info(board, past_positions, options, on_info)
+    }
+
+    // Searches the position even if it's in the opening book, since the score
+    // and the PV are what matter when analyzing it rather than playing
+    pub fn analyze(&self, board: &Board, past_positions: &[u64], options: SearchOptions) -> SearchResult {
+        self.analyze_with_info(board, past_positions, options, |_| {})
     }
 
-    pub fn update_config(&mut self, config: EngineConfig) {
+    // Same as analyze(), but `on_info` is called with the results of every
+    // iteration of the search as they are completed
+    pub fn analyze_with_info<F: FnMut(SearchInfo)>(
+        &self,
+        board: &Board,
+        past_positions: &[u64],
+        options: SearchOptions,
+        on_info: F,
+    ) -> SearchResult {
+        let tt = &self.tt;
+        tt.new_search();
+        if self.config.threads <= 1 {
+            return self.create_search(options, past_positions, tt).with_info_callback(on_info).find_best(board);
+        }
+
+        // Lazy SMP: the helper threads search the same position, sharing the
+        // trasposition table with the main one, which fills it faster and lets
+        // the main thread take advantage of what the others found
+        let stop = AtomicBool::new(false);
+        let shared_nodes = AtomicU64::new(0);
+        thread::scope(|scope| {
+            let helpers: Vec<_> = (1..self.config.threads).map(|thread_id| {
+                let (options, stop, shared_nodes) = (options.clone(), &stop, &shared_nodes);
+                scope.spawn(move || self.create_search(options, past_positions, tt)
+                    .into_helper(thread_id, stop)
+                    .with_shared_nodes(shared_nodes)
+                    .find_best(board))
+            }).collect();
+
+            let mut result = self.create_search(options, past_positions, tt)
+                .with_shared_nodes(&shared_nodes)
+                .with_info_callback(on_info)
+                .find_best(board);
+            stop.store(true, Ordering::Relaxed);
+
+            // Use the result of the thread that got the deepest, preferring
+            // the main one in case of a tie, see is_better_result()
+            let mut nodes = result.nodes;
+            for helper in helpers {
+                let helper_result = helper.join().unwrap();
+                nodes += helper_result.nodes;
+                if helper_result.best_move.is_some() && is_better_result(&helper_result, &result) {
+                    result = helper_result;
+                }
+            }
+
+            result.nodes = nodes;
+            result
+        })
+    }
+
+    // The moves in the opening book for a position and their weights, the most
+    // common first. The book is queried even if the engine doesn't use it.
+    pub fn book_moves(&self, board: &Board) -> Vec<(Move, u16)> {
+        self.book.get_moves(board)
+    }
+
+    // Searches for the best `options.multipv` moves and their lines, best first.
+    // The opening book is not used, since this is meant for analysis.
+    pub fn find_best_moves(&self, board: &Board, past_positions: &[u64], options: SearchOptions) -> Vec<SearchResult> {
+        self.tt.new_search();
+        let mut search = self.create_search(options, past_positions, &self.tt);
+        search.find_best_lines(board)
+    }
+
+    // Evaluates the position after the captures available in it are played out,
+    // which is more reliable than the static evaluation in tactical positions.
+    // The score is from the point of view of the side to move.
+    pub fn quiescence_eval(&self, board: &Board, past_positions: &[u64]) -> Evaluation {
+        self.create_search(SearchOptions::default(), past_positions, &self.tt).quiescence_eval(board)
+    }
+
+    // Searches without the opening book or helper threads, for reproducible results
+    pub(crate) fn search_single_thread(&self, board: &Board, options: SearchOptions) -> SearchResult {
+        self.tt.new_search();
+        self.create_search(options, &[], &self.tt).find_best(board)
+    }
+
+    // If any of the settings is invalid, or a new file can't be
+    // loaded, the engine is left as it was
+    pub fn update_config(&mut self, config: EngineConfig) -> Result<(), String> {
+        check_hash_size(config.hash_mb)?;
+        let network = if config.nnue_path != self.config.nnue_path { Some(load_network(&config)?) } else { None };
+        let tablebase = if config.syzygy_path != self.config.syzygy_path { Some(load_tablebase(&config)?) } else { None };
+        let book = if config.book_path != self.config.book_path { Some(load_book(&config)?) } else { None };
+
+        if let Some(network) = network {
+            self.network = network;
+        }
+        if let Some(tablebase) = tablebase {
+            self.tablebase = tablebase;
+        }
+        if let Some(book) = book {
+            self.book = book;
+        }
+        if config.hash_mb != self.config.hash_mb {
+            self.tt = new_ttable(config.hash_mb);
+        }
         self.config = config;
+        Ok(())
+    }
+
+    fn create_search<'a>(&'a self, options: SearchOptions, past_positions: &[u64], tt: &'a TTable) -> Search<'a> {
+        Search::from_config(
+            options,
+            past_positions,
+            tt,
+            self.network.as_ref(),
+            self.tablebase.as_ref(),
+            self.config.eval_mode,
+            self.config.contempt,
+        )
     }
 }
 
 impl Default for ShakmatEngine {
     fn default() -> Self {
-        Self::new(EngineConfig::default())
+        // The default config doesn't have any file to load
+        Self::new(EngineConfig::default()).unwrap()
     }
 }
 
 impl Default for EngineConfig {
     fn default() -> Self {
-        Self { only_best_book_moves: true, use_opening_book: true }
+        Self { only_best_book_moves: true, use_opening_book: true, book_path: None, nnue_path: None, syzygy_path: None, eval_mode: EvalMode::Full, contempt: 0, threads: 1, hash_mb: DEFAULT_HASH_MB }
     }
-}
\ No newline at end of file
+}
+
+impl EngineConfig {
+    pub fn builder() -> EngineConfigBuilder {
+        EngineConfigBuilder { config: Self::default() }
+    }
+}
+
+impl EngineConfigBuilder {
+    pub fn use_opening_book(mut self, use_opening_book: bool) -> Self {
+        self.config.use_opening_book = use_opening_book;
+        self
+    }
+
+    pub fn only_best_book_moves(mut self, only_best_book_moves: bool) -> Self {
+        self.config.only_best_book_moves = only_best_book_moves;
+        self
+    }
+
+    pub fn book_path(mut self, book_path: Option<String>) -> Self {
+        self.config.book_path = book_path;
+        self
+    }
+
+    pub fn nnue_path(mut self, nnue_path: Option<String>) -> Self {
+        self.config.nnue_path = nnue_path;
+        self
+    }
+
+    pub fn syzygy_path(mut self, syzygy_path: Option<String>) -> Self {
+        self.config.syzygy_path = syzygy_path;
+        self
+    }
+
+    pub fn eval_mode(mut self, eval_mode: EvalMode) -> Self {
+        self.config.eval_mode = eval_mode;
+        self
+    }
+
+    pub fn contempt(mut self, contempt: i16) -> Self {
+        self.config.contempt = contempt;
+        self
+    }
+
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.config.threads = threads;
+        self
+    }
+
+    pub fn hash_mb(mut self, hash_mb: usize) -> Self {
+        self.config.hash_mb = hash_mb;
+        self
+    }
+
+    pub fn build(self) -> EngineConfig {
+        self.config
+    }
+}
+
+// A forced mate is already exact, so a thread that found one is preferred
+// over threads that got deeper without seeing it. Between two mates, the
+// best score is the quickest win or the slowest loss.
+fn is_better_result(candidate: &SearchResult, current: &SearchResult) -> bool {
+    match (candidate.score.is_mate(), current.score.is_mate()) {
+        (true, true) => candidate.score > current.score,
+        (true, false) => true,
+        (false, true) => false,
+        (false, false) => candidate.depth_reached > current.depth_reached,
+    }
+}
+
+fn check_hash_size(hash_mb: usize) -> Result<(), String> {
+    if (1..=MAX_TABLE_MB).contains(&hash_mb) {
+        Ok(())
+    } else {
+        Err(format!("The hash size must be between 1 and {MAX_TABLE_MB} MB, found {hash_mb}"))
+    }
+}
+
+fn new_ttable(hash_mb: usize) -> TTable {
+    let mut tt = TTable::with_size_mb(hash_mb);
+    tt.clear();
+    tt
+}
+
+// Without a book file, the bundled one is used instead
+fn load_book(config: &EngineConfig) -> Result<OpeningBook, String> {
+    match config.book_path.as_deref() {
+        Some(path) => OpeningBook::from_file(Path::new(path)).map_err(|err| format!("Cannot load the opening book {path}: {err}")),
+        None => Ok(OpeningBook::load()),
+    }
+}
+
+// Without a network, the classical evaluation is used instead
+fn load_network(config: &EngineConfig) -> Result<Option<Network>, String> {
+    config.nnue_path.as_deref().map(Network::load).transpose()
+}
+
+// Without tablebases, endgames are searched like any other position
+fn load_tablebase(config: &EngineConfig) -> Result<Option<Tablebase>, String> {
+    config.syzygy_path.as_deref().map(Tablebase::load).transpose()
+}
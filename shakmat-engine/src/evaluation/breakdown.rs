@@ -0,0 +1,104 @@
+use std::fmt::{Display, Formatter};
+use shakmat_core::Color::{self, *};
+
+use super::{Evaluation, EvalScore};
+use super::evaluate::ScorePair;
+
+// The different groups of terms that make up the evaluation
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum EvalTerm {
+    Material, Psqt, Mobility, Pawns, Passed, Pieces, KingSafety, Threats, BishopPair, Tempo,
+    Scaling, // How much the endgame score changes when scaled down in drawish endings
+}
+
+pub const EVAL_TERMS: [EvalTerm; 11] = [
+    EvalTerm::Material, EvalTerm::Psqt, EvalTerm::Mobility, EvalTerm::Pawns, EvalTerm::Passed,
+    EvalTerm::Pieces, EvalTerm::KingSafety, EvalTerm::Threats, EvalTerm::BishopPair, EvalTerm::Tempo,
+    EvalTerm::Scaling,
+];
+
+// Contribution of every term of the evaluation, for each color and game phase.
+// The scores of each color are from their own point of view, so the total
+// for a term is the white score minus the black one.
+pub struct EvalBreakdown {
+    scores: [[ScorePair; 2]; EVAL_TERMS.len()], // Indexed by term and color
+    pub game_phase: EvalScore, // From 0 (middlegame) to 256 (endgame)
+    pub evaluation: Evaluation, // Final score, from the point of view of the side to move
+}
+
+impl EvalBreakdown {
+    pub fn new() -> Self {
+        Self { scores: [[(0, 0); 2]; EVAL_TERMS.len()], game_phase: 0, evaluation: Evaluation::new(0) }
+    }
+
+    pub fn add(&mut self, color: Color, term: EvalTerm, (mg, eg): ScorePair) {
+        let score = &mut self.scores[term as usize][color.to_index()];
+        score.0 += mg;
+        score.1 += eg;
+    }
+
+    pub fn score(&self, color: Color, term: EvalTerm) -> ScorePair {
+        self.scores[term as usize][color.to_index()]
+    }
+
+    // Total score of a term, from white's point of view
+    pub fn term_total(&self, term: EvalTerm) -> ScorePair {
+        let (white, black) = (self.score(White, term), self.score(Black, term));
+        (white.0 - black.0, white.1 - black.1)
+    }
+
+    // Sum of all the terms, from white's point of view
+    pub fn total(&self) -> ScorePair {
+        EVAL_TERMS.iter().map(|&term| self.term_total(term)).fold((0, 0), |a, b| (a.0 + b.0, a.1 + b.1))
+    }
+}
+
+impl Default for EvalBreakdown {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Display for EvalTerm {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        let name = match self {
+            Self::Material => "Material",
+            Self::Psqt => "PSQT",
+            Self::Mobility => "Mobility",
+            Self::Pawns => "Pawns",
+            Self::Passed => "Passed",
+            Self::Pieces => "Pieces",
+            Self::KingSafety => "King safety",
+            Self::Threats => "Threats",
+            Self::BishopPair => "Bishop pair",
+            Self::Tempo => "Tempo",
+            Self::Scaling => "Scaling",
+        };
+
+        f.pad(name)
+    }
+}
+
+// Prints a table similar to Stockfish's "eval" command, in pawns
+impl Display for EvalBreakdown {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        let pawns = |(mg, eg): ScorePair| format!("{:6.2} {:6.2}", mg as f32 / 100.0, eg as f32 / 100.0);
+        let separator = "+-------------+---------------+---------------+---------------+";
+
+        writeln!(f, "{separator}")?;
+        writeln!(f, "|    Term     |     White     |     Black     |     Total     |")?;
+        writeln!(f, "|             |   MG     EG   |   MG     EG   |   MG     EG   |")?;
+        writeln!(f, "{separator}")?;
+
+        for term in EVAL_TERMS {
+            writeln!(f, "| {term:<11} | {} | {} | {} |",
+                     pawns(self.score(White, term)), pawns(self.score(Black, term)), pawns(self.term_total(term)))?;
+        }
+
+        writeln!(f, "{separator}")?;
+        writeln!(f, "| {:<11} | {:>13} | {:>13} | {} |", "Total", "", "", pawns(self.total()))?;
+        writeln!(f, "{separator}")?;
+        writeln!(f, "Game phase: {}/256", self.game_phase)?;
+        write!(f, "Final evaluation: {} (side to move)", self.evaluation)
+    }
+}
@@ -1,10 +1,17 @@
-use shakmat_core::{Board, Pieces, BitBoard, Color::{*, self}};
-use super::{Evaluation, EvalScore, masks};
+use shakmat_core::{Board, Pieces, BitBoard, Color::{*, self}, PieceType};
+use super::{Evaluation, EvalScore, EvalBreakdown, EvalTerm, EvalParams, masks};
+use super::evaluate::ScorePair;
+
+// The evaluation is scaled by (FIFTY_MOVE_SCALE - halfmoves) / FIFTY_MOVE_SCALE,
+// using the halfmoves since the last capture or pawn move. With 200, it's
+// halved right when the fifty move rule is reached.
+const FIFTY_MOVE_SCALE: i32 = 200;
 
 // Auxiliary struct to store values that are used in different parts
 // of the evaluation, to avoid calculating them multiple times
 pub struct EvalData<'a> {
     pub board: &'a Board,
+    pub params: &'a EvalParams,
     pub game_phase: EvalScore,
     pub score_midgame: EvalScore,
     pub score_endgame: EvalScore,
@@ -19,22 +26,19 @@ pub struct EvalData<'a> {
 
     // Info about the safe mobility squares, i.e., not controlled by enemy pawns 
     pub safe_mobility_area: [BitBoard; 2],
+
+    // Squares attacked by each color and piece type, filled while evaluating the pieces
+    pub attacked_by: [[BitBoard; 6]; 2],
+
+    // Contribution of every term, only when a verbose evaluation is requested
+    pub breakdown: Option<EvalBreakdown>,
 }
 
 
 impl<'a> EvalData<'a> {
-    pub fn new(board: &'a Board) -> Self {
+    pub fn new(board: &'a Board, params: &'a EvalParams) -> Self {
         let black_pieces = board.get_pieces(Black);
-        let br = black_pieces.rooks.count() as EvalScore;
-        let bn = black_pieces.knights.count() as EvalScore;
-        let bb = black_pieces.bishops.count() as EvalScore;
-        let bq = black_pieces.queens.count() as EvalScore;
-
         let white_pieces = board.get_pieces(White);
-        let wr = white_pieces.rooks.count() as EvalScore;
-        let wn = white_pieces.knights.count() as EvalScore;
-        let wb = white_pieces.bishops.count() as EvalScore;
-        let wq = white_pieces.queens.count() as EvalScore;
 
         let attackers_count = [0; 2];
         let attacks_weight = [0; 2];
@@ -47,25 +51,38 @@ impl<'a> EvalData<'a> {
         let king_outer_rings = [masks::king_outer_ring(black_king_pos),
                                 masks::king_outer_ring(white_king_pos)]; 
         let safe_mobility_area = [BitBoard::ones(); 2];
+        let attacked_by = [[BitBoard::new(0); 6]; 2];
 
-        let mut game_phase = 24;
-        game_phase -= wn + bn + wb + bb;
-        game_phase -= 2 * (wr + br);
-        game_phase -= 4 * (wq + bq);
-        game_phase = (game_phase * 256 + 12) / 24;
+        let game_phase = board.game_phase() as EvalScore;
 
-        Self {board, white_pieces, black_pieces, safe_mobility_area,
+        Self {board, params, white_pieces, black_pieces, safe_mobility_area, attacked_by,
              attackers_count, attacks_weight, king_inner_rings, king_outer_rings,
-             game_phase, score_endgame: 0, score_midgame: 0}
+             game_phase, score_endgame: 0, score_midgame: 0, breakdown: None}
     }
 
     pub fn compute_score(&self) -> Evaluation {
         // The values are temporarily promoted to i32 to avoid overflowing when
         // multiplying by the game phase
         let eval = ((self.score_midgame as i32 * (256 - self.game_phase as i32)) + (self.score_endgame as i32 * self.game_phase as i32)) / 256;
+
+        // An advantage is worth less the closer the fifty move rule is to
+        // making the game a draw, so that moves which reset it are preferred
+        let halfmoves = self.board.fifty_move_rule_counter().min(100) as i32;
+        let eval = eval * (FIFTY_MOVE_SCALE - halfmoves) / FIFTY_MOVE_SCALE;
         Evaluation::new(eval as EvalScore * self.board.turn_color().sign() as EvalScore)
     }
 
+    pub fn add_attacks(&mut self, color: Color, piece: PieceType, attacks: BitBoard) {
+        self.attacked_by[color.to_index()][piece.to_index()] |= attacks;
+    }
+
+    // Records the score of an evaluation term, if we are keeping track of them
+    pub fn trace(&mut self, color: Color, term: EvalTerm, score: ScorePair) {
+        if let Some(breakdown) = &mut self.breakdown {
+            breakdown.add(color, term, score);
+        }
+    }
+
     pub fn get_pieces(&self, color: Color) -> &Pieces {
         match color {
             Black => self.black_pieces,
@@ -1,7 +1,8 @@
-use std::fmt::{Formatter, Display};
+use std::fmt::{Debug, Formatter, Display};
 use std::ops::{Neg, Add, Sub};
-use shakmat_core::{Board, Color::{*, self}, BitBoard, PieceType::{*, self}, move_gen};
-use super::{piece_tables, EvalData, masks};
+use shakmat_core::{Board, Color::{*, self}, BitBoard, Pieces, PieceType::{*, self}, move_gen};
+use super::{piece_tables, EvalData, EvalBreakdown, EvalTerm, masks, params};
+use super::pawn_hash::probe_pawn_score;
 
 pub type EvalScore = i16;
 pub type ScorePair = (EvalScore, EvalScore);
@@ -12,50 +13,28 @@ pub type ScorePair = (EvalScore, EvalScore);
 #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Evaluation { score: EvalScore } 
 
-// The contempt factor is the score that the engine associates with a draw.
-// A negative value means that the engine assumes it is superior to its opponent,
-// so drawing is penalized. Conversely, a positive value means that the engine assumes
-// itself to be inferior, so it encourages drawing when it cannot find a decisive advantage.
-const CONTEMPT: EvalScore = 0;
-
-// Bonuses and penalties, measured in centipawns
-// Values that are pairs represent the scores for the middlegame and endgame phases
-const PAWN_BASE_VALUE: EvalScore = 100;
-const BISHOP_BASE_VALUE: EvalScore = 300;
-const KNIGHT_BASE_VALUE: EvalScore = 300;
-const ROOK_BASE_VALUE: EvalScore = 500;
-const QUEEN_BASE_VALUE: EvalScore = 900;
-
-const TEMPO_BONUS: EvalScore = 28;
-const BISHOP_PAIR_BONUS: ScorePair = (20, 60);
-const ROOK_OPEN_FILE_BONUS: ScorePair = (50, 25);
-const ROOK_SEMIOPEN_FILE_BONUS: ScorePair = (20, 10);
-const ROOK_CLOSED_FILE_PENALTY: ScorePair = (-10, -5);
-const PASSED_PAWN_BONUS: [ScorePair; 7] = [
-    (0, 0), (10, 1), (5, 5), (1, 25), (15, 50), (50, 100), (100, 150)
-];
-const CONNECTED_PAWN_BONUS: [EvalScore; 7] = [0, 5, 10, 10, 15, 55, 85];
-
-// Attack values for the different pieces for the outer and inner rings
-const MINOR_PIECE_ATTACK: ScorePair = (8, 21);
-const ROOK_ATTACK: ScorePair = (7, 18);
-const QUEEN_ATTACK: ScorePair = (14, 33);
-
-// Danger values for a king on a semi-open file or with semi-open flanks
-const KING_SEMIOPEN_FILE_DANGER: EvalScore = 70;
-const KING_SEMIOPEN_FLANK_DANGER: EvalScore = 50;
-
-// King danger reduction if the opponent doesn't have a queen
-const NO_QUEEN_DANGER_RED: EvalScore = 800;
-
-// Penalties for a king under different attack values
-const ATTACKED_PENALTIES: [EvalScore; 64] = [0,0,-1,-2,-4,-6,-8,-11,-14,-18,-21,-25,-30,-35,-40,-45,-51,-57,-63,-69,-76,-83,-91,-98,-106,-114,-123,-132,-141,-150,-159,-169,-179,-189,-200,-211,-222,-233,-245,-257,-269,-281,-294,-306,-319,-333,-346,-360,-374,-388,-403,-418,-433,-448,-463,-479,-495,-511,-527,-544,-561,-578,-595,-613];
-
-// Bonuses and penalties for the mobility of different pieces
-const KNIGHT_MOBILITY_BONUS: [ScorePair; 9] = [(-62, -79), (-53, -57), (-12, -31), (-3, -17), (3, 7), (12, 13), (21, 16), (28, 21), (37, 26)];
-const BISHOP_MOBILITY_BONUS: [ScorePair; 14] = [(-47, -59), (-20, -25), (14, -8), (29, 12), (39, 21), (53, 40), (53, 56), (60, 58), (62, 65), (69, 72), (78, 78), (83, 87), (91, 88), (96, 98)];
-const ROOK_MOBILITY_BONUS: [ScorePair; 15] = [(-60, -82), (-24,-15), (0, 17), (3, 43), (4, 72), (14, 100), (20, 102), (30, 122), (41, 133), (41, 139), (41, 153), (45, 160), (57, 165), (58, 170), (67, 175)];
-const QUEEN_MOBILITY_BONUS: [ScorePair; 28] = [(-29, -49), (-16, -29), (-8, -8), (-8, 17), (18, 39), (25, 54), (23, 59), (37, 73), (41, 76), (54, 95), (65, 95), (68, 101), (69, 124), (70, 128), (70, 132), (70, 133), (71, 136), (72, 140), (74, 147), (76, 149), (90, 153), (104, 169), (105, 171), (106, 171), (112, 178), (114, 185), (114, 187), (119, 221)];
+// Which evaluation the search uses. The material-only one just adds up the
+// values of the pieces, their PSQT scores and the tempo bonus, which is much
+// faster but less accurate.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+pub enum EvalMode {
+    #[default]
+    Full,
+    MaterialOnly,
+}
+
+// The bonuses and penalties are defined in params.rs, so that they can be tuned
+
+// Factors to scale down the endgame score in endings that are hard to win
+// even with an advantage, out of SCALE_NORMAL. See endgame_scale() for
+// the endings that each of them applies to.
+const SCALE_NORMAL: i32 = 64;
+const SCALE_OPPOSITE_BISHOPS: i32 = 32;
+const SCALE_ONE_PAWN: i32 = 32;
+const SCALE_NO_PAWNS: i32 = 8;
+const SCALE_DRAW: i32 = 0;
+
+const LIGHT_SQUARES: BitBoard = BitBoard::new(0xAA55AA55AA55AA55);
 
 // Evaluate how favorable a position is for the current side to move
 // We always calculate it so that positive scores favor white, while
@@ -63,15 +42,48 @@ const QUEEN_MOBILITY_BONUS: [ScorePair; 28] = [(-29, -49), (-16, -29), (-8, -8),
 // eval_data.compute_score() adapts the final sign to make it from
 // the point of view of the current player.
 pub fn evaluate_position(board: &Board) -> Evaluation {
-    let mut eval_data = EvalData::new(board);
+    let params = params();
+    let mut eval_data = EvalData::new(board, &params);
+    calc_all_terms(&mut eval_data);
+    eval_data.compute_score()
+}
 
-    calc_piece_score(&mut eval_data);
+// Stripped down evaluation for the EvalMode::MaterialOnly mode
+pub fn evaluate_material_only(board: &Board) -> Evaluation {
+    let params = params();
+    let mut eval_data = EvalData::new(board, &params);
+    calc_material(&mut eval_data);
     calc_positional_score(&mut eval_data);
-    calc_bishop_pair_bonus(&mut eval_data);
     calc_tempo(&mut eval_data);
     eval_data.compute_score()
 }
 
+// Same as evaluate_position(), but also returns the contribution of
+// every term, for debugging and tuning. It's slower, so the search
+// must never use it.
+pub fn evaluate_position_verbose(board: &Board) -> EvalBreakdown {
+    let params = params();
+    let mut eval_data = EvalData::new(board, &params);
+    eval_data.breakdown = Some(EvalBreakdown::new());
+    calc_all_terms(&mut eval_data);
+
+    let mut breakdown = eval_data.breakdown.take().unwrap();
+    breakdown.game_phase = eval_data.game_phase;
+    breakdown.evaluation = eval_data.compute_score();
+    breakdown
+}
+
+fn calc_all_terms(eval_data: &mut EvalData) {
+    calc_piece_score(eval_data);
+    calc_pawn_structure(eval_data);
+    calc_threats(eval_data);
+    calc_positional_score(eval_data);
+    calc_bishop_pair_bonus(eval_data);
+    calc_tempo(eval_data);
+    // Must go last, since it scales the endgame score of all the other terms
+    calc_endgame_scaling(eval_data);
+}
+
 // Computes the total piece score of a color, using the specialized functions
 // It's very important that we evaluate the different pieces in the current order,
 // since some evaluation terms depend on things that are calculated during the
@@ -103,84 +115,304 @@ fn calc_piece_score(eval_data: &mut EvalData) {
     eval_data.score_endgame += wp_eg + wb_eg + wn_eg + wr_eg + wq_eg + wk_eg - bp_eg - bb_eg - bn_eg - br_eg - bq_eg - bk_eg;
 }
 
+// Only the base values of the pieces, which the full evaluation
+// adds while evaluating every piece
+fn calc_material(eval_data: &mut EvalData) {
+    let params = eval_data.params;
+
+    for color in [White, Black] {
+        let pieces = eval_data.get_pieces(color);
+        let material = pieces.pawns.count() as EvalScore * params.pawn_base_value
+            + pieces.knights.count() as EvalScore * params.knight_base_value
+            + pieces.bishops.count() as EvalScore * params.bishop_base_value
+            + pieces.rooks.count() as EvalScore * params.rook_base_value
+            + pieces.queens.count() as EvalScore * params.queen_base_value;
+
+        let sign = color.sign() as EvalScore;
+        eval_data.score_midgame += material * sign;
+        eval_data.score_endgame += material * sign;
+        eval_data.trace(color, EvalTerm::Material, (material, material));
+    }
+}
+
 // Gives positional bonuses to each piece using the corresponding table,
 // for both the middlegame and endgame phases.
 fn calc_positional_score(eval_data: &mut EvalData) {
     let wp = eval_data.white_pieces;
     let bp = eval_data.black_pieces;
 
-    add_pos_scores(eval_data, wp.pawns, &piece_tables::WHITE_PAWNS);
-    add_pos_scores(eval_data, wp.rooks, &piece_tables::WHITE_ROOKS);
-    add_pos_scores(eval_data, wp.knights, &piece_tables::WHITE_KNIGHTS);
-    add_pos_scores(eval_data, wp.bishops, &piece_tables::WHITE_BISHOPS);
-    add_pos_scores(eval_data, wp.queens, &piece_tables::WHITE_QUEENS);
-    add_pos_scores(eval_data, wp.king, &piece_tables::WHITE_KING);
+    let white_score = [
+        pos_scores(wp.pawns, &piece_tables::WHITE_PAWNS),
+        pos_scores(wp.rooks, &piece_tables::WHITE_ROOKS),
+        pos_scores(wp.knights, &piece_tables::WHITE_KNIGHTS),
+        pos_scores(wp.bishops, &piece_tables::WHITE_BISHOPS),
+        pos_scores(wp.queens, &piece_tables::WHITE_QUEENS),
+        pos_scores(wp.king, &piece_tables::WHITE_KING),
+    ].into_iter().fold((0, 0), |a, b| (a.0 + b.0, a.1 + b.1));
+
+    let black_score = [
+        pos_scores(bp.pawns, &piece_tables::BLACK_PAWNS),
+        pos_scores(bp.rooks, &piece_tables::BLACK_ROOKS),
+        pos_scores(bp.knights, &piece_tables::BLACK_KNIGHTS),
+        pos_scores(bp.bishops, &piece_tables::BLACK_BISHOPS),
+        pos_scores(bp.queens, &piece_tables::BLACK_QUEENS),
+        pos_scores(bp.king, &piece_tables::BLACK_KING),
+    ].into_iter().fold((0, 0), |a, b| (a.0 + b.0, a.1 + b.1));
+
+    eval_data.score_midgame += white_score.0 - black_score.0;
+    eval_data.score_endgame += white_score.1 - black_score.1;
+    eval_data.trace(White, EvalTerm::Psqt, white_score);
+    eval_data.trace(Black, EvalTerm::Psqt, black_score);
+}
+
+// The pawn structure terms only depend on the pawns, so their score is
+// cached using the pawn key of the board. The verbose evaluation skips
+// the cache, since it must trace every term.
+fn calc_pawn_structure(eval_data: &mut EvalData) {
+    let (mg, eg) = if eval_data.breakdown.is_some() {
+        pawn_structure_score(eval_data)
+    } else {
+        let key = eval_data.board.pawn_key();
+        probe_pawn_score(key, || pawn_structure_score(eval_data))
+    };
+
+    eval_data.score_midgame += mg;
+    eval_data.score_endgame += eg;
+}
+
+// Total pawn structure score, from white's point of view
+fn pawn_structure_score(eval_data: &mut EvalData) -> ScorePair {
+    let (w_mg, w_eg) = eval_data.white_pieces.pawns.piece_indices()
+        .map(|pos| eval_pawn_structure(White, pos, eval_data))
+        .fold((0, 0), |a, b| (a.0 + b.0, a.1 + b.1));
+    let (b_mg, b_eg) = eval_data.black_pieces.pawns.piece_indices()
+        .map(|pos| eval_pawn_structure(Black, pos, eval_data))
+        .fold((0, 0), |a, b| (a.0 + b.0, a.1 + b.1));
 
-    sub_pos_scores(eval_data, bp.pawns, &piece_tables::BLACK_PAWNS);
-    sub_pos_scores(eval_data, bp.rooks, &piece_tables::BLACK_ROOKS);
-    sub_pos_scores(eval_data, bp.knights, &piece_tables::BLACK_KNIGHTS);
-    sub_pos_scores(eval_data, bp.bishops, &piece_tables::BLACK_BISHOPS);
-    sub_pos_scores(eval_data, bp.queens, &piece_tables::BLACK_QUEENS);
-    sub_pos_scores(eval_data, bp.king, &piece_tables::BLACK_KING);
+    (w_mg - b_mg, w_eg - b_eg)
 }
 
 fn calc_bishop_pair_bonus(eval_data: &mut EvalData) {
-    let bonus_early = BISHOP_PAIR_BONUS.0;
-    let bonus_late = BISHOP_PAIR_BONUS.1;
+    let params = eval_data.params;
+    let bonus_early = params.bishop_pair_bonus.0;
+    let bonus_late = params.bishop_pair_bonus.1;
 
     let white_pair = (eval_data.white_pieces.bishops.count() >= 2) as EvalScore;
     let black_pair = (eval_data.black_pieces.bishops.count() >= 2) as EvalScore;
     
     eval_data.score_midgame += bonus_early * white_pair - bonus_early * black_pair;
     eval_data.score_endgame += bonus_late * white_pair - bonus_late * black_pair;
+    eval_data.trace(White, EvalTerm::BishopPair, (bonus_early * white_pair, bonus_late * white_pair));
+    eval_data.trace(Black, EvalTerm::BishopPair, (bonus_early * black_pair, bonus_late * black_pair));
+}
+
+// Bonuses for the enemy pieces that we attack with less valuable pieces,
+// and for the ones that we attack and are not defended at all. It uses
+// the attacks calculated during the evaluation of the pieces.
+fn calc_threats(eval_data: &mut EvalData) {
+    let params = eval_data.params;
+
+    for color in [White, Black] {
+        let (us, them) = (color.to_index(), (!color).to_index());
+        let enemy_pieces = eval_data.get_pieces(!color);
+        let all_attacks = eval_data.attacked_by[us].iter().fold(BitBoard::new(0), |acc, &bb| acc | bb);
+        let all_defenses = eval_data.attacked_by[them].iter().fold(BitBoard::new(0), |acc, &bb| acc | bb);
+
+        // Our attacks with pieces worth less than each kind of target
+        let by_pawns = eval_data.attacked_by[us][Pawn.to_index()];
+        let by_minors = by_pawns | eval_data.attacked_by[us][Knight.to_index()] | eval_data.attacked_by[us][Bishop.to_index()];
+        let by_rooks = by_minors | eval_data.attacked_by[us][Rook.to_index()];
+
+        let threatened = (enemy_pieces.knights & by_pawns) | (enemy_pieces.bishops & by_pawns)
+            | (enemy_pieces.rooks & by_minors) | (enemy_pieces.queens & by_rooks);
+        let non_king = enemy_pieces.pawns | enemy_pieces.knights | enemy_pieces.bishops | enemy_pieces.rooks | enemy_pieces.queens;
+        let hanging = non_king & all_attacks & !all_defenses;
+
+        let (n_threats, n_hanging) = (threatened.count() as EvalScore, hanging.count() as EvalScore);
+        let score = (params.threat_by_lesser_piece.0 * n_threats + params.hanging_piece_bonus.0 * n_hanging,
+                     params.threat_by_lesser_piece.1 * n_threats + params.hanging_piece_bonus.1 * n_hanging);
+
+        let sign = color.sign() as EvalScore;
+        eval_data.score_midgame += score.0 * sign;
+        eval_data.score_endgame += score.1 * sign;
+        eval_data.trace(color, EvalTerm::Threats, score);
+    }
 }
 
 fn calc_tempo(eval_data: &mut EvalData) {
     // Small bonus for having the right to move, only
     // in the early game
-    eval_data.score_midgame += TEMPO_BONUS;
+    let params = eval_data.params;
+    eval_data.score_midgame += params.tempo_bonus;
+    eval_data.trace(White, EvalTerm::Tempo, (params.tempo_bonus, 0));
+}
+
+fn calc_endgame_scaling(eval_data: &mut EvalData) {
+    let scale = endgame_scale(eval_data);
+    if scale == SCALE_NORMAL {
+        return;
+    }
+
+    let scaled = (eval_data.score_endgame as i32 * scale / SCALE_NORMAL) as EvalScore;
+    eval_data.trace(White, EvalTerm::Scaling, (0, scaled - eval_data.score_endgame));
+    eval_data.score_endgame = scaled;
+}
+
+// The endings are checked from the most to the least drawish, and
+// the first one that matches the position sets the scale
+fn endgame_scale(eval_data: &EvalData) -> i32 {
+    let strong_color = match eval_data.score_endgame {
+        0 => return SCALE_NORMAL,
+        score if score > 0 => White,
+        _ => Black,
+    };
+
+    let strong = eval_data.get_pieces(strong_color);
+    let weak = eval_data.get_pieces(!strong_color);
+    let (strong_material, weak_material) = (non_pawn_material(strong), non_pawn_material(weak));
+
+    // Without pawns, a single minor piece or two knights can't force mate
+    if strong.pawns.is_empty() && (strong_material <= 3 || strong_material == 6 && strong.knights.count() == 2) {
+        return SCALE_DRAW;
+    }
+
+    // A bishop and rook pawns whose promotion square is of the other color than
+    // the bishop, with the enemy king in front of them, can't make progress
+    if is_wrong_rook_pawn(strong_color, strong, weak.king) {
+        return SCALE_DRAW;
+    }
+
+    // Without pawns, being a minor piece or less ahead isn't usually enough
+    if strong.pawns.is_empty() && strong_material - weak_material <= 3 {
+        return SCALE_NO_PAWNS;
+    }
+
+    // Opposite colored bishops, with nothing else but pawns: the weaker side can
+    // often build a blockade on the squares of their bishop's color
+    let others = strong.knights | strong.rooks | strong.queens | weak.knights | weak.rooks | weak.queens;
+    if others.is_empty() && strong.bishops.count() == 1 && weak.bishops.count() == 1
+        && (strong.bishops & LIGHT_SQUARES).is_empty() != (weak.bishops & LIGHT_SQUARES).is_empty() {
+        return SCALE_OPPOSITE_BISHOPS;
+    }
+
+    // A single pawn against the same pieces, such as KRP vs KR, is often held
+    if strong.pawns.count() == 1 && weak.pawns.is_empty() && strong_material == weak_material {
+        return SCALE_ONE_PAWN;
+    }
+
+    SCALE_NORMAL
+}
+
+// Material of the pieces other than pawns and the king, in pawns
+fn non_pawn_material(pieces: &Pieces) -> i32 {
+    3 * (pieces.knights.count() + pieces.bishops.count()) as i32
+        + 5 * pieces.rooks.count() as i32 + 9 * pieces.queens.count() as i32
+}
+
+fn is_wrong_rook_pawn(color: Color, pieces: &Pieces, enemy_king: BitBoard) -> bool {
+    if (pieces.knights | pieces.rooks | pieces.queens).is_not_empty() || pieces.bishops.count() != 1 || pieces.pawns.is_empty() {
+        return false;
+    }
+
+    // All the pawns must be on the same rook file. The squares are numbered
+    // from h1 (0) to a8 (63), so files A and H are those of a1 (7) and h1 (0).
+    let promotion_square = match (color, pieces.pawns) {
+        (White, pawns) if (pawns & !masks::file(0)).is_empty() => 56,
+        (White, pawns) if (pawns & !masks::file(7)).is_empty() => 63,
+        (Black, pawns) if (pawns & !masks::file(0)).is_empty() => 0,
+        (Black, pawns) if (pawns & !masks::file(7)).is_empty() => 7,
+        _ => return false,
+    };
+
+    let promotion_bb = BitBoard::from_square(promotion_square);
+    let light_promotion = (promotion_bb & LIGHT_SQUARES).is_not_empty();
+    let light_bishop = (pieces.bishops & LIGHT_SQUARES).is_not_empty();
+    light_promotion != light_bishop && (enemy_king & masks::king_inner_ring(promotion_square)).is_not_empty()
 }
 
 ///////////////////////////////////////////////////////////////////////////////
 /// Specialized functions for each piece type
 fn eval_pawn(color: Color, pos: u8, _: BitBoard, eval_data: &mut EvalData) -> ScorePair {
-    let mut mg = PAWN_BASE_VALUE;
-    let mut eg = PAWN_BASE_VALUE;
+    let params = eval_data.params;
+    let (mg, eg) = (params.pawn_base_value, params.pawn_base_value);
     let them = (!color).to_index();
+    eval_data.trace(color, EvalTerm::Material, (mg, eg));
 
     // Check the squares controlled by this pawn
     let attack_bb = move_gen::pawn_attacks(pos as usize, color);
     eval_data.safe_mobility_area[them] &= !attack_bb;
+    eval_data.add_attacks(color, Pawn, attack_bb);
+
+    (mg, eg)
+}
+
+// Pawn structure terms, which must only depend on the placement of the pawns
+fn eval_pawn_structure(color: Color, pos: u8, eval_data: &mut EvalData) -> ScorePair {
+    let params = eval_data.params;
+    let (mut mg, mut eg) = (0, 0);
+    let attack_bb = move_gen::pawn_attacks(pos as usize, color);
 
     // Check if this is a passed pawn, and add bonuses acordingly
-    let (enemy_pawns, passed_mask, rel_rank) = match color {
-        White => (eval_data.black_pieces.pawns, masks::white_passed_pawn(pos), pos / 8),
-        Black => (eval_data.white_pieces.pawns, masks::black_passed_pawn(pos), 7 - (pos / 8)),
+    let (our_pawns, enemy_pawns, passed_mask, rel_rank) = match color {
+        White => (eval_data.white_pieces.pawns, eval_data.black_pieces.pawns, masks::white_passed_pawn(pos), pos / 8),
+        Black => (eval_data.black_pieces.pawns, eval_data.white_pieces.pawns, masks::black_passed_pawn(pos), 7 - (pos / 8)),
     };
 
     if (enemy_pawns & passed_mask).is_empty() {
         // This pawn is a passer, assign a bonus depending on its relative rank
-        let (mg_bonus, eg_bonus) = PASSED_PAWN_BONUS[rel_rank as usize];
+        let (mg_bonus, eg_bonus) = params.passed_pawn_bonus[rel_rank as usize];
         mg += mg_bonus;
         eg += eg_bonus;
+        eval_data.trace(color, EvalTerm::Passed, (mg_bonus, eg_bonus));
     }
 
     // Check if this pawn is connected to friendly pawns
-    let our_pawns = match color {
-        Black => eval_data.black_pieces.pawns,
-        White => eval_data.white_pieces.pawns,
-    };
     if (attack_bb & our_pawns).is_not_empty() {
-        let bonus = CONNECTED_PAWN_BONUS[rel_rank as usize];
+        let bonus = params.connected_pawn_bonus[rel_rank as usize];
         mg += bonus;
         eg += bonus;
+        eval_data.trace(color, EvalTerm::Pawns, (bonus, bonus));
+    }
+
+    // Doubled pawns: only the ones with a friendly pawn in front of them
+    // get the penalty, so that it's applied once for every extra pawn
+    if (passed_mask & masks::file(pos) & our_pawns).is_not_empty() {
+        mg += params.doubled_pawn_penalty.0;
+        eg += params.doubled_pawn_penalty.1;
+        eval_data.trace(color, EvalTerm::Pawns, params.doubled_pawn_penalty);
+    }
+
+    // Isolated pawns, with no friendly pawns in the adjacent files
+    let adjacent_pawns = masks::adjacent_files(pos) & our_pawns;
+    if adjacent_pawns.is_empty() {
+        mg += params.isolated_pawn_penalty.0;
+        eg += params.isolated_pawn_penalty.1;
+        eval_data.trace(color, EvalTerm::Pawns, params.isolated_pawn_penalty);
+    } else {
+        // Backward pawns: all the adjacent friendly pawns are ahead of this one,
+        // so none can support its advance, and an enemy pawn controls the
+        // square in front of it. Pawns never stand on the last rank, so
+        // there is always a square in front.
+        let (stop_square, level_or_behind) = match color {
+            White => (pos + 8, masks::black_passed_pawn(pos + 8)),
+            Black => (pos - 8, masks::white_passed_pawn(pos - 8)),
+        };
+
+        let stop_attacked = (move_gen::pawn_attacks(stop_square as usize, color) & enemy_pawns).is_not_empty();
+        if stop_attacked && (adjacent_pawns & level_or_behind).is_empty() {
+            mg += params.backward_pawn_penalty.0;
+            eg += params.backward_pawn_penalty.1;
+            eval_data.trace(color, EvalTerm::Pawns, params.backward_pawn_penalty);
+        }
     }
 
     (mg, eg)
 }
 
 fn eval_bishop(color: Color, pos: u8, _: BitBoard, eval_data: &mut EvalData) -> ScorePair {
-    let (mut mg, mut eg) = (BISHOP_BASE_VALUE, BISHOP_BASE_VALUE);
+    let params = eval_data.params;
+    let (mut mg, mut eg) = (params.bishop_base_value, params.bishop_base_value);
+    eval_data.trace(color, EvalTerm::Material, (mg, eg));
     let us = color.to_index();
 
     // Check if this bishop attacks the enemy king rings.
@@ -188,40 +420,60 @@ fn eval_bishop(color: Color, pos: u8, _: BitBoard, eval_data: &mut EvalData) ->
     // when calculating bishop attacks to the enemy king
     let our_queens_mask = !eval_data.board.get_pieces(color).queens;
     let attack_bb = move_gen::bishop_moves(pos as usize, eval_data.board.get_all_bitboard() & our_queens_mask);
-    add_attack_values(color, attack_bb, eval_data, MINOR_PIECE_ATTACK);
+    add_attack_values(color, attack_bb, eval_data, params.minor_piece_attack);
 
     // Calculate the mobility score for this bishop
     let moves = move_gen::bishop_moves(pos as usize, eval_data.board.get_all_bitboard());
+    eval_data.add_attacks(color, Bishop, moves);
     let safe_moves = (moves & eval_data.safe_mobility_area[us]).count() as usize;
 
-    let (mg_mob_bonus, eg_mob_bonus) = BISHOP_MOBILITY_BONUS[safe_moves];
+    let (mg_mob_bonus, eg_mob_bonus) = params.bishop_mobility_bonus[safe_moves];
     mg += mg_mob_bonus;
     eg += eg_mob_bonus;
+    eval_data.trace(color, EvalTerm::Mobility, (mg_mob_bonus, eg_mob_bonus));
+
+    if is_outpost(color, pos, eval_data) {
+        mg += params.bishop_outpost_bonus.0;
+        eg += params.bishop_outpost_bonus.1;
+        eval_data.trace(color, EvalTerm::Pieces, params.bishop_outpost_bonus);
+    }
 
     (mg, eg)
 }
 
 fn eval_knight(color: Color, pos: u8, _: BitBoard, eval_data: &mut EvalData) -> ScorePair {
-    let (mut mg, mut eg) = (KNIGHT_BASE_VALUE, KNIGHT_BASE_VALUE);
+    let params = eval_data.params;
+    let (mut mg, mut eg) = (params.knight_base_value, params.knight_base_value);
+    eval_data.trace(color, EvalTerm::Material, (mg, eg));
     let us = color.to_index();
 
     // Check if this knight attacks the enemy king ring.
     let attack_bb = move_gen::knight_moves(pos as usize);
-    add_attack_values(color, attack_bb, eval_data, MINOR_PIECE_ATTACK);
+    add_attack_values(color, attack_bb, eval_data, params.minor_piece_attack);
+    eval_data.add_attacks(color, Knight, attack_bb);
 
     // Calculate the mobility score for this knight
     let safe_moves = (attack_bb & eval_data.safe_mobility_area[us]).count() as usize;
 
-    let (mg_mob_bonus, eg_mob_bonus) = KNIGHT_MOBILITY_BONUS[safe_moves];
+    let (mg_mob_bonus, eg_mob_bonus) = params.knight_mobility_bonus[safe_moves];
     mg += mg_mob_bonus;
     eg += eg_mob_bonus;
+    eval_data.trace(color, EvalTerm::Mobility, (mg_mob_bonus, eg_mob_bonus));
+
+    if is_outpost(color, pos, eval_data) {
+        mg += params.knight_outpost_bonus.0;
+        eg += params.knight_outpost_bonus.1;
+        eval_data.trace(color, EvalTerm::Pieces, params.knight_outpost_bonus);
+    }
 
     (mg, eg)
 }
 
 fn eval_rook(color: Color, pos: u8, bb: BitBoard, eval_data: &mut EvalData) -> ScorePair {
-    let mut mg = ROOK_BASE_VALUE;
-    let mut eg = ROOK_BASE_VALUE;
+    let params = eval_data.params;
+    let mut mg = params.rook_base_value;
+    let mut eg = params.rook_base_value;
+    eval_data.trace(color, EvalTerm::Material, (mg, eg));
     let us = color.to_index();
 
     // Check if this rook attacks the enemy king ring.
@@ -229,15 +481,17 @@ fn eval_rook(color: Color, pos: u8, bb: BitBoard, eval_data: &mut EvalData) -> S
     // when calculating rook attacks to the enemy king
     let our_pieces_mask = !(eval_data.board.get_pieces(color).queens | bb);
     let attack_bb = move_gen::rook_moves(pos as usize, eval_data.board.get_all_bitboard() & our_pieces_mask);
-    add_attack_values(color, attack_bb, eval_data, ROOK_ATTACK);
+    add_attack_values(color, attack_bb, eval_data, params.rook_attack);
 
     // Calculate the mobility score for this rook
     let moves = move_gen::rook_moves(pos as usize, eval_data.board.get_all_bitboard());
+    eval_data.add_attacks(color, Rook, moves);
     let safe_moves = (moves & eval_data.safe_mobility_area[us]).count() as usize;
 
-    let (mg_mob_bonus, eg_mob_bonus) = ROOK_MOBILITY_BONUS[safe_moves];
+    let (mg_mob_bonus, eg_mob_bonus) = params.rook_mobility_bonus[safe_moves];
     mg += mg_mob_bonus;
     eg += eg_mob_bonus;
+    eval_data.trace(color, EvalTerm::Mobility, (mg_mob_bonus, eg_mob_bonus));
 
     let file = masks::file(pos);
     let (friendly_pawns, enemy_pawns) = match color {
@@ -248,35 +502,62 @@ fn eval_rook(color: Color, pos: u8, bb: BitBoard, eval_data: &mut EvalData) -> S
     // Check if the rook is in a closed, semi-open or open file
     if (file & friendly_pawns).is_not_empty() {
         // Friendly pawns on this file, we consider it closed and substract a penalty
-        mg += ROOK_CLOSED_FILE_PENALTY.0;
-        eg += ROOK_CLOSED_FILE_PENALTY.1;
+        mg += params.rook_closed_file_penalty.0;
+        eg += params.rook_closed_file_penalty.1;
+        eval_data.trace(color, EvalTerm::Pieces, params.rook_closed_file_penalty);
     } else if (file & enemy_pawns).is_not_empty() {
         // Only enemy pawns, we consider it semi-open and add a bonus
-        mg += ROOK_SEMIOPEN_FILE_BONUS.0;
-        eg += ROOK_SEMIOPEN_FILE_BONUS.1;
+        mg += params.rook_semiopen_file_bonus.0;
+        eg += params.rook_semiopen_file_bonus.1;
+        eval_data.trace(color, EvalTerm::Pieces, params.rook_semiopen_file_bonus);
     } else {
         // No pawns, we consider it open
-        mg += ROOK_OPEN_FILE_BONUS.0;
-        eg += ROOK_OPEN_FILE_BONUS.1;
+        mg += params.rook_open_file_bonus.0;
+        eg += params.rook_open_file_bonus.1;
+        eval_data.trace(color, EvalTerm::Pieces, params.rook_open_file_bonus);
+    }
+
+    // Check if the rook is on the 7th rank from our point of view
+    let enemy_king_pos = eval_data.get_pieces(!color).king.first_piece_index();
+    let (rel_rank, enemy_king_rel_rank) = match color {
+        White => (pos / 8, enemy_king_pos / 8),
+        Black => (7 - (pos / 8), 7 - (enemy_king_pos / 8)),
+    };
+
+    if rel_rank == 6 {
+        mg += params.rook_seventh_rank_bonus.0;
+        eg += params.rook_seventh_rank_bonus.1;
+        eval_data.trace(color, EvalTerm::Pieces, params.rook_seventh_rank_bonus);
+
+        // The enemy king is confined to its back rank
+        if enemy_king_rel_rank == 7 {
+            mg += params.rook_seventh_king_bonus.0;
+            eg += params.rook_seventh_king_bonus.1;
+            eval_data.trace(color, EvalTerm::Pieces, params.rook_seventh_king_bonus);
+        }
     }
 
     (mg, eg)
 }
 
 fn eval_queen(color: Color, pos: u8, _: BitBoard, eval_data: &mut EvalData) -> ScorePair {
-    let (mut mg, mut eg) = (QUEEN_BASE_VALUE, QUEEN_BASE_VALUE);
+    let params = eval_data.params;
+    let (mut mg, mut eg) = (params.queen_base_value, params.queen_base_value);
+    eval_data.trace(color, EvalTerm::Material, (mg, eg));
     let us = color.to_index();
 
     // Check if this queen attacks the enemy king ring.
     let attack_bb = move_gen::queen_moves(pos as usize, eval_data.board.get_all_bitboard());
-    add_attack_values(color, attack_bb, eval_data, QUEEN_ATTACK);
+    add_attack_values(color, attack_bb, eval_data, params.queen_attack);
+    eval_data.add_attacks(color, Queen, attack_bb);
 
     // Calculate the mobility score for this queen
     let safe_moves = (attack_bb & eval_data.safe_mobility_area[us]).count() as usize;
 
-    let (mg_mob_bonus, eg_mob_bonus) = QUEEN_MOBILITY_BONUS[safe_moves];
+    let (mg_mob_bonus, eg_mob_bonus) = params.queen_mobility_bonus[safe_moves];
     mg += mg_mob_bonus;
     eg += eg_mob_bonus;
+    eval_data.trace(color, EvalTerm::Mobility, (mg_mob_bonus, eg_mob_bonus));
 
     (mg, eg)
 }
@@ -285,6 +566,7 @@ fn eval_queen(color: Color, pos: u8, _: BitBoard, eval_data: &mut EvalData) -> S
 // follow the path of our lord and savior Stockfish and compute a safety value
 // by multiplying the number of attackers with the total weight of their attacks
 fn eval_king(color: Color, pos: u8, _: BitBoard, eval_data: &mut EvalData) -> ScorePair {
+    let params = eval_data.params;
     let (mut mg, eg) = (0, 0);
     let enemy = !color;
     let our_pawns = match color {
@@ -292,6 +574,8 @@ fn eval_king(color: Color, pos: u8, _: BitBoard, eval_data: &mut EvalData) -> Sc
         White => eval_data.white_pieces.pawns,
     };
 
+    eval_data.add_attacks(color, King, move_gen::king_moves(pos as usize));
+
     let file = pos % 8;
     let king_file_mask = masks::file(pos);
 
@@ -301,29 +585,50 @@ fn eval_king(color: Color, pos: u8, _: BitBoard, eval_data: &mut EvalData) -> Sc
 
     // Assignate a penalty if the king is in a semi-open file
     if (our_pawns & king_file_mask).is_empty() {
-        threat += KING_SEMIOPEN_FILE_DANGER;
+        threat += params.king_semiopen_file_danger;
     }
 
     // Penalty if the king has semi-open flanks to its sides
     // The right flank is analyzed if the king is not on the H file
     if file != 0 && (our_pawns & (king_file_mask >> 1)).is_empty() {
-        threat += KING_SEMIOPEN_FLANK_DANGER;
+        threat += params.king_semiopen_flank_danger;
     }
 
     // And the left flank is analyzed if the king is not on the A file
     if file != 7 && (our_pawns & (king_file_mask << 1)).is_empty() {
-        threat += KING_SEMIOPEN_FLANK_DANGER;
+        threat += params.king_semiopen_flank_danger;
     }
 
     // Reduce king danger if the enemy doesn't have a queen
     let enemy_queens = eval_data.get_pieces(enemy).queens;
-    threat -= NO_QUEEN_DANGER_RED * enemy_queens.is_empty() as EvalScore;
+    threat -= params.no_queen_danger_red * enemy_queens.is_empty() as EvalScore;
 
     // Index the king safety penalty using the threat value and
     // setting it to 0 if it's negative
     let threat_index = threat.max(0);
-    mg += ATTACKED_PENALTIES[(threat_index as usize / 8).min(ATTACKED_PENALTIES.len() - 1)];
+    mg += params.attacked_penalties[(threat_index as usize / 8).min(params.attacked_penalties.len() - 1)];
+
+    // Pawn shield: look for the closest friendly pawn in front of
+    // the king in its file and the adjacent ones
+    let front_mask = match color {
+        White => masks::white_passed_pawn(pos),
+        Black => masks::black_passed_pawn(pos),
+    };
 
+    let shield_files = [Some(pos), (file != 0).then(|| pos - 1), (file != 7).then(|| pos + 1)];
+    for shield_file in shield_files.into_iter().flatten() {
+        let closest = (front_mask & masks::file(shield_file) & our_pawns).piece_indices()
+            .map(|pawn| (pawn / 8).abs_diff(pos / 8) as usize)
+            .min();
+
+        mg += match closest {
+            Some(distance) if distance < params.pawn_shield_bonus.len() => params.pawn_shield_bonus[distance],
+            _ => params.missing_shield_pawn_penalty,
+        };
+    }
+
+    // Everything that the king evaluates is related to its safety
+    eval_data.trace(color, EvalTerm::KingSafety, (mg, eg));
     (mg, eg)
 }
 
@@ -344,6 +649,23 @@ fn eval_bitboard(piece_color: Color, piece_type: PieceType, bb: BitBoard, eval_d
       .fold((0, 0), |a, b| (a.0 + b.0, a.1 + b.1))
 }
 
+///////////////////////////////////////////////////////////////////////////////
+/// Aux function to check whether a minor piece is on an outpost, i.e., a square in
+/// the opponent's half of the board that is defended by one of our pawns and that
+/// can never be attacked by an enemy pawn
+fn is_outpost(color: Color, pos: u8, eval_data: &EvalData) -> bool {
+    let (our_pawns, enemy_pawns, front_mask, in_enemy_half) = match color {
+        White => (eval_data.white_pieces.pawns, eval_data.black_pieces.pawns, masks::white_passed_pawn(pos), pos / 8 >= 4),
+        Black => (eval_data.black_pieces.pawns, eval_data.white_pieces.pawns, masks::black_passed_pawn(pos), pos / 8 < 4),
+    };
+
+    // The enemy pawns that could attack this square are the ones in the
+    // adjacent files that are further ahead from our point of view
+    let defended = (move_gen::pawn_attacks(pos as usize, !color) & our_pawns).is_not_empty();
+    let attackable = (front_mask & masks::adjacent_files(pos) & enemy_pawns).is_not_empty();
+    in_enemy_half && defended && !attackable
+}
+
 ///////////////////////////////////////////////////////////////////////////////
 /// Aux function to add attack values from a certain piece to the enemy king
 fn add_attack_values(color: Color, attack_bb: BitBoard, eval_data: &mut EvalData, weights: ScorePair) {
@@ -355,23 +677,13 @@ fn add_attack_values(color: Color, attack_bb: BitBoard, eval_data: &mut EvalData
 }
 
 ///////////////////////////////////////////////////////////////////////////////
-/// Aux functions to add/substract positional scores
-fn add_pos_scores(eval_data: &mut EvalData, bb: BitBoard, table: &[ScorePair]) {
-    bb.piece_indices().for_each(|pos| {
-        // All positions are <64, so it's safe to skip bounds checking
-        let (mg, eg) = unsafe { table.get_unchecked(pos as usize) };
-        eval_data.score_midgame += mg;
-        eval_data.score_endgame += eg;
-    });
-}
-
-fn sub_pos_scores(eval_data: &mut EvalData, bb: BitBoard, table: &[ScorePair]) {
-    bb.piece_indices().for_each(|pos| {
+/// Aux function to add up the positional scores of a set of pieces
+fn pos_scores(bb: BitBoard, table: &[ScorePair]) -> ScorePair {
+    bb.piece_indices().fold((0, 0), |(mg_acc, eg_acc), pos| {
         // All positions are <64, so it's safe to skip bounds checking
         let (mg, eg) = unsafe { table.get_unchecked(pos as usize) };
-        eval_data.score_midgame -= mg;
-        eval_data.score_endgame -= eg;
-    });
+        (mg_acc + mg, eg_acc + eg)
+    })
 }
 
 ///////////////////////////////////////////////////////////////////////////////
@@ -381,9 +693,13 @@ impl Evaluation {
         Self { score }
     }
 
-    pub const fn contempt() -> Self {
-        Self::new(CONTEMPT)
-    } 
+    // The contempt factor determines the score that the engine associates with a draw.
+    // A positive value means that the engine assumes it is superior to its opponent,
+    // so drawing is penalized. Conversely, a negative value means that the engine assumes
+    // itself to be inferior, so it encourages drawing when it cannot find a decisive advantage.
+    pub const fn contempt(contempt: EvalScore) -> Self {
+        Self::new(-contempt)
+    }
 
     // The min value is set to i16::MIN + 1, so that -min_val() == max_val()
     // and viceversa. Otherwise, it overflows when swapping its sign
@@ -411,6 +727,17 @@ impl Evaluation {
     pub fn is_mate(&self) -> bool {
         self.is_negative_mate() || self.is_positive_mate()
     }
+
+    // Number of moves until mate, negative if it's the side to move who gets mated
+    pub fn moves_to_mate(&self) -> Option<EvalScore> {
+        if self.is_positive_mate() {
+            Some((EvalScore::MAX - self.score()) / 2)
+        } else if self.is_negative_mate() {
+            Some(-(self.score() - EvalScore::MIN - 1) / 2)
+        } else {
+            None
+        }
+    }
 }
 
 impl Neg for Evaluation {
@@ -465,14 +792,22 @@ impl PartialEq<EvalScore> for Evaluation {
     }
 }
 
+// Shows the raw score, and the moves to mate if it's a mate score
+impl Debug for Evaluation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Evaluation")
+            .field("score", &self.score)
+            .field("mate", &self.moves_to_mate())
+            .finish()
+    }
+}
+
 impl Display for Evaluation {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        if self.is_positive_mate() {
-            write!(f, "M{}", (EvalScore::MAX - self.score()) / 2)
-        } else if self.is_negative_mate() {
-            write!(f, "-M{}", (self.score() - EvalScore::MIN - 1) / 2)
-        } else {
-            write!(f, "{:+.2}", self.score() as f32 / 100.0)
+        match self.moves_to_mate() {
+            Some(moves) if self.is_positive_mate() => write!(f, "M{moves}"),
+            Some(moves) => write!(f, "-M{}", -moves),
+            None => write!(f, "{:+.2}", self.score() as f32 / 100.0),
         }
     }
 }
@@ -28,6 +28,11 @@ pub fn init_evaluation() {
             let file_diff = (file as isize - other_file as isize).abs();
             let rank_diff = (rank as isize - other_rank as isize).abs();
 
+            // The files to both sides of this one
+            if file_diff == 1 {
+                unsafe { masks::ADJACENT_FILES[pos] |= bb };
+            }
+
             // Inner ring
             if file_diff <= 1 && rank_diff <= 1 {
                 unsafe { masks::KING_INNER_RING[pos] |= bb }
@@ -4,6 +4,7 @@ use shakmat_core::BitBoard;
 // They are generated automatically on startup using evaluation::init_evaluation()
 pub static mut FILES: [BitBoard; 64] = [BitBoard::new(0); 64];
 pub static mut RANKS: [BitBoard; 64] = [BitBoard::new(0); 64];
+pub static mut ADJACENT_FILES: [BitBoard; 64] = [BitBoard::new(0); 64];
 pub static mut WHITE_PASSED_PAWN: [BitBoard; 64] = [BitBoard::new(0); 64];
 pub static mut BLACK_PASSED_PAWN: [BitBoard; 64] = [BitBoard::new(0); 64];
 pub static mut KING_INNER_RING: [BitBoard; 64] = [BitBoard::new(0); 64];
@@ -16,6 +17,10 @@ pub fn file(pos: u8) -> BitBoard {
     unsafe { FILES[pos as usize] }
 }
 
+pub fn adjacent_files(pos: u8) -> BitBoard {
+    unsafe { ADJACENT_FILES[pos as usize] }
+}
+
 pub fn white_passed_pawn(pos: u8) -> BitBoard {
     unsafe { WHITE_PASSED_PAWN[pos as usize] }
 }
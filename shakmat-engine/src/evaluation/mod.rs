@@ -1,9 +1,18 @@
+mod breakdown;
 mod evaluate;
 mod eval_data;
 mod init;
 mod masks;
+mod nnue;
+mod params;
+mod pawn_hash;
 mod piece_tables;
+mod tuning;
 
-pub use evaluate::{Evaluation, EvalScore, evaluate_position};
+pub use breakdown::{EvalBreakdown, EvalTerm, EVAL_TERMS};
+pub use evaluate::{Evaluation, EvalScore, EvalMode, evaluate_position, evaluate_material_only, evaluate_position_verbose};
 pub use eval_data::EvalData;
-pub use init::init_evaluation;
\ No newline at end of file
+pub use init::init_evaluation;
+pub use nnue::{Network, Accumulator};
+pub use params::{params, params_version, set_eval_params, EvalParams};
+pub use tuning::{read_tuning_dataset, tuning_loss, tune, TuningEntry};
\ No newline at end of file
@@ -0,0 +1,179 @@
+use std::fs;
+
+use shakmat_core::{BitBoard, Board, Color::{self, *}, Move, PieceType::{self, *}};
+use super::{Evaluation, EvalScore};
+
+// The network has one input for each combination of color, piece type and square,
+// which feed a hidden layer that is computed from the point of view of both sides.
+// The two halves of the hidden layer are then concatenated, side to move first,
+// and connected to a single output neuron.
+pub const INPUT_SIZE: usize = 768;
+pub const HIDDEN_SIZE: usize = 256;
+
+// Quantization factors for the weights of the hidden and output layers,
+// and the scale to convert the output of the network into centipawns
+const QA: i64 = 255;
+const QB: i64 = 64;
+const EVAL_SCALE: i64 = 400;
+
+// Keep the network scores away from the mate scores
+const MAX_NNUE_SCORE: i64 = 10000;
+
+// The network file contains the following values as little-endian i16s:
+// feature weights (INPUT_SIZE x HIDDEN_SIZE), feature biases (HIDDEN_SIZE),
+// output weights (2 x HIDDEN_SIZE) and the output bias
+const NETWORK_VALUES: usize = INPUT_SIZE * HIDDEN_SIZE + HIDDEN_SIZE + 2 * HIDDEN_SIZE + 1;
+
+pub struct Network {
+    feature_weights: Vec<i16>,
+    feature_biases: Vec<i16>,
+    output_weights: Vec<i16>,
+    output_bias: i16,
+}
+
+// The values of the hidden layer from the point of view of each side. They are
+// updated incrementally when pieces move, instead of computing them from scratch.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Accumulator {
+    white: [i16; HIDDEN_SIZE],
+    black: [i16; HIDDEN_SIZE],
+}
+
+impl Network {
+    pub fn load(path: &str) -> Result<Self, String> {
+        let bytes = fs::read(path).map_err(|err| format!("Cannot read the NNUE network {path}: {err}"))?;
+        if bytes.len() != NETWORK_VALUES * 2 {
+            return Err(format!("Invalid NNUE network {path}: expected {} bytes, found {}", NETWORK_VALUES * 2, bytes.len()));
+        }
+
+        let mut values = bytes.chunks_exact(2).map(|pair| i16::from_le_bytes([pair[0], pair[1]]));
+        let feature_weights = values.by_ref().take(INPUT_SIZE * HIDDEN_SIZE).collect();
+        let feature_biases = values.by_ref().take(HIDDEN_SIZE).collect();
+        let output_weights = values.by_ref().take(2 * HIDDEN_SIZE).collect();
+        let output_bias = values.next().unwrap();
+
+        Ok(Self { feature_weights, feature_biases, output_weights, output_bias })
+    }
+
+    fn weights(&self, feature: usize) -> &[i16] {
+        &self.feature_weights[feature * HIDDEN_SIZE..(feature + 1) * HIDDEN_SIZE]
+    }
+}
+
+impl Accumulator {
+    // Computes the accumulator for a board from scratch
+    pub fn new(net: &Network, board: &Board) -> Self {
+        let mut acc = Self { white: [0; HIDDEN_SIZE], black: [0; HIDDEN_SIZE] };
+        acc.white.copy_from_slice(&net.feature_biases);
+        acc.black.copy_from_slice(&net.feature_biases);
+
+        for color in [White, Black] {
+            for piece in [Pawn, Knight, Bishop, Rook, Queen, King] {
+                for square in board.get_pieces(color).get_pieces_of_type(piece).piece_indices() {
+                    acc.add_feature(net, color, piece, square);
+                }
+            }
+        }
+
+        acc
+    }
+
+    // Returns the accumulator for the board that results from making a move in the
+    // provided board, which must be the one this accumulator was computed for
+    pub fn make_move(&self, net: &Network, board: &Board, mv: &Move) -> Self {
+        let mut acc = *self;
+        let color = board.turn_color();
+
+        match mv {
+            Move::ShortCastle | Move::LongCastle => {
+                let row_start = if color == White { 0 } else { 56 };
+                let (king_to, rook_from, rook_to) = if matches!(mv, Move::ShortCastle) {
+                    (row_start + 1, row_start, row_start + 2)
+                } else {
+                    (row_start + 5, row_start + 7, row_start + 4)
+                };
+
+                acc.move_feature(net, color, King, row_start + 3, king_to);
+                acc.move_feature(net, color, Rook, rook_from, rook_to);
+            },
+            Move::Normal { from, to } | Move::PawnPromotion { from, to, .. } => {
+                let piece = mv.piece_moving(board);
+
+                if let Some(captured) = mv.piece_captured(board) {
+                    acc.remove_feature(net, !color, captured, *to);
+                } else if piece == Pawn && (BitBoard::from_square(*to) & board.ep_square()).is_not_empty() {
+                    let ep_target = if color == White { to - 8 } else { to + 8 };
+                    acc.remove_feature(net, !color, Pawn, ep_target);
+                }
+
+                let placed = mv.promotion_piece().unwrap_or(piece);
+
+                acc.remove_feature(net, color, piece, *from);
+                acc.add_feature(net, color, placed, *to);
+            },
+        }
+
+        acc
+    }
+
+    pub fn add_feature(&mut self, net: &Network, color: Color, piece: PieceType, square: u8) {
+        let (white_idx, black_idx) = perspective_indices(color, piece, square);
+        add_weights(&mut self.white, net.weights(white_idx));
+        add_weights(&mut self.black, net.weights(black_idx));
+    }
+
+    pub fn remove_feature(&mut self, net: &Network, color: Color, piece: PieceType, square: u8) {
+        let (white_idx, black_idx) = perspective_indices(color, piece, square);
+        sub_weights(&mut self.white, net.weights(white_idx));
+        sub_weights(&mut self.black, net.weights(black_idx));
+    }
+
+    fn move_feature(&mut self, net: &Network, color: Color, piece: PieceType, from: u8, to: u8) {
+        self.remove_feature(net, color, piece, from);
+        self.add_feature(net, color, piece, to);
+    }
+
+    // Forward pass of the network, returns the score for the side to move
+    pub fn evaluate(&self, net: &Network, color: Color) -> Evaluation {
+        let (ours, theirs) = match color {
+            White => (&self.white, &self.black),
+            Black => (&self.black, &self.white),
+        };
+
+        let (our_weights, their_weights) = net.output_weights.split_at(HIDDEN_SIZE);
+        let output = crelu_dot(ours, our_weights) + crelu_dot(theirs, their_weights) + net.output_bias as i64;
+        let score = output * EVAL_SCALE / (QA * QB);
+        Evaluation::new(score.clamp(-MAX_NNUE_SCORE, MAX_NNUE_SCORE) as EvalScore)
+    }
+}
+
+// Index of the input of the network for a piece of a certain color on a square,
+// from white's point of view. Black's point of view is the same one with the
+// colors swapped and the board flipped vertically.
+pub fn feature_index(color: Color, piece: PieceType, square: u8) -> usize {
+    let color_offset = if color == White { 0 } else { 384 };
+    color_offset + piece.to_index() * 64 + square as usize
+}
+
+fn perspective_indices(color: Color, piece: PieceType, square: u8) -> (usize, usize) {
+    (feature_index(color, piece, square), feature_index(!color, piece, square ^ 56))
+}
+
+// The network may come from anywhere, so its weights could overflow the
+// accumulator. Wrapping keeps the incremental updates consistent with
+// computing it from scratch, instead of panicking.
+fn add_weights(values: &mut [i16; HIDDEN_SIZE], weights: &[i16]) {
+    values.iter_mut().zip(weights).for_each(|(val, &w)| *val = val.wrapping_add(w));
+}
+
+fn sub_weights(values: &mut [i16; HIDDEN_SIZE], weights: &[i16]) {
+    values.iter_mut().zip(weights).for_each(|(val, &w)| *val = val.wrapping_sub(w));
+}
+
+// Clipped ReLU activation followed by the dot product with the output weights.
+// The sums can exceed an i32 with large weights, so they are done in i64.
+fn crelu_dot(values: &[i16; HIDDEN_SIZE], weights: &[i16]) -> i64 {
+    values.iter().zip(weights)
+        .map(|(&val, &w)| (val as i64).clamp(0, QA) * w as i64)
+        .sum()
+}
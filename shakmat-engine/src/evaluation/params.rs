@@ -0,0 +1,205 @@
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::sync::{PoisonError, RwLock, RwLockReadGuard};
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use super::EvalScore;
+use super::evaluate::ScorePair;
+
+// Any type that is made up of evaluation scores, so that the
+// parameters can be read, written and tuned without knowing their shape
+pub trait EvalValues {
+    fn values(&self) -> Vec<EvalScore>;
+    fn values_mut(&mut self) -> Vec<&mut EvalScore>;
+}
+
+// Declares the EvalParams struct, whose fields are all the tunable weights
+// of the evaluation, along with their default values
+macro_rules! eval_params {
+    ($($name:ident: $ty:ty = $default:expr,)*) => {
+        #[derive(Clone, PartialEq, Eq, Debug)]
+        pub struct EvalParams {
+            $(pub $name: $ty,)*
+        }
+
+        impl EvalParams {
+            pub const DEFAULT: Self = Self { $($name: $default,)* };
+
+            // The name and values of every parameter, in declaration order
+            pub fn params(&self) -> Vec<(&'static str, Vec<EvalScore>)> {
+                vec![$((stringify!($name), self.$name.values()),)*]
+            }
+
+            pub fn params_mut(&mut self) -> Vec<(&'static str, Vec<&mut EvalScore>)> {
+                vec![$((stringify!($name), self.$name.values_mut()),)*]
+            }
+        }
+    };
+}
+
+// The piece-square tables are not included, since they
+// are precomputed for both colors in piece_tables.rs
+eval_params! {
+    // Bonuses and penalties, measured in centipawns
+    // Values that are pairs represent the scores for the middlegame and endgame phases
+    pawn_base_value: EvalScore = 100,
+    bishop_base_value: EvalScore = 300,
+    knight_base_value: EvalScore = 300,
+    rook_base_value: EvalScore = 500,
+    queen_base_value: EvalScore = 900,
+
+    tempo_bonus: EvalScore = 28,
+    bishop_pair_bonus: ScorePair = (20, 60),
+    rook_open_file_bonus: ScorePair = (50, 25),
+    rook_semiopen_file_bonus: ScorePair = (20, 10),
+    rook_closed_file_penalty: ScorePair = (-10, -5),
+    rook_seventh_rank_bonus: ScorePair = (10, 30),
+    rook_seventh_king_bonus: ScorePair = (10, 20), // Extra if the enemy king is on the 8th rank
+    knight_outpost_bonus: ScorePair = (30, 20),
+    bishop_outpost_bonus: ScorePair = (15, 10),
+    passed_pawn_bonus: [ScorePair; 7] = [(0, 0), (10, 1), (5, 5), (1, 25), (15, 50), (50, 100), (100, 150)],
+    connected_pawn_bonus: [EvalScore; 7] = [0, 5, 10, 10, 15, 55, 85],
+    doubled_pawn_penalty: ScorePair = (-11, -50),
+    isolated_pawn_penalty: ScorePair = (-5, -15),
+    backward_pawn_penalty: ScorePair = (-9, -24),
+
+    // Enemy pieces attacked by a less valuable piece of ours, or attacked and undefended
+    threat_by_lesser_piece: ScorePair = (35, 20),
+    hanging_piece_bonus: ScorePair = (30, 15),
+
+    // Attack values for the different pieces for the outer and inner rings
+    minor_piece_attack: ScorePair = (8, 21),
+    rook_attack: ScorePair = (7, 18),
+    queen_attack: ScorePair = (14, 33),
+
+    // Danger values for a king on a semi-open file or with semi-open flanks
+    king_semiopen_file_danger: EvalScore = 70,
+    king_semiopen_flank_danger: EvalScore = 50,
+
+    // Middlegame bonuses for pawns in front of the king, depending on how many ranks
+    // ahead of it they are, and penalty for files around the king without one of them
+    pawn_shield_bonus: [EvalScore; 3] = [0, 20, 10],
+    missing_shield_pawn_penalty: EvalScore = -25,
+
+    // King danger reduction if the opponent doesn't have a queen
+    no_queen_danger_red: EvalScore = 800,
+
+    // Penalties for a king under different attack values
+    attacked_penalties: [EvalScore; 64] = [0,0,-1,-2,-4,-6,-8,-11,-14,-18,-21,-25,-30,-35,-40,-45,-51,-57,-63,-69,-76,-83,-91,-98,-106,-114,-123,-132,-141,-150,-159,-169,-179,-189,-200,-211,-222,-233,-245,-257,-269,-281,-294,-306,-319,-333,-346,-360,-374,-388,-403,-418,-433,-448,-463,-479,-495,-511,-527,-544,-561,-578,-595,-613],
+
+    // Bonuses and penalties for the mobility of different pieces
+    knight_mobility_bonus: [ScorePair; 9] = [(-62, -79), (-53, -57), (-12, -31), (-3, -17), (3, 7), (12, 13), (21, 16), (28, 21), (37, 26)],
+    bishop_mobility_bonus: [ScorePair; 14] = [(-47, -59), (-20, -25), (14, -8), (29, 12), (39, 21), (53, 40), (53, 56), (60, 58), (62, 65), (69, 72), (78, 78), (83, 87), (91, 88), (96, 98)],
+    rook_mobility_bonus: [ScorePair; 15] = [(-60, -82), (-24,-15), (0, 17), (3, 43), (4, 72), (14, 100), (20, 102), (30, 122), (41, 133), (41, 139), (41, 153), (45, 160), (57, 165), (58, 170), (67, 175)],
+    queen_mobility_bonus: [ScorePair; 28] = [(-29, -49), (-16, -29), (-8, -8), (-8, 17), (18, 39), (25, 54), (23, 59), (37, 73), (41, 76), (54, 95), (65, 95), (68, 101), (69, 124), (70, 128), (70, 132), (70, 133), (71, 136), (72, 140), (74, 147), (76, 149), (90, 153), (104, 169), (105, 171), (106, 171), (112, 178), (114, 185), (114, 187), (119, 221)],
+}
+
+// The parameters used by the evaluation. Every evaluation holds the read lock
+// from start to end, which is cheap next to the evaluation itself, so they
+// can be replaced at any moment, even while a search is running.
+static PARAMS: RwLock<EvalParams> = RwLock::new(EvalParams::DEFAULT);
+
+// Increased every time the parameters change, so that the
+// cached pawn structure scores can be discarded
+static PARAMS_VERSION: AtomicU32 = AtomicU32::new(0);
+
+pub fn params() -> RwLockReadGuard<'static, EvalParams> {
+    PARAMS.read().unwrap_or_else(PoisonError::into_inner)
+}
+
+pub fn params_version() -> u32 {
+    PARAMS_VERSION.load(Ordering::Relaxed)
+}
+
+// Replaces the parameters of the evaluation. The evaluations that are taking
+// place finish with the previous ones. The version changes while holding the
+// lock, so that no evaluation caches a pawn score with the wrong version.
+pub fn set_eval_params(params: EvalParams) {
+    let mut current = PARAMS.write().unwrap_or_else(PoisonError::into_inner);
+    *current = params;
+    PARAMS_VERSION.fetch_add(1, Ordering::Relaxed);
+}
+
+impl EvalParams {
+    // Reads the parameters from a file in the format written by Display, where
+    // every line contains a parameter name followed by its values. Parameters
+    // that are missing from the file keep their default values.
+    pub fn load(path: &str) -> Result<Self, String> {
+        let text = fs::read_to_string(path).map_err(|err| format!("Cannot read the parameters file {path}: {err}"))?;
+        text.parse()
+    }
+}
+
+impl Default for EvalParams {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+impl std::str::FromStr for EvalParams {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, String> {
+        let mut res = Self::DEFAULT;
+        let mut params = res.params_mut();
+
+        for line in text.lines().map(str::trim).filter(|line| !line.is_empty() && !line.starts_with('#')) {
+            let mut tokens = line.split_whitespace();
+            let name = tokens.next().unwrap();
+            let values = params.iter_mut().find(|(param, _)| *param == name)
+                .map(|(_, values)| values)
+                .ok_or(format!("Unknown evaluation parameter: {name}"))?;
+
+            let parsed = tokens.map(|val| val.parse::<EvalScore>().map_err(|_| format!("Invalid value for {name}: {val}")))
+                .collect::<Result<Vec<_>, _>>()?;
+            if parsed.len() != values.len() {
+                return Err(format!("Expected {} values for {name}, found {}", values.len(), parsed.len()));
+            }
+
+            values.iter_mut().zip(parsed).for_each(|(val, new)| **val = new);
+        }
+
+        drop(params);
+        Ok(res)
+    }
+}
+
+impl Display for EvalParams {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        for (name, values) in self.params() {
+            let values: Vec<String> = values.iter().map(|val| val.to_string()).collect();
+            writeln!(f, "{name} {}", values.join(" "))?;
+        }
+        Ok(())
+    }
+}
+
+impl EvalValues for EvalScore {
+    fn values(&self) -> Vec<EvalScore> {
+        vec![*self]
+    }
+
+    fn values_mut(&mut self) -> Vec<&mut EvalScore> {
+        vec![self]
+    }
+}
+
+impl EvalValues for ScorePair {
+    fn values(&self) -> Vec<EvalScore> {
+        vec![self.0, self.1]
+    }
+
+    fn values_mut(&mut self) -> Vec<&mut EvalScore> {
+        vec![&mut self.0, &mut self.1]
+    }
+}
+
+impl<T: EvalValues, const N: usize> EvalValues for [T; N] {
+    fn values(&self) -> Vec<EvalScore> {
+        self.iter().flat_map(EvalValues::values).collect()
+    }
+
+    fn values_mut(&mut self) -> Vec<&mut EvalScore> {
+        self.iter_mut().flat_map(EvalValues::values_mut).collect()
+    }
+}
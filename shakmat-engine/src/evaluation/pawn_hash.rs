@@ -0,0 +1,39 @@
+use std::cell::RefCell;
+
+use super::params_version;
+use super::evaluate::ScorePair;
+
+// Number of entries of the table, must be a power of 2.
+// Each entry takes 16 bytes, so every thread uses 256 KB.
+const PAWN_TABLE_SIZE: usize = 1 << 14;
+
+// The pawn structure score of a position, from white's point of view,
+// along with the version of the parameters used to compute it
+#[derive(Clone, Copy, Default)]
+struct PawnEntry {
+    key: u64,
+    params_version: u32,
+    score: ScorePair,
+}
+
+// Every thread has its own table, so the threads of a parallel search
+// don't have to synchronize while evaluating
+thread_local! {
+    static PAWN_TABLE: RefCell<Vec<PawnEntry>> = RefCell::new(vec![PawnEntry::default(); PAWN_TABLE_SIZE]);
+}
+
+// Returns the cached pawn structure score for the provided pawn key,
+// or computes and stores it if it's not present
+pub fn probe_pawn_score(key: u64, compute: impl FnOnce() -> ScorePair) -> ScorePair {
+    let index = key as usize & (PAWN_TABLE_SIZE - 1);
+    let version = params_version();
+
+    let entry = PAWN_TABLE.with(|table| table.borrow()[index]);
+    if entry.key == key && entry.params_version == version {
+        return entry.score;
+    }
+
+    let score = compute();
+    PAWN_TABLE.with(|table| table.borrow_mut()[index] = PawnEntry { key, params_version: version, score });
+    score
+}
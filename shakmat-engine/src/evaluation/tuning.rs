@@ -0,0 +1,80 @@
+use shakmat_core::{Board, Color};
+
+use super::{evaluate_position, params, set_eval_params, EvalParams};
+
+// Scaling constant of the sigmoid that maps evaluations to expected results
+const SIGMOID_K: f64 = 1.0;
+
+// A position along with the result of the game it was taken from,
+// from white's point of view: 1 for a win, 0.5 for a draw and 0 for a loss
+pub struct TuningEntry {
+    pub board: Board,
+    pub result: f64,
+}
+
+// Reads a dataset for tuning, where every line has a FEN and the result of
+// the game separated by a semicolon. The result can be either in PGN format
+// (1-0, 1/2-1/2, 0-1) or as a number (1.0, 0.5, 0.0).
+pub fn read_tuning_dataset(text: &str) -> Result<Vec<TuningEntry>, String> {
+    text.lines().map(str::trim).filter(|line| !line.is_empty() && !line.starts_with('#')).map(|line| {
+        let (fen, result) = line.rsplit_once(';').ok_or(format!("Missing game result: {line}"))?;
+        let result = match result.trim() {
+            "1-0" => 1.0,
+            "1/2-1/2" => 0.5,
+            "0-1" => 0.0,
+            other => other.parse().map_err(|_| format!("Invalid game result: {other}"))?,
+        };
+
+        Ok(TuningEntry { board: Board::from_fen(fen.trim())?, result })
+    }).collect()
+}
+
+// Mean squared error between the game results and the ones predicted
+// by the static evaluation, using the current parameters
+pub fn tuning_loss(dataset: &[TuningEntry]) -> f64 {
+    let total: f64 = dataset.iter().map(|entry| {
+        let sign = if entry.board.turn_color() == Color::White { 1.0 } else { -1.0 };
+        let score = evaluate_position(&entry.board).score() as f64 * sign;
+        let expected = 1.0 / (1.0 + 10f64.powf(-SIGMOID_K * score / 400.0));
+        (entry.result - expected).powi(2)
+    }).sum();
+
+    total / dataset.len() as f64
+}
+
+// Texel's tuning method: a local search that tries to increase and decrease
+// every parameter by one, keeping the changes that reduce the loss, for
+// the given number of iterations or until no change improves it.
+// The evaluation uses the tuned parameters when this function returns.
+pub fn tune(dataset: &[TuningEntry], iterations: usize) -> EvalParams {
+    let mut best = params().clone();
+    let mut best_loss = tuning_loss(dataset);
+    let n_values: usize = best.params().iter().map(|(_, values)| values.len()).sum();
+
+    for _ in 0..iterations {
+        let mut improved = false;
+
+        for i in 0..n_values {
+            for delta in [1, -1] {
+                let mut candidate = best.clone();
+                *candidate.params_mut().into_iter().flat_map(|(_, values)| values).nth(i).unwrap() += delta;
+                set_eval_params(candidate.clone());
+
+                let loss = tuning_loss(dataset);
+                if loss < best_loss {
+                    best = candidate;
+                    best_loss = loss;
+                    improved = true;
+                    break;
+                }
+            }
+        }
+
+        if !improved {
+            break;
+        }
+    }
+
+    set_eval_params(best.clone());
+    best
+}
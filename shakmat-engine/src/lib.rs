@@ -1,11 +1,20 @@
+mod bench;
 mod engine;
 mod evaluation;
 mod polyglot;
 mod search;
+mod syzygy;
 mod time;
 mod trasposition;
 
 // Exports
-pub use search::{is_draw_by_repetition, SearchResult, SearchOptions};
-pub use engine::{ShakmatEngine, EngineConfig};
-pub use evaluation::init_evaluation;
\ No newline at end of file
+pub use search::{is_draw_by_repetition, see, SearchResult, SearchOptions, SearchInfo};
+pub use engine::{ShakmatEngine, EngineConfig, EngineConfigBuilder};
+pub use bench::{BenchResult, BENCH_FENS, DEFAULT_BENCH_DEPTH};
+pub use time::TimeManager;
+pub use evaluation::{init_evaluation, evaluate_position, evaluate_position_verbose, evaluate_material_only, EvalBreakdown, EvalTerm, EVAL_TERMS, EvalMode};
+pub use evaluation::{EvalParams, set_eval_params, read_tuning_dataset, tuning_loss, tune, TuningEntry};
+pub use polyglot::create_book;
+pub use trasposition::{TTable, TTEntry, TTData, NodeType};
+pub use evaluation::{Evaluation, Network, Accumulator};
+pub use syzygy::{Tablebase, Wdl};
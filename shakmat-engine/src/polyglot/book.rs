@@ -1,3 +1,6 @@
+use std::fs;
+use std::io;
+use std::path::Path;
 use rustc_hash::FxHashMap;
 use rand::prelude::*;
 use rand::distributions::WeightedIndex;
@@ -14,21 +17,34 @@ struct WeightedMove {
     weight: u16,
 }
 
+// Size in bytes of every entry in a polyglot book
+const ENTRY_SIZE: usize = 16;
+
 impl OpeningBook {
-    // TO-DO: Allow Shakmat to read these as files in the future
-    // instead of hardcoding them into the engine
-    
+    // Loads the book that is bundled with the engine
+    pub fn load() -> Self {
+        Self::from_bytes(include_bytes!("openings.bin")).unwrap()
+    }
+
+    pub fn from_file(path: &Path) -> io::Result<Self> {
+        Self::from_bytes(&fs::read(path)?)
+    }
+
     // The polyglot file format is a binary chunk of data, where each
     // entry is 16 bytes long. The format of every entry is:
     // - Bytes 0-7: Zobrist key
     // - Bytes 8-9: Move
     // - Bytes 10-11: Weight
     // - Bytes 12-15: "Learn"
-    pub fn load() -> Self {
-        let bytes = include_bytes!("openings.bin");
+    pub fn from_bytes(bytes: &[u8]) -> io::Result<Self> {
+        if !bytes.len().is_multiple_of(ENTRY_SIZE) {
+            let msg = format!("Invalid polyglot book: its size ({} bytes) is not a multiple of {ENTRY_SIZE}", bytes.len());
+            return Err(io::Error::new(io::ErrorKind::InvalidData, msg));
+        }
+
         let mut book: FxHashMap<u64, Vec<WeightedMove>> = FxHashMap::default();
 
-        for pos_data in bytes.chunks_exact(16) {
+        for pos_data in bytes.chunks_exact(ENTRY_SIZE) {
             // Load the info from the entry (we can ignore the "learn" data)
             let zobrist = u64::from_be_bytes(pos_data[..8].try_into().unwrap());
             let move_data = u16::from_be_bytes(pos_data[8..10].try_into().unwrap());
@@ -42,47 +58,62 @@ impl OpeningBook {
         // that during the search
         book.values_mut().for_each(|ls| ls.sort_by(|a, b| b.weight.cmp(&a.weight)));
 
-        Self { book }
+        Ok(Self { book })
     }
 
-    pub fn get_move(&self, board: &Board, only_best: bool) -> Option<Move> {
-        // TO-DO: Transform castling moves into the equivalent normal move
-        // if castling is not legal!!
-        self.book.get(&board.zobrist_key()).map(|ls| {
-            // We have a hit from the book!
-
-            // If we are instructed to only return the best move, return the
-            // first move in the list, since it is sorted
-            let index = if only_best {
-                0
-            } else {
-                // Otherwise, get a random move conditioned to their respective weights
-                let dist = WeightedIndex::new(ls.iter().map(|entry| entry.weight)).unwrap();
-                dist.sample(&mut thread_rng())
-            };
-            
-            let mut mv = ls[index].mv;
-
-            // There seems to be some disparity in the way castling moves are
-            // stored in the book. If the piece to move is the king, and it's
-            // moving two squares to the left or the right, transform that move
-            // into a castling move.
-            if let Move::Normal{from, to} = mv {
-                if let Some(King) = board.piece_on(from) {
-                    // Ugly nested ifs because if-let cant be combined yet :<
-                    if to == from - 2 {
-                        mv = Move::ShortCastle;
-                    } else if to == from + 2 {
-                        mv = Move::LongCastle;
-                    }
-                }
-            }
-
-            mv
-        })
+    pub fn get_move(&self, board: &Board, only_best: bool, min_weight: u16) -> Option<Move> {
+        let mut moves = self.get_moves(board);
+        moves.retain(|(_, weight)| *weight >= min_weight);
+
+        if moves.is_empty() {
+            return None;
+        }
+
+        // If we are instructed to only return the best move, return the
+        // first move in the list, since it is sorted
+        let index = if only_best {
+            0
+        } else {
+            // Otherwise, get a random move conditioned to their respective weights
+            WeightedIndex::new(moves.iter().map(|(_, weight)| *weight))
+                .map_or(0, |dist| dist.sample(&mut thread_rng()))
+        };
+
+        Some(moves[index].0)
+    }
+
+    // All the moves in the book for a position with their weights, sorted by
+    // decreasing weight. It's empty if the position isn't in the book.
+    pub fn get_moves(&self, board: &Board) -> Vec<(Move, u16)> {
+        let entries = match self.book.get(&board.zobrist_key()) {
+            Some(entries) => entries,
+            None => return Vec::new(),
+        };
+
+        // We have a hit from the book! Make sure that the moves can actually
+        // be played, skipping those that can't, just in case
+        let legal_moves = board.legal_moves();
+        entries.iter()
+            .filter_map(|entry| legal_book_move(board, entry.mv, &legal_moves).map(|mv| (mv, entry.weight)))
+            .collect()
     }
 }
 
+// There seems to be some disparity in the way castling moves are
+// stored in the book. If the piece to move is the king, and it's
+// moving two squares to the left or the right, transform that move
+// into a castling move. The resulting move is only returned if it's legal.
+fn legal_book_move(board: &Board, mv: Move, legal_moves: &[Move]) -> Option<Move> {
+    let castle = match mv {
+        Move::Normal { from, to } if board.piece_on(from) == &Some(King) && to + 2 == from => Some(Move::ShortCastle),
+        Move::Normal { from, to } if board.piece_on(from) == &Some(King) && to == from + 2 => Some(Move::LongCastle),
+        _ => None,
+    };
+
+    // If castling is not possible, the king move may still be legal by itself
+    castle.into_iter().chain([mv]).find(|candidate| legal_moves.contains(candidate))
+}
+
 fn u16_to_move(bits: u16) -> Move {
 /*  
     Polyglot encodes moves in 16 bits, as follows:
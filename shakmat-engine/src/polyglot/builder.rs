@@ -0,0 +1,59 @@
+use rustc_hash::FxHashMap;
+use shakmat_core::{read_pgn, Board, Color, Move, PieceType::*, Square};
+
+// Creates a polyglot book from the games in a PGN text, considering only their
+// first `max_plies` moves. The weight of every move is the number of times it
+// was played in its position, scaled down if needed to fit in 16 bits.
+pub fn create_book(pgn: &str, max_plies: usize) -> Result<Vec<u8>, String> {
+    let mut counts: FxHashMap<(u64, u16), u64> = FxHashMap::default();
+
+    for (initial, moves) in read_pgn(pgn)? {
+        let mut board = initial;
+        for mv in moves.iter().take(max_plies) {
+            *counts.entry((board.zobrist_key(), move_to_u16(&board, mv))).or_default() += 1;
+            board = board.make_move(mv);
+        }
+    }
+
+    let max_count = counts.values().copied().max().unwrap_or(1);
+    let scale = max_count.div_ceil(u16::MAX as u64);
+
+    // Polyglot books are sorted by key, so that they can be binary searched
+    let mut entries: Vec<_> = counts.into_iter().collect();
+    entries.sort_unstable_by_key(|&((key, mv), count)| (key, std::cmp::Reverse(count), mv));
+
+    let mut bytes = Vec::with_capacity(entries.len() * 16);
+    for ((key, mv), count) in entries {
+        let weight = (count / scale).max(1) as u16;
+        bytes.extend(key.to_be_bytes());
+        bytes.extend(mv.to_be_bytes());
+        bytes.extend(weight.to_be_bytes());
+        bytes.extend([0; 4]); // Learn data, unused
+    }
+
+    Ok(bytes)
+}
+
+// Inverse of the conversion in book.rs. Castling is encoded as the
+// king capturing its own rook, i.e., e1h1, e1a1, e8h8 or e8a8.
+fn move_to_u16(board: &Board, mv: &Move) -> u16 {
+    let rank = if board.turn_color() == Color::White { 0 } else { 7 };
+    let (from, to, promotion) = match mv {
+        Move::ShortCastle => return encode_squares(4, rank, 7, rank),
+        Move::LongCastle => return encode_squares(4, rank, 0, rank),
+        Move::Normal { from, to } => (*from, *to, 0),
+        Move::PawnPromotion { from, to, promote_to } => (*from, *to, match promote_to {
+            Knight => 1,
+            Bishop => 2,
+            Rook => 3,
+            _ => 4,
+        }),
+    };
+
+    let (from, to) = (Square::new(from), Square::new(to));
+    encode_squares(from.file() as u16, from.rank() as u16, to.file() as u16, to.rank() as u16) | promotion << 12
+}
+
+fn encode_squares(from_file: u16, from_rank: u16, to_file: u16, to_rank: u16) -> u16 {
+    to_file | to_rank << 3 | from_file << 6 | from_rank << 9
+}
@@ -1,3 +1,5 @@
 mod book;
+mod builder;
 
-pub use book::OpeningBook;
\ No newline at end of file
+pub use book::OpeningBook;
+pub use builder::create_book;
\ No newline at end of file
@@ -1,11 +1,20 @@
 use super::move_ordering::{MoveScore, MAX_HISTORY_VAL};
-use shakmat_core::{Color::{self, *}, Move::{self, *}};
+use shakmat_core::{Board, Color::{self, *}, Move::{self, *}, PieceType};
+
+// History values always stay within [-HISTORY_LIMIT, HISTORY_LIMIT]
+pub const HISTORY_LIMIT: MoveScore = 16384;
+const _: () = assert!(HISTORY_LIMIT < MAX_HISTORY_VAL);
 
 // Stores move evaluation data indexed by [color][from][to]
 pub struct HistoryTable {
     data: [[[MoveScore; 64]; 64]; 2]
 }
 
+// Same, but for captures, indexed by [color][piece moving][to][piece captured]
+pub struct CaptureHistory {
+    data: [[[[MoveScore; 6]; 64]; 6]; 2]
+}
+
 impl HistoryTable {
     pub fn new() -> Self {
         Self { data: [[[0; 64]; 64]; 2] }
@@ -18,28 +27,41 @@ impl HistoryTable {
 
     pub fn add_bonus(&mut self, mv: &Move, color: Color, bonus: MoveScore) {
         let (from, to) = get_from_to(mv, color);
-        
-        let newval = self.data[color.to_index()][from][to] + bonus;
-        self.data[color.to_index()][from][to] = newval;
-        
-        // If we reach the maximum history value, scale down the whole table
-        if newval > MAX_HISTORY_VAL {
-            self.age();
-        }
+        apply_bonus(&mut self.data[color.to_index()][from][to], bonus);
+    }
+}
+
+impl CaptureHistory {
+    pub fn new() -> Self {
+        Self { data: [[[[0; 6]; 64]; 6]; 2] }
     }
 
-    pub fn age(&mut self) {
-        self.data.iter_mut().for_each(|color| {
-            color.iter_mut().for_each(|from_mat| {
-                from_mat.iter_mut().for_each(|val| {
-                    *val /= 2;
-                })
-            })
-        });
+    // The move must be a capture
+    pub fn get_value(&self, mv: &Move, board: &Board) -> MoveScore {
+        let (piece, to, captured) = capture_indices(mv, board);
+        self.data[board.turn_color().to_index()][piece][to][captured]
     }
+
+    pub fn add_bonus(&mut self, mv: &Move, board: &Board, bonus: MoveScore) {
+        let (piece, to, captured) = capture_indices(mv, board);
+        apply_bonus(&mut self.data[board.turn_color().to_index()][piece][to][captured], bonus);
+    }
+}
+
+// Gravity update: the closer a value is to the limit, the less it moves
+// towards it, so the values settle instead of saturating in long searches
+fn apply_bonus(val: &mut MoveScore, bonus: MoveScore) {
+    let bonus = bonus.clamp(-HISTORY_LIMIT, HISTORY_LIMIT);
+    *val += bonus - *val * bonus.abs() / HISTORY_LIMIT;
+}
+
+fn capture_indices(mv: &Move, board: &Board) -> (usize, usize, usize) {
+    // The destination square is empty in e.p. captures
+    let captured = mv.piece_captured(board).unwrap_or(PieceType::Pawn);
+    (mv.piece_moving(board).to_index(), mv.to() as usize, captured.to_index())
 }
 
-fn get_from_to(mv: &Move, color: Color) -> (usize, usize) {
+pub fn get_from_to(mv: &Move, color: Color) -> (usize, usize) {
     match (mv, color) {
         (ShortCastle, White) => (3, 1),
         (LongCastle, White) => (3, 5),
@@ -1,6 +1,8 @@
 mod history;
 mod move_ordering;
 mod pv_line;
+mod see;
 mod searching;
 
-pub use searching::{is_draw_by_repetition, SearchResult, SearchOptions, Search};
\ No newline at end of file
+pub use searching::{is_draw_by_repetition, SearchResult, SearchOptions, SearchInfo, Search};
+pub use see::see;
\ No newline at end of file
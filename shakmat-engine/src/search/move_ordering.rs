@@ -1,14 +1,20 @@
-use shakmat_core::{Board, Move, PieceType};
-use super::history::HistoryTable;
+use shakmat_core::{move_gen, Board, Move, PieceType, MAX_MOVES};
+use super::history::{HistoryTable, CaptureHistory, HISTORY_LIMIT};
+use super::see::see;
 
 // Heuristic values of different kinds of moves
 // The priorities and assigned score ranges are as follows:
 // Move stored in the TT: Max
 // Recapture of last moved piece: Max - 1
-// Captures using MMV-LVA: [Max - 1000, Max - 1)
+// Captures using MMV-LVA: [Max - 1000, Max - 1), ties broken by capture history
+// Queen promotions: [Max - 210, Max - 120], below the best captures
 // Primary killer move: Max - 1001
 // Secondary killer move: Max - 1002
+// Countermove to the previous move: Max - 1003
+// Knight promotion giving check: Max - 1004
 // History heuristics: rest
+// Captures that lose material according to SEE: after everything else
+// Other underpromotions: at the very end
 
 pub type MoveScore = i32;
 
@@ -17,37 +23,79 @@ const TT_MOVE: MoveScore = MoveScore::MAX; // Best move stored in the transposit
 const CAPTURE_BASE_VAL: MoveScore = MoveScore::MAX - 1000; // Base value for any other capture
 const PRIMARY_KILLER: MoveScore = CAPTURE_BASE_VAL - 1; // Primary killer move for this depth
 const SECONDARY_KILLER: MoveScore = PRIMARY_KILLER - 1; // Secondary killer move for this depth
-pub const MAX_HISTORY_VAL: MoveScore = SECONDARY_KILLER - 1;
+const COUNTERMOVE: MoveScore = SECONDARY_KILLER - 1; // Move that refuted the previous move somewhere else
+const KNIGHT_PROMOTION_CHECK: MoveScore = COUNTERMOVE - 1; // Underpromotion to a knight that gives check
+pub const MAX_HISTORY_VAL: MoveScore = KNIGHT_PROMOTION_CHECK - 1;
+const QUEEN_PROMOTION: MoveScore = CAPTURE_BASE_VAL + 790; // Plus a tenth of the value of the piece captured, if any
+const CAPTURE_HISTORY_RANGE: MoveScore = 8; // Less than the smallest gap between two MVV-LVA scores
+const BAD_CAPTURE_BASE_VAL: MoveScore = MoveScore::MIN / 2; // Base value for losing captures
+const UNDERPROMOTION: MoveScore = BAD_CAPTURE_BASE_VAL - 10000; // Underpromotions, which are rarely useful
 
 // Struct to hold a pair of (Move, move heuristical value)
+#[derive(Clone, Copy)]
 pub struct RatedMove {
     pub mv: Move,
     pub score: MoveScore
 }
 
 // Receives the pseudolegal moves for the current position and, optionally,
-// the best move according to the transposition table
-// Returns a list of RatedMoves according to the heuristics above.
-pub fn order_moves(moves: Vec<Move>, board: &Board, tt_move: Option<Move>, killers: &[Move], history: &HistoryTable) -> Vec<RatedMove> {
-    let mut rated_moves: Vec<RatedMove> = moves.into_iter().map(|mv| rate_move(mv, tt_move, board, killers, history)).collect();
-    rated_moves.sort_unstable_by_key(|rm| rm.score);
-    rated_moves
+// the best move according to the transposition table and the countermove
+// Returns the RatedMoves sorted according to the heuristics above.
+// They are kept in an array on the stack to avoid allocating memory.
+pub fn order_moves(
+    moves: &[Move],
+    board: &Board,
+    tt_move: Option<Move>,
+    killers: &[Move],
+    countermove: Option<Move>,
+    history: &HistoryTable,
+    capture_history: &CaptureHistory,
+) -> impl Iterator<Item = RatedMove> {
+    let mut rated_moves = [RatedMove { mv: Move::ShortCastle, score: 0 }; MAX_MOVES];
+    for (rated, &mv) in rated_moves.iter_mut().zip(moves) {
+        *rated = rate_move(mv, tt_move, board, killers, countermove, history, capture_history);
+    }
+
+    rated_moves[..moves.len()].sort_unstable_by_key(|rm| rm.score);
+    rated_moves.into_iter().take(moves.len())
 }
 
 // Takes a move by value and returns a struct with that move
 // and its heuristic value according to the consts above
-fn rate_move(mv: Move, pv_move: Option<Move>, board: &Board, killers: &[Move], history: &HistoryTable) -> RatedMove {
+fn rate_move(
+    mv: Move,
+    pv_move: Option<Move>,
+    board: &Board,
+    killers: &[Move],
+    countermove: Option<Move>,
+    history: &HistoryTable,
+    capture_history: &CaptureHistory,
+) -> RatedMove {
     let score = if pv_move == Some(mv) {
         TT_MOVE
+    } else if let Some(promote_to) = mv.promotion_piece() {
+        rate_promotion(&mv, promote_to, board)
     // } else if matches!(mv, Move::Normal{to, ..} | Move::PawnPromotion{to, ..} if to == board.last_moved()) {
     //     // Note: the "if" applies to both patterns, not just the PawnPromotion move
     //     LAST_RECAPTURE
     } else if let Some(captured) = mv.piece_captured(board) {
-        CAPTURE_BASE_VAL + value_of_capture(captured) - value_of_attacker(mv.piece_moving(board))
-    }  else if killers[0] == mv {
+        let attacker = mv.piece_moving(board);
+        // Capturing a less valuable piece may lose material if it's defended
+        let exchange = if value_of_capture(captured) < value_of_capture(attacker) { see(board, &mv) } else { 0 };
+
+        if exchange < 0 {
+            BAD_CAPTURE_BASE_VAL + exchange as MoveScore
+        } else {
+            // The capture history is scaled so that it only breaks ties
+            let tie_break = (capture_history.get_value(&mv, board) + HISTORY_LIMIT) * CAPTURE_HISTORY_RANGE / (2 * HISTORY_LIMIT);
+            CAPTURE_BASE_VAL + value_of_capture(captured) - value_of_attacker(attacker) + tie_break
+        }
+    } else if killers[0] == mv {
         PRIMARY_KILLER
     } else if killers[1] == mv {
         SECONDARY_KILLER
+    } else if countermove == Some(mv) {
+        COUNTERMOVE
     } else {
         history.get_value(&mv, board.turn_color())
     };
@@ -56,6 +104,19 @@ fn rate_move(mv: Move, pv_move: Option<Move>, board: &Board, killers: &[Move], h
     RatedMove { mv, score: -score }
 }
 
+// Queen promotions go right after the best captures, and underpromotions are
+// only worth considering early if they are a knight giving check
+fn rate_promotion(mv: &Move, promote_to: PieceType, board: &Board) -> MoveScore {
+    let to = mv.to() as usize;
+    let enemy_king = board.get_pieces(!board.turn_color()).king;
+
+    match promote_to {
+        PieceType::Queen => QUEEN_PROMOTION + mv.piece_captured(board).map_or(0, value_of_capture) / 10,
+        PieceType::Knight if (move_gen::knight_moves(to) & enemy_king).is_not_empty() => KNIGHT_PROMOTION_CHECK,
+        _ => UNDERPROMOTION,
+    }
+}
+
 // Tables for Most Valuable Victim - Least Valuable Aggressor (MVV-LVA)
 // Attempts to provide a heuristic for capturing moves by
 // capturing with the least valuable piece
@@ -70,7 +131,7 @@ const fn value_of_attacker(piece: PieceType) -> MoveScore {
     }
 }
 
-const fn value_of_capture(piece: PieceType) -> MoveScore {
+pub const fn value_of_capture(piece: PieceType) -> MoveScore {
     match piece {
         PieceType::Pawn => 100,
         PieceType::Knight => 300,
@@ -1,4 +1,4 @@
-use shakmat_core::Move;
+use shakmat_core::{Board, Move};
 
 
 // A struct holding the principal variation line for a search
@@ -24,4 +24,23 @@ impl PVLine {
         self.moves.push(mv);
         self.moves.append(&mut child_line.moves);
     }
-}
\ No newline at end of file
+
+    // Returns the moves in the line, stopping at the first one that is not legal
+    // in the position it would be played. This should never happen, but a collision
+    // in the trasposition table could make the line diverge at some point.
+    pub fn legal_line(&self, board: &Board) -> Vec<Move> {
+        let mut board = *board;
+        let mut line = Vec::with_capacity(self.moves.len());
+
+        for mv in &self.moves {
+            if !board.is_legal_move(mv) {
+                break;
+            }
+
+            board = board.make_move(mv);
+            line.push(*mv);
+        }
+
+        line
+    }
+}
@@ -1,16 +1,17 @@
-use shakmat_core::{Board, Move, PieceType::*};
-use std::cmp::{min, max};
+use shakmat_core::{Board, Color, Move, MoveList, PieceType::*};
+use std::cmp::{min, max, Reverse};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 
-use super::move_ordering::{order_moves, RatedMove, MoveScore};
+use super::move_ordering::{order_moves, value_of_capture, RatedMove, MoveScore};
 use super::pv_line::PVLine;
-use super::history::HistoryTable;
-use crate::evaluation::{evaluate_position, Evaluation, EvalScore};
+use super::history::{HistoryTable, CaptureHistory, get_from_to};
+use super::see::is_losing_move;
+use crate::evaluation::{evaluate_position, evaluate_material_only, Evaluation, EvalScore, EvalMode, Network, Accumulator};
 use crate::trasposition::{TTable, TTEntry, NodeType};
+use crate::syzygy::{Tablebase, Wdl};
 use crate::time::TimeManager;
 
-// Number of entries of the trasposition table.
-const TRASPOSITION_TABLE_SIZE: usize = 1 << 22;
-
 // The maximum depth that will be reached under any circumstances
 const LIMIT_DEPTH: usize = 100;
 
@@ -20,8 +21,11 @@ const MAX_KILLERS: usize = 2;
 // Depth to reduce a null move search. Maybe try dynamic values in the future?
 const NULL_MOVE_REDUCTION: u8 = 2;
 
-// Width for the aspiration window
+// Initial width for the aspiration window, how much it grows every time the
+// score falls outside of it, and the widest window before using the full one
 const ASP_WINDOW: EvalScore = 30;
+const ASP_WIDENING: EvalScore = 3;
+const ASP_MAX_WINDOW: EvalScore = 270;
 
 // The amount that a score must drop between iterations for
 // panic time to be allocated
@@ -36,36 +40,107 @@ const FUTILIY_MARGINS: [EvalScore; 6] = [0, 100, 160, 220, 280, 340];
 // Score margin for reverse futility pruning, scaling with depth
 const REV_FUTILITY_MARGIN: EvalScore = 80;
 
+// Minimum remaining depth for internal iterative deepening, and
+// how much shallower than the current one its search is
+const IID_MIN_DEPTH: u8 = 4;
+const IID_REDUCTION: u8 = 2;
+
+// Score margins for razoring, indexed by the remaining depth
+const RAZOR_MARGIN: [EvalScore; 2] = [0, 400];
+
+// Safety margin for delta pruning in the quiescence search: captures that can't
+// raise the score above alpha even with this much extra material are skipped
+const DELTA_MARGIN: EvalScore = 300;
+
+// Score for a tablebase win, below the mate scores so that actual mates are preferred
+const TB_WIN_SCORE: EvalScore = 20000;
+
 // Typedef for the killer moves table
 pub type Killers = [[Move; MAX_KILLERS]; LIMIT_DEPTH + 2];
 
+// Typedef for the countermoves table, indexed by the [from][to] of the previous move
+pub type CounterMoves = [[Move; 64]; 64];
+
 // Typedef for the pair (alpha, beta) of score bounds
 pub type Bounds = (Evaluation, Evaluation);
 
-// Calculates the number of quiet moves to explore for a certain depth
-// const fn max_movecount(depth: u8) -> usize {
-//     3 + (depth as usize * depth as usize)
-// }
+// Late move pruning is only applied up to this remaining depth, after
+// searching LMP_BASE_MOVES + depth^2 moves
+const LMP_MAX_DEPTH: u8 = 4;
+const LMP_BASE_MOVES: usize = 3;
+
+// Calculates the number of moves to explore before pruning quiet ones
+const fn max_movecount(depth: u8) -> usize {
+    LMP_BASE_MOVES + (depth as usize * depth as usize)
+}
 
 // The Search struct contains all necessary parameters for the search and stores
 // relevant information between iterations. All search-related functions
 // are implemented as methods of this struct.
-pub struct Search {
+pub struct Search<'a> {
     timer: TimeManager,
     max_depth: u8,
     past_positions: Vec<u64>,
     killers: Killers,
-    tt: TTable,
-    node_count: u32,
+    countermoves: CounterMoves,
+    move_stack: [Option<Move>; LIMIT_DEPTH + 2], // Move played in each ply, None for null moves
+    tt: &'a TTable,
+    node_count: u64,
+    max_nodes: u64,
+    shared_nodes: Option<&'a AtomicU64>, // Nodes visited by all the threads of a multi-threaded search
+    reported_nodes: u64, // Nodes of this thread already added to `shared_nodes`
+    max_qsearch_ply: u8,
+    razoring: bool,
+    aspiration_widening: bool,
     history: HistoryTable,
+    capture_history: CaptureHistory,
+    network: Option<&'a Network>,
+    accumulators: Vec<Accumulator>, // NNUE accumulators for each ply, if a network is used
+    eval_mode: EvalMode, // Classical evaluation to use when there is no network
+    tablebase: Option<&'a Tablebase>, // Syzygy tables to probe for endgames, if any
+    tb_root_moves: Vec<Move>, // Root moves that keep the tablebase result, all of them are searched if empty
+    contempt: EvalScore,
+    root_color: Color, // Side to move at the root, the one that the contempt is applied for
+    multipv: usize,
+    excluded_root_moves: Vec<Move>, // Root moves already reported in a MultiPV search
+    stop: Option<&'a AtomicBool>, // Flag to stop the helper threads of a multi-threaded search
+    user_stop: Option<Arc<AtomicBool>>, // Flag to stop the search on demand
+    start_depth: u8,
+    // In mate search mode, the side to move at the root only plays checks, and
+    // the evaluation isn't used. The search only finds a best move if it is a
+    // forced mate within the requested number of moves.
+    mate_search: bool,
+    info_callback: Option<Box<dyn FnMut(SearchInfo) + 'a>>, // Called after every iteration
 }
 
 // The SearchConfig struct contains a series of parameters for the search
+#[derive(Clone)]
 pub struct SearchOptions {
     pub total_time_remaining: Option<u64>, // Milliseconds remaining in our clock
     pub moves_until_control: Option<u64>, // Moves remaining until the next time control stage
+    pub increment_ms: Option<u64>, // Millis added to our clock after every move
     pub time_for_move: Option<u64>, // Millis designated for this move, overrides previous two
+    pub move_overhead_ms: u64, // Millis to keep in reserve for communication lag, 10 by default
     pub max_depth: Option<u8>, // Maximum depth for the search
+    pub multipv: usize, // Number of best lines to find when analyzing
+    pub stop: Option<Arc<AtomicBool>>, // When set to true, the search ends as if the time was up
+    pub max_nodes: Option<u64>, // Maximum number of nodes to visit, checked every 4096 nodes
+    pub mate_in: Option<u8>, // Only look for a forced mate in this many moves, see `mate_search` below
+    pub max_qsearch_ply: Option<u8>, // Maximum depth of the quiescence search, unlimited by default
+    pub razoring: bool, // Drop into the quiescence search near the horizon if the eval is far below alpha, true by default
+    pub aspiration_widening: bool, // Widen the aspiration window gradually instead of using the full one when the score falls outside, true by default
+    pub only_best_book_moves: Option<bool>, // Overrides the engine config for this search
+    pub min_book_weight: u16, // Book moves with a lower weight are not considered
+}
+
+// Information about each completed iteration of the search, for live analysis
+pub struct SearchInfo {
+    pub depth: u8,
+    pub score: Evaluation,
+    pub nodes: u64,
+    pub elapsed_ms: u64,
+    pub hashfull: u32, // Per thousand entries of the trasposition table in use
+    pub pv: Vec<Move>,
 }
 
 // SearchResult a pair of evaluation and best move, so we can return the current evaluation to
@@ -73,54 +148,129 @@ pub struct SearchOptions {
 pub struct SearchResult {
     pub score: Evaluation,
     pub best_move: Option<Move>,
+    pub pv: Vec<Move>, // Principal variation, starting with the best move
+    pub nodes: u64, // Nodes visited during the search
+    pub depth_reached: u8, // Depth of the last completed iteration
+    pub elapsed_ms: u64,
+    pub from_book: bool, // The move was taken from the opening book, without searching
 }
 
-impl Search {
-    pub fn from_config(config: SearchOptions, past_positions: &[u64]) -> Self {
-
-        let mut tt = TTable::new(TRASPOSITION_TABLE_SIZE);
-        tt.flush();
-
+impl<'a> Search<'a> {
+    pub fn from_config(
+        config: SearchOptions,
+        past_positions: &[u64],
+        tt: &'a TTable,
+        network: Option<&'a Network>,
+        tablebase: Option<&'a Tablebase>,
+        eval_mode: EvalMode,
+        contempt: EvalScore,
+    ) -> Self {
         Self {
             timer: TimeManager::new(&config),
-            max_depth: min(config.max_depth.unwrap_or(LIMIT_DEPTH as u8), LIMIT_DEPTH as u8),
+            max_depth: match config.mate_in {
+                // A mate in N moves takes 2N - 1 plies
+                Some(moves) => min(max(moves, 1) * 2 - 1, LIMIT_DEPTH as u8),
+                None => min(config.max_depth.unwrap_or(LIMIT_DEPTH as u8), LIMIT_DEPTH as u8),
+            },
             tt,
             killers: [[Move::empty(); MAX_KILLERS]; LIMIT_DEPTH + 2],
+            countermoves: [[Move::empty(); 64]; 64],
+            move_stack: [None; LIMIT_DEPTH + 2],
             node_count: 0,
+            max_nodes: config.max_nodes.unwrap_or(u64::MAX),
+            shared_nodes: None,
+            reported_nodes: 0,
+            max_qsearch_ply: config.max_qsearch_ply.unwrap_or(u8::MAX),
+            razoring: config.razoring,
+            aspiration_widening: config.aspiration_widening,
             past_positions: past_positions.to_vec(),
             history: HistoryTable::new(),
+            capture_history: CaptureHistory::new(),
+            network,
+            accumulators: Vec::new(),
+            eval_mode,
+            tablebase,
+            tb_root_moves: Vec::new(),
+            contempt,
+            root_color: Color::White,
+            multipv: max(config.multipv, 1),
+            excluded_root_moves: Vec::new(),
+            stop: None,
+            user_stop: config.stop,
+            start_depth: 1,
+            mate_search: config.mate_in.is_some(),
+            info_callback: None,
         }
     }
 
+    // Turns this search into a helper thread for a Lazy SMP search, which
+    // runs until the stop flag is set. Half of the helpers start one ply
+    // deeper, so that the threads don't search the same depths in sync.
+    pub fn into_helper(mut self, thread_id: usize, stop: &'a AtomicBool) -> Self {
+        self.stop = Some(stop);
+        self.start_depth = 1 + (thread_id % 2) as u8;
+        self
+    }
+
+    // Makes the node limit apply to the nodes visited by all the threads
+    // that share the counter, instead of only those of this one
+    pub fn with_shared_nodes(mut self, shared_nodes: &'a AtomicU64) -> Self {
+        self.shared_nodes = Some(shared_nodes);
+        self
+    }
+
+    // Sets a function to be called with the results of each completed
+    // iteration of find_best()
+    pub fn with_info_callback(mut self, callback: impl FnMut(SearchInfo) + 'a) -> Self {
+        self.info_callback = Some(Box::new(callback));
+        self
+    }
+
     // Wrapper function over the negamax algorithm, returning the best move
     // along with the associated score
     pub fn find_best(&mut self, board: &Board) -> SearchResult {
         // If there is only one legal move, return it immediately
         let legal_moves = board.legal_moves();
-        if legal_moves.len() == 1 {
-            return SearchResult { score: Evaluation::new(0), best_move: Some(legal_moves[0]) };
+        if legal_moves.len() == 1 && !self.mate_search {
+            return SearchResult {
+                score: Evaluation::new(0),
+                best_move: Some(legal_moves[0]),
+                pv: vec![legal_moves[0]],
+                nodes: 0,
+                depth_reached: 0,
+                elapsed_ms: self.timer.elapsed_micros() / 1000,
+                from_book: false,
+            };
         }
 
+        self.root_color = board.turn_color();
+        self.init_accumulators(board);
+        self.add_root_position(board);
+        self.init_tb_root_moves(board);
+
         let mut previous_score = Evaluation::new(0);
         let mut score = Evaluation::min_val();
         let mut best_move = None;
+        let mut pv = vec![];
+        let mut depth_reached = 0;
 
         let mut alpha = Evaluation::min_val();
         let mut beta = Evaluation::max_val();
+        let mut delta = ASP_WINDOW;
 
         // Iterative deepening: instead of diving directly into a search of depth `max_depth`,
         // increase the depth by 1 every time. This may seem counter-intuitive, but it actually
         // makes it run faster. The reason is that we can use the best move from the previous
         // search as the temptative best move in this one in the move ordering, which makes
         // the alpha-beta pruning remove many more branches during the search.
-        let mut depth = 1;
+        let mut depth = self.start_depth;
 
         // The PV line found by the engine
         let mut pv_line = PVLine::new();
 
         while depth <= self.max_depth && !self.timer.times_up() {
             let t_start = self.timer.elapsed_micros();
-            score = self.negamax(board, depth, 0, (alpha, beta), true, &mut pv_line);
+            score = self.negamax(board, depth, 0, (alpha, beta), &mut pv_line);
             let search_time = self.timer.elapsed_micros() - t_start;
 
             // If we ran out of time during the search, stop and
@@ -133,20 +283,36 @@ impl Search {
             // Aspiration windows: the score is unlikely to change a lot between iterations,
             // so we use a window margin around the last score to use as alpha and beta,
             // hoping that this will cause more cutoffs. However, if the score ends up
-            // under alpha or over beta, then we must search again using the full window
-            // size as the search result is not reliable.
-            if score <= alpha {
-                alpha = Evaluation::min_val();
-                continue;
-            }
-
-            if score >= beta {
-                beta = Evaluation::max_val();
+            // under alpha or over beta, then we must search again since the result
+            // is not reliable. Only the bound that failed is widened, a bit more
+            // every time, until we have to use the full window.
+            if score <= alpha || score >= beta {
+                delta *= ASP_WIDENING;
+                let full_window = !self.aspiration_widening || delta > ASP_MAX_WINDOW;
+
+                if score <= alpha {
+                    alpha = if full_window { Evaluation::min_val() } else { previous_score - delta };
+                } else {
+                    beta = if full_window { Evaluation::max_val() } else { previous_score + delta };
+                }
                 continue;
             }
 
             // The best move will be the first one in the PV line
             best_move = pv_line.first();
+            pv = pv_line.legal_line(board);
+            depth_reached = depth;
+
+            if let Some(callback) = self.info_callback.as_mut() {
+                callback(SearchInfo {
+                    depth,
+                    score,
+                    nodes: self.node_count,
+                    elapsed_ms: self.timer.elapsed_micros() / 1000,
+                    hashfull: self.tt.hashfull_permill(),
+                    pv: pv.clone(),
+                });
+            }
 
             // If the currest best score is a forced mate, either for us or for
             // the opponent, return the move right away.
@@ -158,8 +324,6 @@ impl Search {
             // add some extra time to make sure we investigate it and maybe
             // find a better move
             if depth > 3 && previous_score - score >= PANIC_DROP {
-                let worry = (previous_score - score).score() / PANIC_DROP;
-                println!("{}", "😰".to_owned().repeat(min(worry as usize, 10))); // 😰
                 self.timer.add_panic_time();
             }
 
@@ -172,13 +336,92 @@ impl Search {
                 break;
             }
 
-            alpha = score - ASP_WINDOW;
-            beta = score + ASP_WINDOW;
+            delta = ASP_WINDOW;
+            alpha = score - delta;
+            beta = score + delta;
             previous_score = score;
             depth += 1;
         }
 
-        SearchResult { score, best_move }
+        // Only mates for the side to move are meaningful in a mate search,
+        // any other score is not reliable
+        if self.mate_search && !score.is_positive_mate() {
+            score = Evaluation::new(0);
+            best_move = None;
+            pv.clear();
+        }
+
+        SearchResult {
+            score,
+            best_move,
+            pv,
+            nodes: self.node_count,
+            depth_reached,
+            elapsed_ms: self.timer.elapsed_micros() / 1000,
+            from_book: false,
+        }
+    }
+
+    // Runs only the quiescence search from the given position, so that
+    // the pending captures are resolved before evaluating it
+    pub fn quiescence_eval(&mut self, board: &Board) -> Evaluation {
+        self.init_accumulators(board);
+        let mut pv_line = PVLine::new();
+        self.quiesence_search(board, 0, 0, Evaluation::min_val(), Evaluation::max_val(), &mut pv_line)
+    }
+
+    // Finds the best `multipv` lines for the current position, sorted by score.
+    // In each iteration, every line is searched with a full window excluding the
+    // root moves of the lines that have already been found.
+    pub fn find_best_lines(&mut self, board: &Board) -> Vec<SearchResult> {
+        let lines = min(self.multipv, board.legal_moves().len());
+        let mut results = vec![];
+        let mut depth = 1;
+
+        self.root_color = board.turn_color();
+        self.init_accumulators(board);
+        self.add_root_position(board);
+
+        while depth <= self.max_depth && !self.timer.times_up() {
+            let mut depth_results = Vec::with_capacity(lines);
+            self.excluded_root_moves.clear();
+
+            for _ in 0..lines {
+                let mut pv_line = PVLine::new();
+                let score = self.negamax(board, depth, 0, (Evaluation::min_val(), Evaluation::max_val()), &mut pv_line);
+
+                if self.timer.times_up() {
+                    break;
+                }
+
+                match pv_line.first() {
+                    Some(mv) => self.excluded_root_moves.push(mv),
+                    None => break,
+                }
+
+                depth_results.push(SearchResult {
+                    score,
+                    best_move: pv_line.first(),
+                    pv: pv_line.legal_line(board),
+                    nodes: self.node_count,
+                    depth_reached: depth,
+                    elapsed_ms: self.timer.elapsed_micros() / 1000,
+                    from_book: false,
+                });
+            }
+
+            // Only keep the results of complete iterations
+            if self.timer.times_up() {
+                break;
+            }
+
+            depth_results.sort_by_key(|res| Reverse(res.score));
+            results = depth_results;
+            depth += 1;
+        }
+
+        self.excluded_root_moves.clear();
+        results
     }
 
     fn negamax(
@@ -187,7 +430,6 @@ impl Search {
         mut depth_remaining: u8, 
         current_depth: u8, 
         (mut alpha, mut beta): Bounds,
-        can_null: bool,
         pv_line: &mut PVLine,
     ) -> Evaluation {
         self.node_count += 1;
@@ -195,7 +437,7 @@ impl Search {
         // If, for some reason, we go past the limit depth, return the static
         // evaluation value right away.
         if current_depth >= LIMIT_DEPTH as u8 {
-            return evaluate_position(board);
+            return self.evaluate(board, current_depth);
         }
 
         // Update the timer every 4096 nodes. Using a power of 2 makes things
@@ -203,7 +445,7 @@ impl Search {
         // (Rust does compile modulo N == 0 to bitwise ANDs when N is
         // a power of 2, but we do it explicitly anyways)
         if self.node_count & 4095 == 0 {
-            self.timer.update();
+            self.update_timer();
         }
 
         // If we ran out of time, exit immediately returning whatever. The value
@@ -214,15 +456,25 @@ impl Search {
             return Evaluation::new(0);
         }
 
-        // Check whether the current position is in the trasposition table. Getting the
-        // entry itself from the table is unsafe since there will be lockless concurrent
-        // access (in the future), however, the .get_entry() method does some sanity
-        // checks and only returns an entry if the data inside it is valid and the
-        // stored zobrist key matches.
+        // If this is an immediate draw, we don't have to do anything else. This goes
+        // before looking at the trasposition table, since the score stored for this
+        // position may come from a search where it wasn't a repetition.
+        if is_draw_by_repetition(board, current_depth, &self.past_positions) {
+            return self.draw_score(board);
+        }
+
+        // Check whether the current position is in the trasposition table. The table
+        // is shared without locks when searching with several threads, but the
+        // .get_entry() method only returns an entry if its data was stored along
+        // with the zobrist key that we are looking for.
+        // The root is always searched, since we need its best move and PV line.
+        // A mate search doesn't use the table at all, since its scores only
+        // consider checks and don't mean the same as those of a normal search.
         let mut tt_move = None;
         let zobrist = board.zobrist_key();
-        if let Some(tt_data) = self.tt.get_entry(zobrist, depth_remaining, &mut tt_move) {
-            let tt_score = tt_data.eval_score();
+        let tt_entry = if self.mate_search { None } else { self.tt.get_entry(zobrist, depth_remaining, &mut tt_move) };
+        if let Some(tt_data) = tt_entry.filter(|_| current_depth != 0) {
+            let tt_score = tt_data.eval_score(current_depth);
             match tt_data.node_type() {
                 NodeType::Exact => return tt_score,
                 NodeType::Lowerbound => alpha = max(alpha, tt_score),
@@ -234,9 +486,13 @@ impl Search {
             }
         }
 
-        // If this is an immediate draw, we don't have to do anything else
-        if is_draw_by_repetition(board, current_depth, &self.past_positions) {
-            return Evaluation::contempt();
+        // Endgames with few enough pieces are looked up in the tablebases, if there
+        // are any. They are only probed right after a capture or pawn move, since
+        // otherwise the result may depend on the fifty move counter.
+        if current_depth != 0 && !self.mate_search && board.fifty_move_rule_counter() == 0 {
+            if let Some(wdl) = self.probe_wdl(board) {
+                return self.tablebase_score(wdl, board, current_depth);
+            }
         }
 
         // The current position is not stored, perform the full search from here.
@@ -245,25 +501,33 @@ impl Search {
         // entering in quiesence mode
         let color_moving = board.turn_color();
         let is_check = board.is_check(color_moving);
-        if is_check {
+        if is_check && !self.mate_search {
             depth_remaining += 1;
         }
 
         // If we are on a leaf node, use the quiesence search to make sure the
-        // static evaluation is reliable
-        if depth_remaining == 0 {
-            return self.quiesence_search(board, current_depth, alpha, beta, pv_line);
+        // static evaluation is reliable. A mate search doesn't care about that,
+        // reaching a leaf means that no mate was found in this line.
+        if depth_remaining == 0 && self.mate_search {
+            return if is_check && board.is_checkmate() {
+                Evaluation::min_val() + current_depth as EvalScore
+            } else {
+                Evaluation::new(0)
+            };
+        } else if depth_remaining == 0 {
+            return self.quiesence_search(board, current_depth, 0, alpha, beta, pv_line);
         }
 
-        let is_pv = beta - alpha != 1;
+        // Widen the scores first, the full window would overflow an EvalScore
+        let is_pv = beta.score() as i32 - alpha.score() as i32 != 1;
 
         // Reverse futility pruning: if the current score exceeds what the
         // opponent can already guarantee, even if we substract a margin from it,
         // we can assume that they will not allow this position and prune it.
         // TO-DO: probably add a depth condition to avoid calling the evaluation
         // in early depths where the margin is huge and see how that works
-        if !is_pv && !is_check && !beta.is_mate() {
-            let score = evaluate_position(board);
+        if !is_pv && !is_check && !beta.is_mate() && !self.mate_search {
+            let score = self.evaluate(board, current_depth);
             let margin = depth_remaining as EvalScore * REV_FUTILITY_MARGIN;
             let reduced = score - margin;
 
@@ -272,6 +536,22 @@ impl Search {
             }
         }
 
+        // Razoring: close to the horizon, if the static evaluation is so far
+        // below alpha that not even a margin can bring it back, go straight
+        // into the quiescence search, and trust its result if it fails low too.
+        // The quiescence search is fail-hard, so we use a null window below
+        // alpha to be able to tell whether it failed low.
+        if self.razoring && (depth_remaining as usize) < RAZOR_MARGIN.len() && !is_pv && !is_check
+        && !alpha.is_mate() && !beta.is_mate() && !self.mate_search {
+            let eval = self.evaluate(board, current_depth);
+            if eval + RAZOR_MARGIN[depth_remaining as usize] < alpha {
+                let score = self.quiesence_search(board, current_depth, 0, alpha - 1, alpha, pv_line);
+                if score < alpha {
+                    return score;
+                }
+            }
+        }
+
         // PV line for the recursive calls
         let mut next_pv_line = PVLine::new();
 
@@ -283,10 +563,18 @@ impl Search {
         // current side to move is in check (it would be illegal), or in late
         // game positions where not moving is actually the best move. Also, don't
         // do it in positions close to the horizon.
+        // Two null moves in a row would just give the turn back to us, searching
+        // the same position with less depth, so we never pass right after the
+        // opponent passed.
+        let parent_passed = current_depth > 0 && self.move_stack[current_depth as usize - 1].is_none();
 
-        if can_null && !is_check && depth_remaining > NULL_MOVE_REDUCTION && !board.only_pawns_or_endgame() && !is_pv {
+        if !parent_passed && !is_check && depth_remaining > NULL_MOVE_REDUCTION && !board.only_pawns_or_endgame() && !is_pv && !self.mate_search {
             let new_board = board.make_null_move();
-            let score = -self.negamax(&new_board, depth_remaining - NULL_MOVE_REDUCTION - 1, current_depth + 1, (-beta, -beta + 1), false, &mut next_pv_line);
+            self.update_accumulator(board, None, current_depth);
+            self.move_stack[current_depth as usize] = None;
+            self.past_positions.push(new_board.zobrist_key());
+            let score = -self.negamax(&new_board, depth_remaining - NULL_MOVE_REDUCTION - 1, current_depth + 1, (-beta, -beta + 1), &mut next_pv_line);
+            self.past_positions.pop();
 
             // If the opponent can't improve their position, return beta
             if score >= beta && !score.is_positive_mate() {
@@ -303,13 +591,22 @@ impl Search {
         // positions such as checks and in the PV.
         let mut do_futility = false;
         if (depth_remaining as usize)  < FUTILIY_MARGINS.len() && !is_pv && !is_check
-        && !alpha.is_mate() {
-            let eval = evaluate_position(board);
+        && !alpha.is_mate() && !self.mate_search {
+            let eval = self.evaluate(board, current_depth);
             if eval + FUTILIY_MARGINS[depth_remaining as usize] < alpha {
                 do_futility = true;
             }
         }
 
+        // Internal iterative deepening: in PV nodes without a move from the
+        // trasposition table, move ordering is poor, so we do a shallower search
+        // first to find a good move to try before the rest
+        if tt_move.is_none() && is_pv && depth_remaining >= IID_MIN_DEPTH && !self.mate_search {
+            self.negamax(board, depth_remaining - IID_REDUCTION, current_depth, (alpha, beta), &mut next_pv_line);
+            tt_move = next_pv_line.first();
+            next_pv_line.clear();
+        }
+
         let mut best_score = Evaluation::min_val();
         let mut best_move = None;
         let mut node_type = NodeType::Upperbound;
@@ -317,14 +614,29 @@ impl Search {
         // We use the pseudolegal move generator to construct the new board ourselves
         // and filter out moves that result in illegal positions. This is exactly what
         // board.legal_moves() does, so this way we avoid doing it twice.
-        let moves = board.pseudolegal_moves();
+        let moves = board.pseudolegal_move_list();
         let mut analyzed_moves = 0;
-        let rated_moves = order_moves(moves, board, tt_move, &self.killers[current_depth as usize], &self.history);
+        let countermove = self.countermove(board, current_depth);
+        let rated_moves = order_moves(&moves, board, tt_move, &self.killers[current_depth as usize], countermove, &self.history, &self.capture_history);
 
         // A list with the quiet (non-capture) moves that we have analyzed
-        let mut analyzed_quiets = Vec::with_capacity(64);
+        let mut analyzed_quiets = MoveList::new();
+
+        let excluding_moves = current_depth == 0 && !self.excluded_root_moves.is_empty();
+
+        // Whether this is a mate search and the side trying to deliver mate is moving
+        let only_checks = self.mate_search && current_depth & 1 == 0;
+        let mut skipped_quiet_moves = false;
 
         for RatedMove{mv, ..} in rated_moves {
+            if excluding_moves && self.excluded_root_moves.contains(&mv) {
+                continue;
+            }
+
+            if current_depth == 0 && !self.tb_root_moves.is_empty() && !self.tb_root_moves.contains(&mv) {
+                continue;
+            }
+
             let next_board = board.make_move(&mv);
 
             // This is a pseudo-legal move, we must make sure that the side moving is not in check.
@@ -336,20 +648,29 @@ impl Search {
 
             // Some information about this move
             let is_capture = mv.is_capture(board);
-            let cap_or_prom = is_capture || matches!(mv, Move::PawnPromotion{..});
+            let cap_or_prom = !mv.is_quiet(board);
             let gives_check = next_board.is_check(next_board.turn_color());
             let is_pawn_move = mv.piece_moving(board) == Pawn;
             let is_tactical = is_check || gives_check || cap_or_prom || is_pawn_move || self.is_killer(&mv, current_depth);
 
-            // Late move pruning: in non-PV nodes, skip late quiet moves since they are less
-            // likely to be interesting. The closer we are to the horizon, the more
-            // moves we prune. However, we only do that when there are non-pawn pieces on
-            // the board, not in the root node, and we're not under a checkmate threat
-            // Not working very well ATM
-            // if !is_pv && !is_tactical && current_depth != 0 && !board.only_pawns()
-            //    && !best_score.is_negative_mate() && analyzed_moves >= max_movecount(depth_remaining) {
-            //     continue;
-            // }
+            // In a mate search, every move of the attacking side must be a check
+            if only_checks && !gives_check {
+                skipped_quiet_moves = true;
+                continue;
+            }
+
+            // Late move pruning: in non-PV nodes close to the horizon, skip late quiet
+            // moves since they are less likely to be interesting. The closer we are to
+            // the horizon, the more moves we prune. However, we only do that when there
+            // are non-pawn pieces on the board, not in the root node, and when the moves
+            // searched so far don't all get us mated, since a quiet move may be the only
+            // defense. Since at least one move has been searched, pruning the rest never
+            // makes us think that there are no legal moves.
+            if !is_pv && !is_tactical && current_depth != 0 && depth_remaining <= LMP_MAX_DEPTH
+               && !self.mate_search && !board.only_pawns() && !best_score.is_negative_mate()
+               && analyzed_moves >= max_movecount(depth_remaining) {
+                continue;
+            }
 
             // Futility pruning, part 2: if we decided earlier that we can
             // use this pruning, and the current move is not a tactical one,
@@ -358,8 +679,10 @@ impl Search {
                 continue;
             }
 
-            // Update the vec of past positions with the current zobrist key before the recursive calls
-            self.past_positions.push(zobrist);
+            // Update the vec of past positions with the new zobrist key before the recursive calls
+            self.past_positions.push(next_board.zobrist_key());
+            self.move_stack[current_depth as usize] = Some(mv);
+            self.update_accumulator(board, Some(&mv), current_depth);
 
             // Late move reduction: Moves after the first one are less likely
             // to be interesting, so we search them with a reduced depth and
@@ -367,7 +690,7 @@ impl Search {
             // captures, promotions, PV nodes, shallow depth, killers and pawn moves
             // Also, we never reduce at the root
             let mut red = 0;
-            if !is_pv && !is_tactical && depth_remaining >= 3 && analyzed_moves >= LMR_MOVES && current_depth != 0 {
+            if !is_pv && !is_tactical && depth_remaining >= 3 && analyzed_moves >= LMR_MOVES && current_depth != 0 && !self.mate_search {
                 // The base reduction starts at 2 because it's one more than the
                 // usual reduction in depth by 1 when calling recursively
                 // The reduction increases by 1 for each 5 moves after the LMR move limit.
@@ -389,25 +712,25 @@ impl Search {
 
             // If we are reducing, try to search with reduced depth first
             if red != 0 {
-                score = -self.negamax(&next_board, depth_remaining - red, current_depth + 1, ((-alpha)-1, -alpha), true, &mut next_pv_line);
+                score = -self.negamax(&next_board, depth_remaining - red, current_depth + 1, ((-alpha)-1, -alpha), &mut next_pv_line);
                 // If the reduced search fails low, we don't have to search using full depth
                 do_full_depth = score > alpha;
             }
 
             // Since the moves are ordered, only evaluate the first move with a full window
             if analyzed_moves == 0 {
-                score = -self.negamax(&next_board, depth_remaining - 1, current_depth + 1, (-beta, -alpha), true, &mut next_pv_line);
+                score = -self.negamax(&next_board, depth_remaining - 1, current_depth + 1, (-beta, -alpha), &mut next_pv_line);
             } else if do_full_depth {
                 // Try a minimal window first. If the value falls under [alpha, beta] then use the standard window
-                score = -self.negamax(&next_board, depth_remaining - 1, current_depth + 1, ((-alpha)-1, -alpha), true, &mut next_pv_line);
+                score = -self.negamax(&next_board, depth_remaining - 1, current_depth + 1, ((-alpha)-1, -alpha), &mut next_pv_line);
 
                 if score > alpha && score < beta {
                     // Do a full evaluation since the position was not significantly worsened
-                    score = -self.negamax(&next_board, depth_remaining - 1, current_depth + 1, (-beta, -alpha), true, &mut next_pv_line);
+                    score = -self.negamax(&next_board, depth_remaining - 1, current_depth + 1, (-beta, -alpha), &mut next_pv_line);
                 }
             };
 
-            // We're done calling recursively, remove the current state from the history
+            // We're done calling recursively, remove the new state from the history
             self.past_positions.pop();
             analyzed_moves += 1;
 
@@ -452,6 +775,16 @@ impl Search {
         // If we have a best move, update history stats and killers
         if let Some(bm) = best_move {
             self.update_histories(&bm, &analyzed_quiets, board, depth_remaining);
+
+            // A quiet move that caused a cutoff is a good reply to the previous move
+            if node_type == NodeType::Lowerbound && !bm.is_capture(board) {
+                self.update_countermove(bm, board, current_depth);
+            } else if node_type == NodeType::Lowerbound {
+                self.update_capture_history(&bm, board, depth_remaining);
+            }
+        } else if skipped_quiet_moves {
+            // There are legal moves, but none of them are checks, so there is no mate here
+            best_score = Evaluation::new(0);
         } else {
             // Otherwise, there are no legal moves available.
             // Check whether this is a checkmate or a draw, and assign
@@ -461,13 +794,17 @@ impl Search {
                 Evaluation::min_val() + current_depth as EvalScore
             } else {
                 // Stalemate or other cause of draw
-                Evaluation::contempt()
+                self.draw_score(board)
             };
         }
 
         // Update the transposition table with the information that we have obtained
-        // for this position
-        self.tt.write_entry(zobrist, TTEntry::new(zobrist, depth_remaining, best_score, node_type, best_move));
+        // for this position. When looking for the next MultiPV line, the root result
+        // is only about some of the moves, so it is not stored. Neither are the
+        // results of a mate search, see above.
+        if !excluding_moves && !self.mate_search {
+            self.tt.write_entry(TTEntry::new(zobrist, depth_remaining, best_score, node_type, best_move, current_depth));
+        }
         best_score
     }
 
@@ -479,6 +816,7 @@ impl Search {
         &mut self, 
         board: &Board, 
         current_depth: u8, 
+        qsearch_ply: u8, // Plies since the quiescence search started
         mut alpha: Evaluation, 
         beta: Evaluation,
         pv_line: &mut PVLine
@@ -488,12 +826,12 @@ impl Search {
         // If, for some reason, we go past the limit depth, return the static
         // evaluation value right away.
         if current_depth >= LIMIT_DEPTH as u8 {
-            return evaluate_position(board);
+            return self.evaluate(board, current_depth);
         }
 
         // Update the timer every 4096 nodes.
         if self.node_count & 4095 == 0 {
-            self.timer.update();
+            self.update_timer();
         }
 
         // If we ran out of time, exit immediately returning whatever. The value
@@ -504,7 +842,10 @@ impl Search {
             return Evaluation::new(0);
         }
 
-        let static_score = evaluate_position(board);
+        // There is no need to look for repetitions here. Captures and promotions
+        // can't be undone, so no position in a quiescence line can have happened
+        // before it, and the node where it starts was already checked by negamax.
+        let static_score = self.evaluate(board, current_depth);
 
         if static_score >= beta {
             return beta;
@@ -512,12 +853,30 @@ impl Search {
             alpha = static_score;
         }
 
+        if qsearch_ply >= self.max_qsearch_ply {
+            return alpha;
+        }
+
+        // Delta pruning is not done with few pieces left, where giving up material
+        // to reach a won ending or a draw is more common
+        let delta_pruning = !board.only_pawns_or_endgame();
         let mut next_pv_line = PVLine::new();
 
         // Only consider moves that are captures or pawn promotions
-        let moves = board.pseudolegal_caps();
-        let rated_moves = order_moves(moves, board, None, &self.killers[current_depth as usize], &self.history);
+        let moves = board.pseudolegal_caps_list();
+        let rated_moves = order_moves(&moves, board, None, &self.killers[current_depth as usize], None, &self.history, &self.capture_history);
         for RatedMove{mv, ..} in rated_moves {
+            // Captures that lose material are very unlikely to improve alpha
+            if is_losing_move(board, &mv) {
+                continue;
+            }
+
+            // Delta pruning: skip captures that can't raise alpha even if the
+            // material they win comes with an extra positional advantage
+            if delta_pruning && static_score + material_gain(board, &mv) + DELTA_MARGIN < alpha {
+                continue;
+            }
+
             // As in the normal search, we are using pseudolegal moves, so we must make sure that
             // the moving side is not in check. Castling moves are not generated now so we
             // don't have to worry about them
@@ -526,7 +885,8 @@ impl Search {
                 continue;
             }
 
-            let next_score = -self.quiesence_search(&next_board, current_depth + 1, -beta, -alpha, &mut next_pv_line);
+            self.update_accumulator(board, Some(&mv), current_depth);
+            let next_score = -self.quiesence_search(&next_board, current_depth + 1, qsearch_ply + 1, -beta, -alpha, &mut next_pv_line);
 
             if next_score >= beta {
                 return beta;
@@ -543,10 +903,129 @@ impl Search {
         alpha
     }
 
+    // Checks whether the time is up, the node budget has been spent,
+    // or the search has been told to stop
+    fn update_timer(&mut self) {
+        self.timer.update();
+
+        let nodes = match self.shared_nodes {
+            Some(shared) => {
+                let new_nodes = self.node_count - self.reported_nodes;
+                self.reported_nodes = self.node_count;
+                shared.fetch_add(new_nodes, Ordering::Relaxed) + new_nodes
+            },
+            None => self.node_count,
+        };
+
+        if nodes >= self.max_nodes {
+            self.timer.stop();
+        }
+
+        let must_stop = |stop: &AtomicBool| stop.load(Ordering::Relaxed);
+        if self.stop.is_some_and(must_stop) || self.user_stop.as_deref().is_some_and(must_stop) {
+            self.timer.stop();
+        }
+    }
+
+    // Returning to the root position during the search is a repetition,
+    // so it must be in the history even if the caller didn't include it
+    fn add_root_position(&mut self, board: &Board) {
+        if self.past_positions.last() != Some(&board.zobrist_key()) {
+            self.past_positions.push(board.zobrist_key());
+        }
+    }
+
+    // The contempt is from the point of view of the side to move at the root,
+    // so the opponent gets the opposite score for the same draw. Otherwise,
+    // the negamax sign would make the engine avoid draws only in half the plies.
+    fn draw_score(&self, board: &Board) -> Evaluation {
+        let score = Evaluation::contempt(self.contempt);
+        if board.turn_color() == self.root_color { score } else { -score }
+    }
+
+    // If the root position is in the tablebases, only the moves that keep its result
+    // are searched, which the search alone may not find in long endgames. Mate
+    // searches look for the quickest mate instead, so all moves are searched.
+    fn init_tb_root_moves(&mut self, board: &Board) {
+        self.tb_root_moves.clear();
+        if let Some(tb) = self.tablebase.filter(|tb| !self.mate_search && board.get_all_bitboard().count() <= tb.max_pieces()) {
+            self.tb_root_moves = tb.best_root_moves(board).unwrap_or_default();
+        }
+    }
+
+    fn probe_wdl(&self, board: &Board) -> Option<Wdl> {
+        let tb = self.tablebase.filter(|tb| board.get_all_bitboard().count() <= tb.max_pieces())?;
+        tb.probe_wdl(board)
+    }
+
+    // Tablebase wins are closer to zero the further they are from the root, so
+    // that the quickest one is preferred. Results that are spoiled by the fifty
+    // move rule are draws.
+    fn tablebase_score(&self, wdl: Wdl, board: &Board, current_depth: u8) -> Evaluation {
+        match wdl {
+            Wdl::Win => Evaluation::new(TB_WIN_SCORE - current_depth as EvalScore),
+            Wdl::Loss => Evaluation::new(current_depth as EvalScore - TB_WIN_SCORE),
+            Wdl::CursedWin | Wdl::Draw | Wdl::BlessedLoss => self.draw_score(board),
+        }
+    }
+
+    // The accumulators of the following plies are derived from the root one
+    fn init_accumulators(&mut self, board: &Board) {
+        if let Some(net) = self.network {
+            self.accumulators = vec![Accumulator::new(net, board); LIMIT_DEPTH + 2];
+        }
+    }
+
+    // Uses the NNUE network to evaluate the position if we have one,
+    // otherwise, the classical evaluation selected in the config is used
+    fn evaluate(&self, board: &Board, current_depth: u8) -> Evaluation {
+        match (self.network, self.eval_mode) {
+            (Some(net), _) => self.accumulators[current_depth as usize].evaluate(net, board.turn_color()),
+            (None, EvalMode::Full) => evaluate_position(board),
+            (None, EvalMode::MaterialOnly) => evaluate_material_only(board),
+        }
+    }
+
+    // Computes the accumulator for the next ply after a move (or a null move)
+    fn update_accumulator(&mut self, board: &Board, mv: Option<&Move>, current_depth: u8) {
+        if let Some(net) = self.network {
+            let i = current_depth as usize;
+            self.accumulators[i + 1] = match mv {
+                Some(mv) => self.accumulators[i].make_move(net, board, mv),
+                None => self.accumulators[i],
+            };
+        }
+    }
+
     fn is_killer(&self, mv: &Move, depth: u8) -> bool {
         self.killers[depth as usize][0] == *mv || self.killers[depth as usize][1] == *mv
     }
 
+    // The move that refuted the previous move the last time it was played, if any
+    fn countermove(&self, board: &Board, current_depth: u8) -> Option<Move> {
+        let (from, to) = self.previous_move_squares(board, current_depth)?;
+        Some(self.countermoves[from][to])
+    }
+
+    fn update_countermove(&mut self, mv: Move, board: &Board, current_depth: u8) {
+        if let Some((from, to)) = self.previous_move_squares(board, current_depth) {
+            self.countermoves[from][to] = mv;
+        }
+    }
+
+    fn previous_move_squares(&self, board: &Board, current_depth: u8) -> Option<(usize, usize)> {
+        let prev_ply = current_depth.checked_sub(1)?;
+        let prev_move = self.move_stack[prev_ply as usize]?;
+        Some(get_from_to(&prev_move, !board.turn_color()))
+    }
+
+    // Rewards a capture that caused a cutoff. Unlike with quiet moves, penalizing
+    // the captures tried before it made the ordering worse.
+    fn update_capture_history(&mut self, best_move: &Move, board: &Board, depth: u8) {
+        let bonus = (depth as MoveScore) * (depth as MoveScore);
+        self.capture_history.add_bonus(best_move, board, bonus);
+    }
+
     fn update_histories(&mut self, best_move: &Move, quiet_moves: &[Move], board: &Board, depth: u8) {
         // We only need to update histories if the best move is a quiet one
         if !best_move.is_capture(board) {
@@ -573,8 +1052,19 @@ impl Default for SearchOptions {
         Self {
             total_time_remaining: None,
             moves_until_control: None,
+            increment_ms: None,
             time_for_move: None,
+            move_overhead_ms: 10,
             max_depth: Some(7),
+            multipv: 1,
+            stop: None,
+            max_nodes: None,
+            mate_in: None,
+            max_qsearch_ply: None,
+            razoring: true,
+            aspiration_widening: true,
+            only_best_book_moves: None,
+            min_book_weight: 0,
         }
     }
 }
@@ -582,45 +1072,57 @@ impl Default for SearchOptions {
 ///////////////////////////////////////////////////////////////////////////////
 // Some auxiliary functions:
 
+// Material won by a capture or promotion, not counting what may be lost afterwards
+fn material_gain(board: &Board, mv: &Move) -> EvalScore {
+    let captured = match mv.piece_captured(board) {
+        Some(piece) => value_of_capture(piece),
+        None if mv.is_capture(board) => value_of_capture(Pawn), // En passant
+        None => 0,
+    };
+    let promoted = mv.promotion_piece().map_or(0, |piece| value_of_capture(piece) - value_of_capture(Pawn));
+    (captured + promoted) as EvalScore
+}
+
 // Determines if a given position is a draw by repetition considering the previous history.
 // This function returns true if the current state is either:
 // - The third repetition of a position, where the previous two have happened
 //   during the previous moves that have been played
-// - The second repetition of a position that occured entirely during the search process
+// - The second repetition of a position that occured during the search process,
+//   including the root of the search
 // This is because we assume that if a position has repeated twice during
 // the search, it is likely that a third repetition will occur, so we save time.
 pub fn is_draw_by_repetition(board: &Board, cur_depth: u8, history: &[u64]) -> bool {
     let current_zobrist = board.zobrist_key();
     let mut rep_count = 1;
 
+    // The history may or may not end with the current position. It's left out
+    // if so, since what matters is how many plies ago every other state happened.
+    let previous = match history.split_last() {
+        Some((&last, rest)) if last == current_zobrist => rest,
+        _ => history,
+    };
+
     // We don't actually have to consider all past states. Moves which update the
     // 50 move rule are irreversible, and thus no repetitions can occur before them.
-    let last_irr_move = board.current_ply() - board.fifty_move_rule_counter();
-
-    // This is a board state that occured during the search, so we're a number of moves
-    // ahead of the actual game. Determine the last ply that was actually played so we
-    // know if we should stop searching at 2 repetitions or 3 (see comment above the function)
-    let last_played_ply = board.current_ply() - cur_depth as u16;
-
-    let prev_states = history.iter()
+    let prev_states = previous.iter()
         .copied() // Copy the u64 references into this iter
-        .enumerate() // Associate each board state with the (0-based) ply in which it occured
-        .skip(last_irr_move as usize) // Fast forward to the last irreversible state of the board
         .rev() // Start with the most recent move and go backwards
-        .step_by(2) // We only need to consider every other state, since reps can only
-                    // occur when the side to play is the same as the current one
-        .skip(1); // We don't need to consider the current state 
+        .take(board.fifty_move_rule_counter() as usize) // Stop at the last irreversible state of the board
+        .skip(1) // We only need to consider every other state, since reps can only
+        .step_by(2) // occur when the side to play is the same as the current one
+        .enumerate()
+        .map(|(i, zobrist)| (2 * (i + 1), zobrist)); // Associate each state with how many plies ago it occured
 
-    for (ply, zobrist) in prev_states {
+    for (plies_ago, zobrist) in prev_states {
         if zobrist == current_zobrist { // We have a repetition!
             rep_count += 1;
-            // Stop if we're still inside the search and it's the second rep,
-            // or if it's the third one
-            if rep_count == 2 && ply as u16 > last_played_ply || rep_count == 3 {
+            // Stop if it's the second rep and the previous one happened during the search
+            // (the root of the search is `cur_depth` plies ago), or if it's the third one
+            if rep_count == 2 && plies_ago <= cur_depth as usize || rep_count == 3 {
                 return true;
             }
         }
-    };
-        
+    }
+
     false
 }
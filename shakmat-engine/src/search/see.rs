@@ -0,0 +1,100 @@
+use std::cmp::max;
+use shakmat_core::{BitBoard, Board, Color::{self, *}, Move, PieceType::{self, *}, move_gen};
+
+use super::move_ordering::value_of_capture;
+use crate::evaluation::EvalScore;
+
+// Static Exchange Evaluation: determines the material balance, from the point of
+// view of the side moving, after all the captures on the target square of the move
+// are resolved, each side always recapturing with their least valuable piece.
+// Either side can stop capturing at any point if continuing doesn't pay off.
+pub fn see(board: &Board, mv: &Move) -> EvalScore {
+    let (from, to) = match mv {
+        Move::Normal { from, to } | Move::PawnPromotion { from, to, .. } => (*from, *to),
+        _ => return 0, // Castling never captures anything
+    };
+
+    let mut occupied = board.get_all_bitboard();
+    let mut attacker = mv.piece_moving(board);
+    let mut from_bb = BitBoard::from_square(from);
+    let mut color = board.turn_color();
+
+    // Score gained by each successive capture, as if the sequence stopped there
+    let mut gain = [0; 32];
+    gain[0] = match mv.piece_captured(board) {
+        Some(piece) => value_of_capture(piece),
+        None if attacker == Pawn && (BitBoard::from_square(to) & board.ep_square()).is_not_empty() => {
+            // En passant: the captured pawn isn't on the target square
+            let ep_target = if color == White { to - 8 } else { to + 8 };
+            occupied ^= BitBoard::from_square(ep_target);
+            value_of_capture(Pawn)
+        },
+        None => 0,
+    };
+
+    // Promotions also gain the difference between the new piece and the pawn
+    if let Some(promote_to) = mv.promotion_piece() {
+        gain[0] += value_of_capture(promote_to) - value_of_capture(Pawn);
+        attacker = promote_to;
+    }
+
+    let mut depth = 0;
+    loop {
+        depth += 1;
+        // Speculative score if the piece we just moved is captured back
+        gain[depth] = value_of_capture(attacker) - gain[depth - 1];
+
+        // If neither stopping nor continuing improves the result, we're done
+        if max(-gain[depth - 1], gain[depth]) < 0 || depth == gain.len() - 1 {
+            break;
+        }
+
+        // Remove the piece that just captured, this may uncover sliding attackers behind it
+        occupied ^= from_bb;
+        color = !color;
+
+        match least_valuable_attacker(board, to, occupied, color) {
+            Some((piece, bb)) => {
+                attacker = piece;
+                from_bb = bb;
+            },
+            None => break,
+        }
+    }
+
+    // Go back through the sequence, letting each side stop capturing if it's better for them
+    while depth > 1 {
+        depth -= 1;
+        gain[depth - 1] = -max(-gain[depth - 1], gain[depth]);
+    }
+
+    gain[0] as EvalScore
+}
+
+// Whether a move loses material according to SEE. Capturing a piece that is
+// at least as valuable as the one moving never does, so we skip those.
+pub fn is_losing_move(board: &Board, mv: &Move) -> bool {
+    let captured_value = mv.piece_captured(board).map_or(0, value_of_capture);
+    captured_value < value_of_capture(mv.piece_moving(board)) && see(board, mv) < 0
+}
+
+// Finds the cheapest piece of a color attacking a square, considering only the
+// pieces that are still in the provided occupancy bitboard
+fn least_valuable_attacker(board: &Board, square: u8, occupied: BitBoard, color: Color) -> Option<(PieceType, BitBoard)> {
+    let sq = square as usize;
+    let pieces = board.get_pieces(color);
+    let diagonal = move_gen::bishop_moves(sq, occupied);
+    let orthogonal = move_gen::rook_moves(sq, occupied);
+
+    [
+        (Pawn, move_gen::pawn_attacks(sq, !color) & pieces.pawns),
+        (Knight, move_gen::knight_moves(sq) & pieces.knights),
+        (Bishop, diagonal & pieces.bishops),
+        (Rook, orthogonal & pieces.rooks),
+        (Queen, (diagonal | orthogonal) & pieces.queens),
+        (King, move_gen::king_moves(sq) & pieces.king),
+    ].into_iter()
+        .map(|(piece, attackers)| (piece, attackers & occupied))
+        .find(|(_, attackers)| attackers.is_not_empty())
+        .map(|(piece, attackers)| (piece, BitBoard::from_square(attackers.first_piece_index())))
+}
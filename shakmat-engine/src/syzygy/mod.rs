@@ -0,0 +1,3 @@
+mod tablebase;
+
+pub use tablebase::{Tablebase, Wdl};
@@ -0,0 +1,160 @@
+use std::cmp::Reverse;
+use shakmaty::{CastlingMode, Chess, FromSetup, Piece, PositionError, Role, Setup, Square};
+use shakmaty_syzygy::{AmbiguousWdl, Syzygy, Tablebase as SyzygyTables, Wdl as SyzygyWdl};
+
+use shakmat_core::{Board, Color::{self, *}, Move, PieceType::{self, *}};
+
+// Win/draw/loss information stored in the Syzygy WDL tables, from the point
+// of view of the side to move. Cursed wins and blessed losses are wins and
+// losses that are drawn due to the fifty move rule.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Wdl {
+    Loss,
+    BlessedLoss,
+    Draw,
+    CursedWin,
+    Win,
+}
+
+// A set of Syzygy tablebases, read from the files in a directory when probed
+pub struct Tablebase {
+    tables: SyzygyTables<Chess>,
+}
+
+impl Tablebase {
+    // Looks for WDL (.rtbw) and DTZ (.rtbz) tables in a directory. Fails if the
+    // directory can't be read, but not if it's missing some or all of the tables:
+    // positions without a table are just searched as usual.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn load(path: &str) -> Result<Self, String> {
+        let mut tables = SyzygyTables::new();
+        tables.add_directory(path).map_err(|err| format!("Cannot read the Syzygy tablebases in {path}: {err}"))?;
+        Ok(Self { tables })
+    }
+
+    // There is no file system to read the tables from in the browser
+    #[cfg(target_arch = "wasm32")]
+    pub fn load(path: &str) -> Result<Self, String> {
+        Err(format!("Cannot read the Syzygy tablebases in {path}: not supported in WebAssembly"))
+    }
+
+    // Largest number of pieces, kings included, of the positions in the tables
+    pub fn max_pieces(&self) -> u32 {
+        self.tables.max_pieces() as u32
+    }
+
+    // Returns the WDL value of the position for the side to move, if it is in
+    // the tables. Right after a capture or pawn move only the WDL table is
+    // needed, otherwise the DTZ table is used to take the fifty move counter
+    // into account, and the result may be unknown due to its rounding.
+    pub fn probe_wdl(&self, board: &Board) -> Option<Wdl> {
+        let pos = to_position(board)?;
+        let wdl = if board.fifty_move_rule_counter() == 0 {
+            self.tables.probe_wdl_after_zeroing(&pos).ok()?
+        } else {
+            self.tables.probe_wdl(&pos).ok()?.unambiguous()?
+        };
+        Some(wdl.into())
+    }
+
+    // Returns the distance to zeroing of the position: the number of plies until
+    // the next capture or pawn move, with optimal play by both sides. It is positive
+    // if the side to move wins, negative if it loses, and 0 if it's a draw. The DTZ
+    // tables may store it rounded up by one ply.
+    pub fn probe_dtz(&self, board: &Board) -> Option<i32> {
+        let pos = to_position(board)?;
+        self.tables.probe_dtz(&pos).ok().map(|dtz| dtz.ignore_rounding().0)
+    }
+
+    // Returns the legal moves that keep the best outcome of the position, taking
+    // the fifty move counter into account. If the position is won or lost, only
+    // the moves that make the quickest progress (or the slowest, if we lose)
+    // are returned, so that the win is not frustrated by the fifty move rule.
+    // Returns None if any of the positions after the moves can't be probed.
+    pub fn best_root_moves(&self, board: &Board) -> Option<Vec<Move>> {
+        let rated_moves = board.legal_moves().into_iter().map(|mv| {
+            let next_board = board.make_move(&mv);
+            let pos = to_position(&next_board)?;
+            // The result of the opponent, so the lowest is the best for us
+            let wdl = self.tables.probe_wdl(&pos).ok()?;
+            let progress = if wdl == AmbiguousWdl::Draw {
+                None
+            } else {
+                // Mating right away is always the best, then a capture or pawn move
+                // if winning (and the opposite when losing), and then the DTZ
+                let dtz = self.tables.probe_dtz(&pos).ok()?.ignore_rounding();
+                let zeroing = next_board.fifty_move_rule_counter() == 0;
+                Some((Reverse(next_board.is_checkmate()), zeroing ^ dtz.is_negative(), Reverse(dtz)))
+            };
+            Some((mv, (wdl, progress)))
+        }).collect::<Option<Vec<_>>>()?;
+
+        let best = rated_moves.iter().map(|(_, rating)| *rating).min()?;
+        Some(rated_moves.into_iter().filter(|(_, rating)| *rating == best).map(|(mv, _)| mv).collect())
+    }
+}
+
+impl From<SyzygyWdl> for Wdl {
+    fn from(wdl: SyzygyWdl) -> Self {
+        match wdl {
+            SyzygyWdl::Loss => Wdl::Loss,
+            SyzygyWdl::BlessedLoss => Wdl::BlessedLoss,
+            SyzygyWdl::Draw => Wdl::Draw,
+            SyzygyWdl::CursedWin => Wdl::CursedWin,
+            SyzygyWdl::Win => Wdl::Win,
+        }
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+// Conversion of boards into the positions that the tables are probed with.
+// Positions with castling rights or more pieces than the tables are never
+// stored in them, so they are not converted.
+
+fn to_position(board: &Board) -> Option<Chess> {
+    if !board.castling_info().has_no_rights() || board.get_all_bitboard().count() > Chess::MAX_PIECES as u32 {
+        return None;
+    }
+
+    let mut setup = Setup::empty();
+    for color in [White, Black] {
+        for piece in [Pawn, Knight, Bishop, Rook, Queen, King] {
+            for square in board.get_pieces(color).get_pieces_of_type(piece).piece_indices() {
+                setup.board.set_piece_at(to_square(square), Piece { color: to_color(color), role: to_role(piece) });
+            }
+        }
+    }
+
+    setup.turn = to_color(board.turn_color());
+    setup.ep_square = board.ep_square().piece_indices().next().map(to_square);
+    setup.halfmoves = board.fifty_move_rule_counter() as u32;
+
+    // The en passant square is set after every double pawn push, even if
+    // it can't be captured, which the position doesn't allow
+    Chess::from_setup(setup, CastlingMode::Standard)
+        .or_else(PositionError::ignore_invalid_ep_square)
+        .ok()
+}
+
+// Shakmat numbers the squares from h1 to a8, and shakmaty from a1 to h8
+fn to_square(square: u8) -> Square {
+    Square::new((square ^ 7) as u32)
+}
+
+fn to_color(color: Color) -> shakmaty::Color {
+    match color {
+        White => shakmaty::Color::White,
+        Black => shakmaty::Color::Black,
+    }
+}
+
+fn to_role(piece: PieceType) -> Role {
+    match piece {
+        Pawn => Role::Pawn,
+        Knight => Role::Knight,
+        Bishop => Role::Bishop,
+        Rook => Role::Rook,
+        Queen => Role::Queen,
+        King => Role::King,
+    }
+}
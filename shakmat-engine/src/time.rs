@@ -7,9 +7,6 @@ use web_time::Instant;
 use std::cmp::min;
 use crate::search::SearchOptions;
 
-// Offset in microseconds to substract from the allocated time,
-// based on my observations. TO-DO: does this really make sense?
-const OFFSET: u64 = 10_000;
 pub struct TimeManager {
     unlimited: bool, // Whether we have unlimited time to make a move
     time_for_this_move: u64, // Amount of us that we have calculated
@@ -27,10 +24,15 @@ impl TimeManager {
         let mut unlimited = false;
         let mut hard_limit = false;
 
+        // Time to substract from the allocated time, to account for the lag
+        // between us finishing the search and the move reaching the clock.
+        // If it's more than the time we have, we just move as fast as we can.
+        let overhead = options.move_overhead_ms * 1000;
+
         if let Some(time) = options.time_for_move {
             // We are given a specific value *in millis* for the time we have to
             // make this move, use that value
-            time_for_this_move = time * 1000 - OFFSET;
+            time_for_this_move = (time * 1000).saturating_sub(overhead);
             hard_limit = true;
         } else if options.total_time_remaining.is_none() {
             // We are not given a time remaining, so we have
@@ -44,11 +46,16 @@ impl TimeManager {
             // divide that amount over the time remaining to know the
             // average time per move we have. Otherwise, assume that
             // the game will keep going on for 40 more moves.
-            let moves_remaining = options.moves_until_control.unwrap_or(40);
+            let moves_remaining = options.moves_until_control.unwrap_or(40).max(1);
 
             // Aim to make a move in 80% of that time, so that we have
             // some extra time later on if we need to allocate panic time.
-            time_for_this_move = total_remaining / moves_remaining * 4 / 5 - OFFSET;
+            // We also get to spend most of the increment, since it will be
+            // given back after the move, but never more than half of what
+            // is left in the clock, or we could flag with a small base time.
+            let increment = options.increment_ms.unwrap_or(0) * 1000;
+            let base_time = total_remaining / moves_remaining * 4 / 5 + increment * 3 / 4;
+            time_for_this_move = min(base_time, total_remaining / 2).saturating_sub(overhead);
         }
 
         Self { time_for_this_move, total_remaining, unlimited, hard_limit, start: Instant::now(), finished: false }
@@ -68,11 +75,16 @@ impl TimeManager {
     }
 
     pub fn update(&mut self) {
-        if !self.unlimited {
+        if !self.unlimited && !self.finished {
             self.finished = self.elapsed_micros() >= self.time_for_this_move;
         }
     }
 
+    // Ends the search regardless of the time remaining
+    pub fn stop(&mut self) {
+        self.finished = true;
+    }
+
     pub fn remaining_micros(&mut self) -> u64 {
         self.update();
 
@@ -81,10 +93,16 @@ impl TimeManager {
         } else if self.unlimited {
             u64::MAX
         } else {
-            self.time_for_this_move - self.elapsed_micros()
+            self.time_for_this_move.saturating_sub(self.elapsed_micros())
         }
     }
 
+    // Time that the search can spend on this move, in micros. It's
+    // meaningless if the time is unlimited.
+    pub fn allocated_micros(&self) -> u64 {
+        self.time_for_this_move
+    }
+
     pub fn elapsed_micros(&self) -> u64 {
         self.start.elapsed().as_micros() as u64
     }
@@ -1,16 +1,17 @@
-use std::mem::MaybeUninit;
-use shakmat_core::Move;
-use crate::evaluation::Evaluation;
+use std::cmp::max;
+use shakmat_core::{Move, PieceType::{self, *}};
+use crate::evaluation::{Evaluation, EvalScore};
 
 #[derive(Copy, Clone)]
 pub struct TTEntry {
     zobrist: u64,
-    data: MaybeUninit<TTData>,
+    data: TTData,
 }
 
 #[derive(Copy, Clone)]
 pub struct TTData {
     pub depth: u8,
+    pub generation: u8, // Search in which the entry was stored, set by the table
     pub eval: Evaluation,
     pub node_type: NodeType,
     pub best_move: Option<Move>
@@ -22,8 +23,13 @@ pub enum NodeType {
 }
 
 impl TTEntry {
-    pub fn new(zobrist: u64, depth: u8, eval: Evaluation, node_type: NodeType, best_move: Option<Move>) -> Self {
-        let data = MaybeUninit::new(TTData { depth, eval, node_type, best_move });
+    // Mate scores are relative to the root of the search, but the entry may be used
+    // in a different ply, or in another search. They are stored relative to the
+    // current node instead, using the ply in which it was found.
+    pub fn new(zobrist: u64, depth: u8, eval: Evaluation, node_type: NodeType, best_move: Option<Move>, current_depth: u8) -> Self {
+        let eval = shift_mate_score(eval, current_depth as EvalScore);
+
+        let data = TTData { depth, generation: 0, eval, node_type, best_move };
         Self { zobrist, data }
     }
 
@@ -31,17 +37,95 @@ impl TTEntry {
         self.zobrist
     }
 
-    pub fn data(&self) -> MaybeUninit<TTData> {
+    pub fn data(&self) -> TTData {
         self.data
     }
+
+    pub fn with_generation(mut self, generation: u8) -> Self {
+        self.data.generation = generation;
+        self
+    }
 }
 
 impl TTData {
-    pub fn eval_score(&self) -> Evaluation {
-        self.eval
+    // Makes mate scores relative to the root again, see TTEntry::new()
+    pub fn eval_score(&self, current_depth: u8) -> Evaluation {
+        shift_mate_score(self.eval, -(current_depth as EvalScore))
     }
 
     pub fn node_type(&self) -> NodeType {
         self.node_type
     }
-}
\ No newline at end of file
+
+    // The data is packed in a single u64 so that the table can store it atomically:
+    // the move in the lowest 16 bits, then the score, the depth, the generation
+    // and the node type.
+    pub fn pack(&self) -> u64 {
+        let node_type = match self.node_type {
+            NodeType::Exact => 0,
+            NodeType::Upperbound => 1,
+            NodeType::Lowerbound => 2,
+        };
+
+        pack_move(self.best_move) as u64
+            | (self.eval.score() as u16 as u64) << 16
+            | (self.depth as u64) << 32
+            | (self.generation as u64) << 40
+            | node_type << 48
+    }
+
+    pub fn unpack(packed: u64) -> Self {
+        let node_type = match (packed >> 48) & 3 {
+            0 => NodeType::Exact,
+            1 => NodeType::Upperbound,
+            _ => NodeType::Lowerbound,
+        };
+
+        Self {
+            best_move: unpack_move(packed as u16),
+            eval: Evaluation::new((packed >> 16) as u16 as EvalScore),
+            depth: (packed >> 32) as u8,
+            generation: (packed >> 40) as u8,
+            node_type,
+        }
+    }
+}
+
+// Moves take 16 bits: the origin and destination squares in the lowest 12,
+// and the kind of move in the upper 4. A kind of 0 means that there is no move.
+fn pack_move(mv: Option<Move>) -> u16 {
+    let (kind, from, to) = match mv {
+        None => (0, 0, 0),
+        Some(Move::Normal { from, to }) => (1, from, to),
+        Some(Move::ShortCastle) => (2, 0, 0),
+        Some(Move::LongCastle) => (3, 0, 0),
+        Some(Move::PawnPromotion { from, to, promote_to }) => (4 + promote_to.to_index() as u16, from, to),
+    };
+
+    kind << 12 | (from as u16) << 6 | to as u16
+}
+
+fn unpack_move(packed: u16) -> Option<Move> {
+    const PIECES: [PieceType; 6] = [Pawn, Knight, Bishop, Rook, Queen, King];
+    let (from, to) = ((packed >> 6) as u8 & 63, packed as u8 & 63);
+
+    match packed >> 12 {
+        0 => None,
+        1 => Some(Move::Normal { from, to }),
+        2 => Some(Move::ShortCastle),
+        3 => Some(Move::LongCastle),
+        kind => Some(Move::PawnPromotion { from, to, promote_to: PIECES[(kind as usize - 4).min(5)] }),
+    }
+}
+
+// Moves mate scores away from zero by a number of plies (or closer, if negative).
+// The bounds of the search window are in the mate range too, so it saturates.
+fn shift_mate_score(eval: Evaluation, plies: EvalScore) -> Evaluation {
+    if eval.is_positive_mate() {
+        Evaluation::new(eval.score().saturating_add(plies))
+    } else if eval.is_negative_mate() {
+        max(Evaluation::new(eval.score().saturating_sub(plies)), Evaluation::min_val())
+    } else {
+        eval
+    }
+}
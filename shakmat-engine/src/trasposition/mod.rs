@@ -2,4 +2,4 @@ mod entry;
 mod table;
 
 pub use entry::{TTData, TTEntry, NodeType};
-pub use table::TTable;
\ No newline at end of file
+pub use table::{TTable, MAX_TABLE_MB};
\ No newline at end of file
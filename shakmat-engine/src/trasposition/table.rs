@@ -1,51 +1,91 @@
-use std::mem::{MaybeUninit, self};
+use std::mem;
+use std::sync::atomic::{AtomicU8, AtomicU64, Ordering};
 use shakmat_core::Move;
 
 use super::{TTEntry, TTData, NodeType};
 
-// Operations with the trasposition table are unsafe, as it is intended for
-// lock-less multithreaded use, and data races will occur. It is up to us
-// to detect when they do, and act accordingly.
+// Largest size of the table in MB. Bigger sizes are rejected by the engine config.
+pub const MAX_TABLE_MB: usize = 4096;
+
+// The table is shared without locks between the threads of a Lazy SMP search.
+// Each slot holds the packed data and the zobrist key XORed with that data,
+// in two atomics. If another thread writes the slot while we read it, we may
+// get the key of one entry and the data of another, but then the XOR doesn't
+// give back the key that we are looking for and the entry is ignored.
 pub struct TTable {
     size: usize,
-    _content: Vec<MaybeUninit<TTEntry>>,
-    ptr: *mut MaybeUninit<TTEntry>
+    slots: Vec<Slot>,
+    generation: AtomicU8, // Increased every time a new search starts, wraps around
+}
+
+#[derive(Default)]
+struct Slot {
+    key: AtomicU64, // Zobrist key XOR packed data
+    data: AtomicU64,
 }
 
 impl TTable {
     pub fn new(size: usize) -> Self {
-        let mut vec = Vec::with_capacity(size);
-        unsafe {
-            vec.set_len(size);
-        }
-        Self { ptr: vec.as_mut_ptr(), _content: vec, size }
+        let slots = (0..size).map(|_| Slot::default()).collect();
+        Self { slots, size, generation: AtomicU8::new(0) }
+    }
+
+    // Creates the largest table with a power of 2 number of entries that fits
+    // in the given amount of megabytes. Each entry takes 16 bytes, so every
+    // MB holds 65536 entries. The bytes may not fit in a usize in 32 bit targets.
+    // The size must have been checked against MAX_TABLE_MB by the engine.
+    pub fn with_size_mb(megabytes: usize) -> Self {
+        debug_assert!(megabytes <= MAX_TABLE_MB);
+        let bytes = megabytes.saturating_mul(1024 * 1024);
+        let max_entries = (bytes / mem::size_of::<Slot>()).max(1);
+        // Largest power of two that is not above the maximum
+        let size = 1 << (usize::BITS - 1 - max_entries.leading_zeros());
+        Self::new(size)
     }
 
-    pub fn flush(&mut self) {
-        unsafe {
-            for entry in self._content.iter_mut() {
-                entry.write(mem::zeroed());
-            }
+    // Empties the table, so that no information from previous searches is used
+    pub fn clear(&mut self) {
+        for slot in self.slots.iter_mut() {
+            *slot = Slot::default();
         }
     }
 
+    // Must be called at the start of every search, so that the entries
+    // stored by previous searches are replaced before the current ones
+    pub fn new_search(&self) {
+        self.generation.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn generation(&self) -> u8 {
+        self.generation.load(Ordering::Relaxed)
+    }
+
+    // Estimates how full the table is, in entries per thousand, by looking at
+    // how many of the first slots hold entries from the current search
+    pub fn hashfull_permill(&self) -> u32 {
+        let sample_size = self.size.min(1000);
+        let generation = self.generation();
+        let used = self.slots[..sample_size].iter().filter(|slot| {
+            let (key, data) = slot.load();
+            key != 0 && TTData::unpack(data).generation == generation
+        }).count();
+
+        (used * 1000 / sample_size) as u32
+    }
+
     // Returns a data entry from the table, if all of the following are true:
     // - The entry exists, and the zobrist key matches
     // - The depth of the search that stored the entry is at least that of
     //   the search that is querying for the entry, to avoid using info from
     //   shallower depths
     pub fn get_entry(&self, zobrist_key: u64, depth: u8, tt_move: &mut Option<Move>) -> Option<TTData> {
-        let index = zobrist_key as usize % self.size;
-        let entry = unsafe {
-            (*self.ptr.add(index)).assume_init()
-        };
-
-        if entry.zobrist() != zobrist_key {
+        let (key, data) = self.slot(zobrist_key).load();
+        if key != zobrist_key {
             return None;
         }
 
         // The entry key matches, load the best move regardless of depth
-        let entry_data = unsafe { entry.data().assume_init() };
+        let entry_data = TTData::unpack(data);
         *tt_move = entry_data.best_move;
 
         // If the stored depth is higher, use the stored data
@@ -56,32 +96,47 @@ impl TTable {
         }
     }
 
-    // We only replace an entity if any of the following is true:
-    // - The zobrist key is different
+    // We only replace an entry if any of the following is true:
+    // - The slot is empty
+    // - The stored entry comes from a previous search
     // - The new depth is higher
-    // - The stored entry has a different flag and it's not exact
-    pub fn write_entry(&self, zobrist_key: u64, entry: TTEntry) {
-        let index = zobrist_key as usize % self.size;
-        let prev_entry = unsafe {
-            (*self.ptr.add(index)).assume_init()
-        };
-
-        if prev_entry.zobrist() != zobrist_key {
-            // The previous zobrist is different (or zero), overwrite the entry
-            unsafe {
-                *self.ptr.add(index) = MaybeUninit::new(entry);
-            }
-        } else {
-            // The previous zobrist is the same, check if the new entry is better
-            let prev_data = unsafe { prev_entry.data().assume_init() };
-            let new_data = unsafe { entry.data().assume_init() };
-
-            if new_data.depth > prev_data.depth || 
-               (new_data.node_type() != prev_data.node_type() && prev_data.node_type() != NodeType::Exact) {
-                    unsafe {
-                        *self.ptr.add(index) = MaybeUninit::new(entry);
-                    }
-               }
+    // - The new depth is the same, and the position is different
+    // - The position is the same, and the stored entry has a different flag and it's not exact
+    // If the stored entry is for the same position, comes from a previous search
+    // and is deeper, its data is kept and it's marked as part of the current search.
+    pub fn write_entry(&self, entry: TTEntry) {
+        let zobrist_key = entry.zobrist();
+        let slot = self.slot(zobrist_key);
+        let generation = self.generation();
+        let new_data = entry.with_generation(generation).data();
+        let (prev_key, prev_data) = slot.load();
+        let prev_data = TTData::unpack(prev_data);
+        let is_stale = prev_data.generation != generation;
+        let same_position = prev_key == zobrist_key;
+
+        if same_position && is_stale && prev_data.depth > new_data.depth {
+            slot.store(zobrist_key, TTData { generation, ..prev_data }.pack());
+        } else if prev_key == 0 || is_stale || new_data.depth > prev_data.depth ||
+            (new_data.depth == prev_data.depth && !same_position) ||
+            (same_position && new_data.node_type != prev_data.node_type && prev_data.node_type != NodeType::Exact) {
+            slot.store(zobrist_key, new_data.pack());
         }
     }
-}
\ No newline at end of file
+
+    fn slot(&self, zobrist_key: u64) -> &Slot {
+        &self.slots[zobrist_key as usize % self.size]
+    }
+}
+
+impl Slot {
+    // Returns the zobrist key of the stored entry, and its packed data
+    fn load(&self) -> (u64, u64) {
+        let data = self.data.load(Ordering::Relaxed);
+        (self.key.load(Ordering::Relaxed) ^ data, data)
+    }
+
+    fn store(&self, zobrist_key: u64, data: u64) {
+        self.key.store(zobrist_key ^ data, Ordering::Relaxed);
+        self.data.store(data, Ordering::Relaxed);
+    }
+}
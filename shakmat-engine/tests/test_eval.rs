@@ -0,0 +1,113 @@
+use shakmat_core::{Board, Color, DEFAULT_FEN};
+use shakmat_engine::{evaluate_material_only, evaluate_position, evaluate_position_verbose, init_evaluation, EvalTerm};
+
+#[test]
+fn breakdown_adds_up() {
+    init_evaluation();
+    let fens = [
+        DEFAULT_FEN,
+        "r1bq1rk1/pp2bppp/2n1pn2/3p4/2PP4/2N1PN2/PP3PPP/R2QKB1R w KQ - 0 9",
+        "8/2R2pk1/4p1p1/1p1nP3/1P3P2/r5P1/6K1/8 b - - 0 40",
+    ];
+
+    // The terms of the breakdown must account for the whole evaluation
+    for fen in fens {
+        let board = Board::from_fen(fen).unwrap();
+        let breakdown = evaluate_position_verbose(&board);
+        let (mg, eg) = breakdown.total();
+        let phase = breakdown.game_phase as i32;
+        let white_eval = (mg as i32 * (256 - phase) + eg as i32 * phase) / 256;
+        let sign = if board.turn_color() == Color::White { 1 } else { -1 };
+        assert_eq!(white_eval * sign, breakdown.evaluation.score() as i32, "{fen}");
+    }
+}
+
+#[test]
+fn breakdown_terms() {
+    init_evaluation();
+    let board = Board::from_fen("4k3/8/8/8/8/8/3PPP2/3QK3 w - - 0 1").unwrap();
+    let breakdown = evaluate_position_verbose(&board);
+
+    assert_eq!((1200, 1200), breakdown.score(Color::White, EvalTerm::Material));
+    assert_eq!((0, 0), breakdown.score(Color::Black, EvalTerm::Material));
+    assert!(breakdown.to_string().contains("King safety"));
+}
+
+#[test]
+fn threats() {
+    init_evaluation();
+    // The black knight is attacked by a pawn, and the black rook is hanging
+    let board = Board::from_fen("4k3/8/8/3n4/4P3/8/1r6/1RK5 w - - 0 1").unwrap();
+    let breakdown = evaluate_position_verbose(&board);
+
+    let (mg, eg) = breakdown.score(Color::White, EvalTerm::Threats);
+    assert!(mg > 0 && eg > 0);
+    assert_eq!((0, 0), breakdown.score(Color::Black, EvalTerm::Threats));
+}
+
+#[test]
+fn material_only_eval() {
+    init_evaluation();
+    let board = Board::from_fen("r1bq1rk1/pp2bppp/2n1pn2/3p4/2PP4/2N1PN2/PP3PPP/R2QKB1R b KQ - 0 9").unwrap();
+    let breakdown = evaluate_position_verbose(&board);
+
+    // Only the material, PSQT and tempo terms are taken into account
+    let (mg, eg) = [EvalTerm::Material, EvalTerm::Psqt, EvalTerm::Tempo].into_iter()
+        .map(|term| breakdown.term_total(term))
+        .fold((0, 0), |a, b| (a.0 + b.0, a.1 + b.1));
+    let phase = breakdown.game_phase as i32;
+    let white_eval = (mg as i32 * (256 - phase) + eg as i32 * phase) / 256;
+    assert_eq!(-white_eval, evaluate_material_only(&board).score() as i32);
+}
+
+#[test]
+fn fifty_move_scaling() {
+    init_evaluation();
+    let fresh = evaluate_position(&Board::from_fen("8/5k2/8/3R4/8/2K5/8/8 w - - 0 60").unwrap()).score();
+    let stale = evaluate_position(&Board::from_fen("8/5k2/8/3R4/8/2K5/8/8 w - - 80 60").unwrap()).score();
+    let limit = evaluate_position(&Board::from_fen("8/5k2/8/3R4/8/2K5/8/8 w - - 100 60").unwrap()).score();
+
+    // The advantage shrinks as the fifty move rule gets closer
+    assert!(fresh > stale && stale > limit && limit > 0);
+    assert_eq!(fresh / 2, limit);
+}
+
+#[test]
+fn opposite_colored_bishops() {
+    init_evaluation();
+    // A pawn up, with bishops of opposite colors (d3 and e7) or the same color (d3 and e8)
+    let opposite = evaluate_position_verbose(&Board::from_fen("8/4bk2/8/2p5/1pP5/1P1B4/P4K2/8 w - - 0 1").unwrap());
+    let same = evaluate_position_verbose(&Board::from_fen("4b3/5k2/8/2p5/1pP5/1P1B4/P4K2/8 w - - 0 1").unwrap());
+
+    let (mg, eg) = opposite.score(Color::White, EvalTerm::Scaling);
+    assert!(mg == 0 && eg < 0);
+    assert_eq!((0, 0), same.score(Color::White, EvalTerm::Scaling));
+    assert!(opposite.evaluation.score() > 0);
+}
+
+#[test]
+fn drawish_endings() {
+    init_evaluation();
+    let eval = |fen| evaluate_position_verbose(&Board::from_fen(fen).unwrap());
+    let scaled = |fen| eval(fen).score(Color::White, EvalTerm::Scaling) != (0, 0);
+
+    // The h8 corner is dark, so a light bishop can't drive the king out of it
+    let wrong_bishop = eval("7k/8/8/8/7P/8/4B3/6K1 w - - 0 1");
+    let right_bishop = eval("7k/8/8/8/7P/8/3B4/6K1 w - - 0 1");
+    assert!(wrong_bishop.evaluation.score().abs() < 30);
+    assert!(right_bishop.evaluation.score() > 200);
+
+    // It's not a draw if the king can't get to the corner
+    assert!(!scaled("8/8/8/8/k6P/8/4B3/6K1 w - - 0 1"));
+
+    // Two knights can't mate, and being a minor piece up isn't enough without pawns.
+    // Only the endgame score is scaled, so some of the middlegame one remains.
+    assert!(eval("8/8/4k3/8/8/2NN4/8/4K3 w - - 0 1").evaluation.score().abs() < 100);
+    assert!(scaled("8/8/4k3/8/8/2b5/8/R3K3 w - - 0 1"));
+    assert!(!scaled("8/8/4k3/8/8/8/8/R3K3 w - - 0 1"));
+
+    // A single extra pawn with the same pieces, and the same for black
+    assert!(scaled("8/8/4k3/8/4P3/2r5/8/R3K3 w - - 0 1"));
+    assert!(scaled("r3k3/8/2R5/4p3/8/4K3/8/8 b - - 0 1"));
+    assert!(!scaled("8/8/4k3/8/3PP3/2r5/8/R3K3 w - - 0 1"));
+}
@@ -0,0 +1,44 @@
+use shakmat_core::{Board, Color};
+use shakmat_engine::{evaluate_position_verbose, init_evaluation, set_eval_params, EvalParams, EvalTerm};
+
+// Kept in its own file, since changing the parameters
+// affects all the evaluations of the test process
+#[test]
+fn custom_params() {
+    init_evaluation();
+
+    // The parameters can be written and read back
+    let text = EvalParams::default().to_string();
+    assert!(text.starts_with("pawn_base_value 100\n"));
+    assert_eq!(Ok(EvalParams::default()), text.parse());
+
+    let params: EvalParams = "pawn_base_value 150\nbishop_pair_bonus 30 70".parse().unwrap();
+    assert_eq!(150, params.pawn_base_value);
+    assert_eq!((30, 70), params.bishop_pair_bonus);
+    assert_eq!(EvalParams::default().rook_base_value, params.rook_base_value);
+
+    assert!("unknown_param 5".parse::<EvalParams>().is_err());
+    assert!("bishop_pair_bonus 30".parse::<EvalParams>().is_err());
+
+    // And they are used by the evaluation
+    let board = Board::from_fen("4k3/8/8/8/8/8/3PPP2/4K3 w - - 0 1").unwrap();
+    set_eval_params(params.clone());
+    let material = evaluate_position_verbose(&board).score(Color::White, EvalTerm::Material);
+    set_eval_params(EvalParams::default());
+    assert_eq!((450, 450), material);
+
+    // Changing them while other threads evaluate positions is safe, and every
+    // evaluation sees either the old or the new parameters, never a mix
+    let reader = std::thread::spawn(move || {
+        (0..20_000).all(|_| {
+            let material = evaluate_position_verbose(&board).score(Color::White, EvalTerm::Material);
+            material == (300, 300) || material == (450, 450)
+        })
+    });
+
+    while !reader.is_finished() {
+        set_eval_params(params.clone());
+        set_eval_params(EvalParams::default());
+    }
+    assert!(reader.join().unwrap());
+}
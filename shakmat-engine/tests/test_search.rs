@@ -0,0 +1,641 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::Duration;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use shakmat_core::{Board, Move, PieceType, DEFAULT_FEN};
+use shakmat_engine::{evaluate_position, init_evaluation, is_draw_by_repetition, Accumulator, EngineConfig, Network, SearchOptions, ShakmatEngine, BENCH_FENS};
+
+fn search(fen: &str, max_depth: u8) -> shakmat_engine::SearchResult {
+    init_evaluation();
+    let engine = ShakmatEngine::new(EngineConfig { use_opening_book: false, ..Default::default() }).unwrap();
+    let options = SearchOptions { max_depth: Some(max_depth), ..Default::default() };
+    engine.find_best_move(&Board::from_fen(fen).unwrap(), &[], options)
+}
+
+#[test]
+fn principal_variation() {
+    let fen = "r1bqkb1r/pppp1ppp/2n2n2/4p2Q/2B1P3/8/PPPP1PPP/RNB1K1NR w KQkq - 4 4";
+    let result = search(fen, 4);
+
+    // Scholar's mate, the PV must start with the best move and be playable
+    assert_eq!("h5f7", result.best_move.unwrap().to_string());
+    assert_eq!(result.pv.first(), result.best_move.as_ref());
+
+    let mut board = Board::from_fen(fen).unwrap();
+    for mv in &result.pv {
+        assert!(board.is_legal_move(mv));
+        board = board.make_move(mv);
+    }
+}
+
+#[test]
+fn search_statistics() {
+    let result = search("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3", 4);
+    assert_eq!(4, result.depth_reached);
+    assert!(result.nodes > 0);
+    assert!(!result.from_book);
+}
+
+#[test]
+fn multipv() {
+    init_evaluation();
+    let engine = ShakmatEngine::new(EngineConfig { use_opening_book: false, ..Default::default() }).unwrap();
+    let board = Board::from_fen("4k3/8/8/8/8/8/1r6/K6R w - - 0 1").unwrap();
+    let options = SearchOptions { max_depth: Some(4), multipv: 3, ..Default::default() };
+    let lines = engine.find_best_moves(&board, &[], options);
+
+    assert_eq!(3, lines.len());
+    assert!(lines.windows(2).all(|pair| pair[0].score >= pair[1].score));
+
+    // All lines start with a different move, and taking the rook is the best one
+    assert_eq!("a1b2", lines[0].best_move.unwrap().to_string());
+    assert_ne!(lines[0].best_move, lines[1].best_move);
+    assert_ne!(lines[1].best_move, lines[2].best_move);
+    assert_ne!(lines[0].best_move, lines[2].best_move);
+}
+
+#[test]
+fn multithreaded_search() {
+    init_evaluation();
+    let engine = ShakmatEngine::new(EngineConfig { use_opening_book: false, threads: 3, ..Default::default() }).unwrap();
+    let board = Board::from_fen("r5rk/5p1p/5R2/4B3/8/8/7P/7K w - - 0 1").unwrap();
+    let options = SearchOptions { max_depth: Some(6), ..Default::default() };
+    let result = engine.find_best_move(&board, &[], options.clone());
+    let single = ShakmatEngine::new(EngineConfig { use_opening_book: false, ..Default::default() }).unwrap().find_best_move(&board, &[], options);
+
+    // The threads may pick different moves in equal positions, but all of
+    // them must agree on a forced mate: 1. Ra6 f6 2. Bxf6+ Rg7 3. Rxa8#
+    assert!(result.score.is_positive_mate());
+    assert_eq!(single.score, result.score);
+    assert!(board.is_legal_move(&result.best_move.unwrap()));
+}
+
+#[test]
+fn stop_flag() {
+    init_evaluation();
+    let engine = ShakmatEngine::new(EngineConfig { use_opening_book: false, ..Default::default() }).unwrap();
+    let board = Board::from_fen("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3").unwrap();
+
+    // Search without any limits, and stop it from another thread
+    let stop = Arc::new(AtomicBool::new(false));
+    let options = SearchOptions { max_depth: None, stop: Some(stop.clone()), ..Default::default() };
+    let stopper = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(300));
+        stop.store(true, Ordering::Relaxed);
+    });
+
+    let result = engine.find_best_move(&board, &[], options);
+    stopper.join().unwrap();
+    assert!(result.best_move.is_some());
+    assert!(result.elapsed_ms < 5000);
+}
+
+#[test]
+fn node_limit() {
+    init_evaluation();
+    let engine = ShakmatEngine::new(EngineConfig { use_opening_book: false, ..Default::default() }).unwrap();
+    let board = Board::from_fen("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3").unwrap();
+    let options = SearchOptions { max_depth: None, max_nodes: Some(50_000), ..Default::default() };
+    let result = engine.find_best_move(&board, &[], options);
+
+    // The limit is checked every 4096 nodes
+    assert!(result.best_move.is_some());
+    assert!(result.nodes < 50_000 + 4096);
+
+    // With several threads, it's for all of them together
+    let engine = ShakmatEngine::new(EngineConfig { use_opening_book: false, threads: 3, ..Default::default() }).unwrap();
+    let options = SearchOptions { max_depth: None, max_nodes: Some(50_000), ..Default::default() };
+    let result = engine.find_best_move(&board, &[], options);
+    assert!(result.best_move.is_some());
+    assert!(result.nodes < 50_000 + 3 * 4096);
+}
+
+#[test]
+fn mate_search() {
+    init_evaluation();
+    let engine = ShakmatEngine::new(EngineConfig { use_opening_book: false, ..Default::default() }).unwrap();
+    let board = Board::from_fen("r2qkb1r/pp2nppp/3p4/2pNN1B1/2BnP3/3P4/PPP2PPP/R2bK2R w KQkq - 1 10").unwrap();
+    let mate_in = |moves| engine.find_best_move(&board, &[], SearchOptions { mate_in: Some(moves), ..Default::default() });
+
+    // Legal's mate: 1. Nf6+ gxf6 2. Bxf7#
+    let result = mate_in(2);
+    assert_eq!("d5f6", result.best_move.unwrap().to_string());
+    assert!(result.score.is_positive_mate());
+    assert_eq!(format!("{:?}", result.score), format!("Evaluation {{ score: {}, mate: {:?} }}", result.score.score(), result.score.moves_to_mate()));
+
+    let result = mate_in(1);
+    assert!(result.best_move.is_none());
+    assert!(!result.score.is_mate());
+}
+
+#[test]
+fn mate_search_keeps_table() {
+    // The mate search scores the lines after 1. Nc7+ as 0, since there is no
+    // mate, which mustn't be used later by normal searches of the same position
+    init_evaluation();
+    let board = Board::from_fen("q3k3/3p4/8/1N6/8/8/PPP5/4K3 w - - 0 1").unwrap();
+    let options = SearchOptions { max_depth: Some(5), ..Default::default() };
+    let fresh = ShakmatEngine::new(EngineConfig { use_opening_book: false, ..Default::default() }).unwrap().find_best_move(&board, &[], options.clone());
+
+    let engine = ShakmatEngine::new(EngineConfig { use_opening_book: false, ..Default::default() }).unwrap();
+    for moves in [1, 2, 3] {
+        engine.find_best_move(&board, &[], SearchOptions { mate_in: Some(moves), ..Default::default() });
+    }
+    let result = engine.find_best_move(&board, &[], options);
+
+    assert_eq!(fresh.score, result.score);
+    assert_eq!(fresh.best_move, result.best_move);
+}
+
+#[test]
+fn iteration_info() {
+    init_evaluation();
+    let engine = ShakmatEngine::new(EngineConfig { use_opening_book: false, ..Default::default() }).unwrap();
+    let board = Board::from_fen("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3").unwrap();
+    let options = SearchOptions { max_depth: Some(5), ..Default::default() };
+
+    let mut depths = vec![];
+    let result = engine.find_best_move_with_info(&board, &[], options, |info| {
+        assert!(!info.pv.is_empty());
+        depths.push(info.depth);
+    });
+
+    assert_eq!(vec![1, 2, 3, 4, 5], depths);
+    assert_eq!(5, result.depth_reached);
+}
+
+#[test]
+fn new_game_clears_table() {
+    init_evaluation();
+    let mut engine = ShakmatEngine::new(EngineConfig { use_opening_book: false, hash_mb: 1, ..Default::default() }).unwrap();
+    let board = Board::from_fen("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3").unwrap();
+    let options = SearchOptions { max_depth: Some(5), ..Default::default() };
+
+    let mut hashfull = 0;
+    let first = engine.find_best_move_with_info(&board, &[], options.clone(), |info| hashfull = info.hashfull);
+    assert!(hashfull > 0);
+
+    // Searching again from a clear table must give the exact same results
+    engine.new_game();
+    let second = engine.find_best_move(&board, &[], options);
+    assert_eq!(first.nodes, second.nodes);
+    assert_eq!(first.best_move, second.best_move);
+}
+
+#[test]
+fn increment_does_not_flag() {
+    init_evaluation();
+    let engine = ShakmatEngine::new(EngineConfig { use_opening_book: false, ..Default::default() }).unwrap();
+    let board = Board::from_fen("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3").unwrap();
+
+    // A large increment must not make us spend more than what's left in the clock
+    let options = SearchOptions { total_time_remaining: Some(400), increment_ms: Some(5000), max_depth: None, ..Default::default() };
+    let result = engine.find_best_move(&board, &[], options);
+    assert!(result.best_move.is_some());
+    assert!(result.elapsed_ms < 400);
+}
+
+#[test]
+fn overhead_larger_than_time() {
+    init_evaluation();
+    let engine = ShakmatEngine::new(EngineConfig { use_opening_book: false, ..Default::default() }).unwrap();
+    let options = SearchOptions { time_for_move: Some(5), move_overhead_ms: 50, max_depth: None, ..Default::default() };
+    let result = engine.find_best_move(&Board::default(), &[], options);
+    assert!(result.best_move.is_some());
+}
+
+// Writes a polyglot book with the provided (position, move, weight) entries
+fn write_book(name: &str, entries: &[(u64, u16, u16)]) -> PathBuf {
+    let bytes: Vec<u8> = entries.iter().flat_map(|(key, mv, weight)| {
+        [&key.to_be_bytes()[..], &mv.to_be_bytes(), &weight.to_be_bytes(), &[0; 4]].concat()
+    }).collect();
+
+    let path = std::env::temp_dir().join(name);
+    std::fs::write(&path, bytes).unwrap();
+    path
+}
+
+fn book_move(book: &str, board: &Board) -> shakmat_engine::SearchResult {
+    let config = EngineConfig { book_path: Some(book.to_owned()), ..Default::default() };
+    ShakmatEngine::new(config).unwrap().find_best_move(board, &[], SearchOptions { max_depth: Some(1), ..Default::default() })
+}
+
+#[test]
+fn book_from_file() {
+    // A book with a single entry, Nh3 in the starting position
+    let board = Board::default();
+    let path = write_book("shakmat_test_book.bin", &[(board.zobrist_key(), 407, 1)]); // g1h3
+    let result = book_move(path.to_str().unwrap(), &board);
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!("g1h3", result.best_move.unwrap().to_string());
+    assert_eq!(0, result.nodes);
+    assert!(result.from_book);
+}
+
+#[test]
+fn book_file_errors() {
+    let config = EngineConfig { book_path: Some("/nonexistent/book.bin".to_owned()), ..Default::default() };
+    assert!(ShakmatEngine::new(config).is_err());
+
+    // A truncated book, one byte short of a full entry
+    let path = std::env::temp_dir().join("shakmat_test_truncated_book.bin");
+    std::fs::write(&path, [0; 15]).unwrap();
+    let config = EngineConfig { book_path: Some(path.to_str().unwrap().to_owned()), ..Default::default() };
+    let mut engine = ShakmatEngine::new(EngineConfig::default()).unwrap();
+    let error = engine.update_config(config).err();
+    std::fs::remove_file(&path).unwrap();
+
+    assert!(error.is_some_and(|msg| msg.contains("not a multiple of 16")));
+    assert!(engine.find_best_move(&Board::default(), &[], SearchOptions::default()).from_book);
+}
+
+#[test]
+fn book_illegal_castle() {
+    // The book suggests castling, but there are no castling rights
+    let board = Board::from_fen("4k3/8/8/8/8/8/8/4K2R w - - 0 1").unwrap();
+    let path = write_book("shakmat_test_castle_book.bin", &[(board.zobrist_key(), 262, 10), (board.zobrist_key(), 261, 1)]); // e1g1, e1f1
+    let result = book_move(path.to_str().unwrap(), &board);
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!("e1f1", result.best_move.unwrap().to_string());
+    assert_eq!(0, result.nodes);
+}
+
+#[test]
+fn book_from_pgn() {
+    let pgn = "1. e4 e5 2. Nf3 Nc6 *\n\n1. e4 c5 2. Nf3 d6 *\n\n1. d4 d5 *";
+    let book = shakmat_engine::create_book(pgn, 2).unwrap();
+    assert_eq!(5 * 16, book.len()); // e4, d4, e5, c5, d5
+
+    let path = std::env::temp_dir().join("shakmat_test_pgn_book.bin");
+    std::fs::write(&path, book).unwrap();
+    let result = book_move(path.to_str().unwrap(), &Board::default());
+    std::fs::remove_file(&path).unwrap();
+
+    // 1. e4 was played more often than 1. d4
+    assert_eq!("e2e4", result.best_move.unwrap().to_string());
+}
+
+#[test]
+fn bundled_book_hits() {
+    // The zobrist keys must match the polyglot ones (see test_zobrist.rs in
+    // the core crate), otherwise the book lookups would never succeed
+    let engine = ShakmatEngine::default();
+    let options = SearchOptions { max_depth: Some(1), ..Default::default() };
+    for fen in [DEFAULT_FEN, "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1"] {
+        let result = engine.find_best_move(&Board::from_fen(fen).unwrap(), &[], options.clone());
+        assert!(result.best_move.is_some());
+        assert_eq!(0, result.nodes);
+    }
+}
+
+#[test]
+fn book_options_per_search() {
+    let board = Board::default();
+    let path = write_book("shakmat_test_weights_book.bin", &[(board.zobrist_key(), 796, 10), (board.zobrist_key(), 407, 1)]); // e2e4, g1h3
+    let config = EngineConfig { book_path: Some(path.to_str().unwrap().to_owned()), only_best_book_moves: false, ..Default::default() };
+    let engine = ShakmatEngine::new(config).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    // Only e2e4 is weighted enough, even though the moves are chosen randomly
+    let options = SearchOptions { max_depth: Some(1), min_book_weight: 5, ..Default::default() };
+    for _ in 0..20 {
+        assert_eq!("e2e4", engine.find_best_move(&board, &[], options.clone()).best_move.unwrap().to_string());
+    }
+
+    // No move is weighted enough, a regular search happens
+    let options = SearchOptions { max_depth: Some(1), min_book_weight: 20, only_best_book_moves: Some(true), ..Default::default() };
+    assert!(engine.find_best_move(&board, &[], options).nodes > 0);
+}
+
+#[test]
+fn zugzwang() {
+    // Kh6 puts black in zugzwang, which null move pruning would miss
+    // if the search could pass the turn in this position
+    let result = search("1q1k4/2Rr4/8/2Q3K1/8/8/8/8 w - - 0 1", 8);
+    assert_eq!("g5h6", result.best_move.unwrap().to_string());
+}
+
+#[test]
+fn tactical_suite() {
+    // Positions from Win At Chess, along with their solutions
+    let positions = [
+        ("2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - - 0 1", "g3g6"),
+        ("5rk1/1ppb3p/p1pb4/6q1/3P1p1r/2P1R2P/PP1BQ1P1/5RKN w - - 0 1", "e3g3"),
+        ("r1bq2rk/pp3pbp/2p1p1pQ/7P/3P4/2PB1N2/PP3PPR/2KR4 w - - 0 1", "h6h7"),
+        ("5k2/6pp/p1qN4/1p1p4/3P4/2PKP2Q/PP3r2/3R4 b - - 0 1", "c6c4"),
+        ("rnbqkb1r/pppp1ppp/8/4P3/6n1/7P/PPPNPPP1/R1BQKBNR b KQkq - 0 1", "g4e3"),
+        ("r4q1k/p2bR1rp/2p2Q1N/5p2/5p2/2P5/PP3PPP/R5K1 w - - 0 1", "e7f7"),
+        ("3q1rk1/p4pp1/2pb3p/3p4/6Pr/1PNQ4/P1PB1PP1/4RRK1 b - - 0 1", "d6h2"),
+        ("2br2k1/2q3rn/p2NppQ1/2p1P3/Pp5R/4P3/1P3PPP/3R2K1 w - - 0 1", "h4h7"),
+    ];
+
+    init_evaluation();
+    // Razoring prunes part of the tree without changing the solutions. It can delay
+    // finding a quiet mate, after which the search stops, so only the positions
+    // that are searched up to the maximum depth are compared.
+    let (mut nodes, mut nodes_without_razoring) = (0, 0);
+    for (fen, solution) in positions {
+        let board = Board::from_fen(fen).unwrap();
+        let results = [true, false].map(|razoring| {
+            let engine = ShakmatEngine::new(EngineConfig { use_opening_book: false, ..Default::default() }).unwrap();
+            let options = SearchOptions { max_depth: Some(6), razoring, ..Default::default() };
+            engine.find_best_move(&board, &[], options)
+        });
+
+        for result in &results {
+            assert_eq!(solution, result.best_move.unwrap().to_string(), "{fen}");
+        }
+
+        if results.iter().all(|result| result.depth_reached == 6) {
+            nodes += results[0].nodes;
+            nodes_without_razoring += results[1].nodes;
+        }
+    }
+
+    assert!(nodes < nodes_without_razoring, "{nodes} {nodes_without_razoring}");
+}
+
+#[test]
+fn aspiration_widening() {
+    init_evaluation();
+    let board = Board::from_fen("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3").unwrap();
+    let nodes = |aspiration_widening| {
+        let engine = ShakmatEngine::new(EngineConfig { use_opening_book: false, ..Default::default() }).unwrap();
+        let options = SearchOptions { max_depth: Some(7), aspiration_widening, ..Default::default() };
+        engine.find_best_move(&board, &[], options).nodes
+    };
+
+    // The score swings by almost a pawn between iterations, widening the bound
+    // that failed step by step is cheaper than re-searching with the full window
+    let (widening, full_window) = (nodes(true), nodes(false));
+    assert!(widening < full_window, "{widening} {full_window}");
+}
+
+#[test]
+fn quiescence_eval() {
+    init_evaluation();
+    let engine = ShakmatEngine::new(EngineConfig { use_opening_book: false, ..Default::default() }).unwrap();
+
+    // The rook is hanging, so the static evaluation is misleading
+    let board = Board::from_fen("4k3/8/8/3r4/4P3/8/8/4K3 w - - 0 1").unwrap();
+    assert!(evaluate_position(&board).score() < 0);
+    assert!(engine.quiescence_eval(&board, &[]).score() > 0);
+
+    // Without captures, it's just the static evaluation
+    let board = Board::from_fen(DEFAULT_FEN).unwrap();
+    assert_eq!(engine.quiescence_eval(&board, &[]).score(), evaluate_position(&board).score());
+}
+
+#[test]
+fn mate_distance_with_tt_hits() {
+    init_evaluation();
+    let board = Board::from_fen("r5rk/5p1p/5R2/4B3/8/8/7P/7K w - - 0 1").unwrap();
+    let options = SearchOptions { max_depth: Some(8), ..Default::default() };
+
+    // Mate in 3: 1. Ra6 f6 2. Bxf6+ Rg7 3. Rxa8#
+    let engine = ShakmatEngine::new(EngineConfig { use_opening_book: false, ..Default::default() }).unwrap();
+    let result = engine.find_best_move(&board, &[], options.clone());
+    assert_eq!(result.pv.len(), 5);
+
+    // Searching a position of the mating line first fills the trasposition table
+    // with mates found closer to the root, which mustn't change the distance
+    let engine = ShakmatEngine::new(EngineConfig { use_opening_book: false, ..Default::default() }).unwrap();
+    let child = board.make_move(&result.pv[0]).make_move(&result.pv[1]);
+    let child_result = engine.find_best_move(&child, &[], options.clone());
+    let root_result = engine.find_best_move(&board, &[], options);
+
+    assert_eq!(child_result.score.moves_to_mate(), result.score.moves_to_mate().map(|moves| moves - 1));
+    assert_eq!(root_result.score.moves_to_mate(), result.score.moves_to_mate());
+}
+
+#[test]
+fn bench_is_deterministic() {
+    init_evaluation();
+    for fen in BENCH_FENS {
+        assert!(Board::from_fen_strict(fen).is_ok(), "{fen}");
+    }
+
+    // The TT is cleared before every position, so previous searches don't matter
+    let mut engine = ShakmatEngine::default();
+    let first = engine.bench(3);
+    let second = engine.bench(3);
+    assert!(first.nodes > 0);
+    assert_eq!(first.nodes, second.nodes);
+}
+
+#[test]
+fn perpetual_check() {
+    // Black is two queens up, but Qf6+ Kg8 Qg5+ Kh8 repeats forever. Quiescence
+    // never needs to look for repetitions, since captures and promotions can't be
+    // undone, so it's up to the main search to score the perpetual as a draw.
+    let result = search("5r1k/5p1p/8/6Q1/1q6/q7/6PP/7K w - - 0 1", 8);
+    assert_eq!("g5f6", result.best_move.unwrap().to_string());
+    assert_eq!(0, result.score.score());
+}
+
+#[test]
+fn contempt_from_root_side() {
+    // Same perpetual as above, but starting with the queen on h4, so the first
+    // repetition (after Qf6+ Kg8 Qg5+ Kh8 Qf6+) has the opponent to move. The
+    // draw must still be worth less than an equal position for the root side.
+    init_evaluation();
+    let options = SearchOptions { max_depth: Some(8), ..Default::default() };
+
+    for (fen, perpetual) in [("5r1k/5p1p/8/8/1q5Q/q7/6PP/7K w - - 0 1", "h4f6"), ("7k/6pp/Q7/1Q5q/8/8/5P1P/5R1K b - - 0 1", "h5f3")] {
+        let engine = ShakmatEngine::new(EngineConfig { use_opening_book: false, contempt: 50, ..Default::default() }).unwrap();
+        let result = engine.find_best_move(&Board::from_fen(fen).unwrap(), &[], options.clone());
+        assert_eq!(perpetual, result.best_move.unwrap().to_string());
+        assert_eq!(-50, result.score.score());
+    }
+}
+
+#[test]
+fn threefold_repetition_from_fen() {
+    // The history doesn't start at the first move of the game
+    let mut board = Board::from_fen("4k3/8/8/8/8/8/8/4K2R w - - 10 40").unwrap();
+    let mut history = vec![board.zobrist_key()];
+    for mv in ["h1h2", "e8d8", "h2h1", "d8e8", "h1h2", "e8d8", "h2h1", "d8e8"] {
+        assert!(!is_draw_by_repetition(&board, 0, &history));
+        board = board.make_move(&Move::from_notation(mv).unwrap());
+        history.push(board.zobrist_key());
+    }
+    assert!(is_draw_by_repetition(&board, 0, &history));
+
+    // The current position may be left out of the history too
+    history.pop();
+    assert!(is_draw_by_repetition(&board, 0, &history));
+}
+
+#[test]
+fn qsearch_ply_limit() {
+    init_evaluation();
+    let board = Board::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1").unwrap();
+    let nodes = |max_qsearch_ply| {
+        let engine = ShakmatEngine::new(EngineConfig { use_opening_book: false, ..Default::default() }).unwrap();
+        let options = SearchOptions { max_depth: Some(4), max_qsearch_ply, ..Default::default() };
+        engine.find_best_move(&board, &[], options).nodes
+    };
+
+    // Without captures at the leaves, the search is just a fixed depth one
+    assert!(nodes(Some(0)) < nodes(None));
+}
+
+#[test]
+fn config_builder() {
+    let config = EngineConfig::builder().hash_mb(1).threads(2).use_opening_book(false).build();
+    assert_eq!(1, config.hash_mb);
+    assert_eq!(2, config.threads);
+    assert!(!config.use_opening_book);
+
+    // Everything else keeps its default value
+    let default = EngineConfig::default();
+    assert_eq!(default.only_best_book_moves, config.only_best_book_moves);
+    assert_eq!(default.contempt, config.contempt);
+    assert!(config.book_path.is_none() && config.nnue_path.is_none() && config.syzygy_path.is_none());
+
+    let result = ShakmatEngine::new(config).unwrap().find_best_move(&Board::default(), &[], SearchOptions { max_depth: Some(3), ..Default::default() });
+    assert!(result.best_move.is_some());
+}
+
+#[test]
+fn hash_size_limits() {
+    for hash_mb in [0, 4097, usize::MAX] {
+        let config = EngineConfig::builder().hash_mb(hash_mb).build();
+        assert!(ShakmatEngine::new(config).is_err());
+    }
+
+    let mut engine = ShakmatEngine::new(EngineConfig::builder().hash_mb(1).build()).unwrap();
+    assert!(engine.update_config(EngineConfig::builder().hash_mb(usize::MAX).build()).is_err());
+    assert!(engine.update_config(EngineConfig::builder().hash_mb(2).build()).is_ok());
+}
+
+#[test]
+fn table_persists_between_moves() {
+    init_evaluation();
+    let board = Board::from_fen("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3").unwrap();
+    let options = SearchOptions { max_depth: Some(7), ..Default::default() };
+
+    // The reply to the best move was already searched, so the next search is cheaper
+    let engine = ShakmatEngine::new(EngineConfig { use_opening_book: false, ..Default::default() }).unwrap();
+    let result = engine.find_best_move(&board, &[], options.clone());
+    let next_board = board.make_move(&result.pv[0]).make_move(&result.pv[1]);
+    let warm_nodes = engine.find_best_move(&next_board, &[], options.clone()).nodes;
+
+    let fresh_engine = ShakmatEngine::new(EngineConfig { use_opening_book: false, ..Default::default() }).unwrap();
+    let cold_nodes = fresh_engine.find_best_move(&next_board, &[], options).nodes;
+    assert!(warm_nodes < cold_nodes);
+}
+
+#[test]
+fn book_moves() {
+    // The book is available even if the engine doesn't play from it
+    let engine = ShakmatEngine::new(EngineConfig { use_opening_book: false, ..Default::default() }).unwrap();
+    let board = Board::default();
+    let moves = engine.book_moves(&board);
+
+    assert!(!moves.is_empty());
+    assert!(moves.windows(2).all(|pair| pair[0].1 >= pair[1].1));
+    assert!(moves.iter().all(|(mv, _)| board.is_legal_move(mv)));
+
+    let board = Board::from_fen("4k3/8/8/8/8/8/1r6/K6R w - - 0 1").unwrap();
+    assert!(engine.book_moves(&board).is_empty());
+}
+
+#[test]
+fn analyze_ignores_book() {
+    init_evaluation();
+    let engine = ShakmatEngine::new(EngineConfig::default()).unwrap();
+    let board = Board::default();
+    let options = SearchOptions { max_depth: Some(3), ..Default::default() };
+
+    assert!(engine.find_best_move(&board, &[], options.clone()).from_book);
+
+    let result = engine.analyze(&board, &[], options);
+    assert!(!result.from_book);
+    assert_eq!(3, result.depth_reached);
+    assert!(result.nodes > 0 && result.pv.len() >= 3);
+}
+
+#[test]
+fn nnue_network_errors() {
+    init_evaluation();
+    let config = EngineConfig { nnue_path: Some("/nonexistent/shakmat.nnue".to_owned()), ..Default::default() };
+    assert!(ShakmatEngine::new(config).is_err());
+
+    // The config is left untouched if the new network can't be loaded
+    let mut engine = ShakmatEngine::new(EngineConfig::default()).unwrap();
+    let config = EngineConfig { nnue_path: Some("/nonexistent/shakmat.nnue".to_owned()), ..Default::default() };
+    assert!(engine.update_config(config).is_err());
+    assert!(engine.update_config(EngineConfig::default()).is_ok());
+}
+
+#[test]
+fn nnue_weights_overflow() {
+    // Every weight is so large that the accumulators overflow with a few pieces,
+    // which must not crash the search
+    init_evaluation();
+    let values = 768 * 256 + 256 + 2 * 256 + 1;
+    let path = std::env::temp_dir().join("shakmat_test_overflow.nnue");
+    std::fs::write(&path, 0x7000i16.to_le_bytes().repeat(values)).unwrap();
+
+    let config = EngineConfig { use_opening_book: false, nnue_path: Some(path.to_str().unwrap().to_owned()), ..Default::default() };
+    let engine = ShakmatEngine::new(config).unwrap();
+    let result = engine.find_best_move(&Board::default(), &[], SearchOptions { max_depth: Some(3), ..Default::default() });
+    std::fs::remove_file(&path).unwrap();
+    assert!(result.best_move.is_some());
+}
+
+#[test]
+fn nnue_incremental_updates() {
+    // Random legal games from positions with castling, en passant and promotions.
+    // After every move, updating the accumulator must give the same one as
+    // computing it from scratch.
+    let mut rng = StdRng::seed_from_u64(1760);
+    let values = 768 * 256 + 256 + 2 * 256 + 1;
+    let weights: Vec<u8> = (0..values).flat_map(|_| rng.gen_range(-300i16..300).to_le_bytes()).collect();
+    let path = std::env::temp_dir().join("shakmat_test_incremental.nnue");
+    std::fs::write(&path, weights).unwrap();
+    let net = Network::load(path.to_str().unwrap()).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    let fens = [
+        DEFAULT_FEN,
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        "r3k2r/1P4P1/8/2pP4/4p3/8/1p4p1/R3K2R w KQkq c6 0 1",
+    ];
+    let (mut castles, mut en_passants, mut promotions) = (0, 0, 0);
+
+    for fen in fens {
+        for _ in 0..20 {
+            let mut board = Board::from_fen(fen).unwrap();
+            let mut acc = Accumulator::new(&net, &board);
+
+            for _ in 0..100 {
+                let moves = board.legal_moves();
+                if moves.is_empty() {
+                    break;
+                }
+
+                let mv = moves[rng.gen_range(0..moves.len())];
+                match mv {
+                    Move::ShortCastle | Move::LongCastle => castles += 1,
+                    Move::PawnPromotion { .. } => promotions += 1,
+                    Move::Normal { from, to } if mv.piece_moving(&board) == PieceType::Pawn
+                        && from % 8 != to % 8 && board.piece_on(to).is_none() => en_passants += 1,
+                    _ => {},
+                }
+
+                acc = acc.make_move(&net, &board, &mv);
+                board = board.make_move(&mv);
+                assert_eq!(acc, Accumulator::new(&net, &board), "{} after {mv}", board.fen());
+                assert_eq!(acc.evaluate(&net, board.turn_color()), Accumulator::new(&net, &board).evaluate(&net, board.turn_color()));
+            }
+        }
+    }
+
+    assert!(castles > 0 && en_passants > 0 && promotions > 0);
+}
@@ -0,0 +1,35 @@
+use shakmat_core::{Board, Move};
+use shakmat_engine::see;
+
+fn run_test(fen: &str, mv: &str) -> i16 {
+    let board = Board::from_fen(fen).unwrap();
+    see(&board, &Move::from_notation(mv).unwrap())
+}
+
+#[test]
+fn winning_captures() {
+    // Undefended pawn
+    assert_eq!(100, run_test("1k1r4/1pp4p/p7/4p3/8/P5P1/1PP4P/2K1R3 w - - 0 1", "e1e5"));
+    // Defended rook captured by a pawn
+    assert_eq!(500, run_test("4k3/8/3r4/2P1p3/8/8/8/4K3 w - - 0 1", "c5d6"));
+    // Rook takes a rook defended by a rook, with an x-ray attacker behind it
+    assert_eq!(500, run_test("3r3k/3r4/8/8/8/8/3R4/3RK3 w - - 0 1", "d2d7"));
+    // En passant
+    assert_eq!(100, run_test("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1", "e5d6"));
+}
+
+#[test]
+fn losing_captures() {
+    // Queen takes a pawn defended by another pawn
+    assert_eq!(-800, run_test("4k3/8/3p4/4p3/8/8/8/4QK2 w - - 0 1", "e1e5"));
+    // Knight takes a pawn, the exchange sequence ends up losing the knight
+    assert!(run_test("1k1r3q/1ppn3p/p4b2/4p3/8/P2N2P1/1PP1R1BP/2K1Q3 w - - 0 1", "d3e5") < 0);
+}
+
+#[test]
+fn even_exchanges() {
+    // Pawn trade
+    assert_eq!(0, run_test("4k3/8/3p4/4p3/3P4/8/8/4K3 w - - 0 1", "d4e5"));
+    // Rook trade where the king recaptures at the end
+    assert_eq!(0, run_test("3rk3/3r4/8/8/8/8/3R4/3RK3 w - - 0 1", "d2d7"));
+}
@@ -0,0 +1,137 @@
+use shakmat_core::Board;
+use shakmat_engine::{init_evaluation, EngineConfig, SearchOptions, ShakmatEngine, Tablebase, Wdl};
+
+// Tables for some of the 3 and 4 piece endgames
+const SYZYGY_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../etc/syzygy");
+
+// Positions with their WDL and DTZ values, from the test suite of shakmaty-syzygy
+const TB_POSITIONS: [(&str, Wdl, i32); 20] = [
+    ("8/4r3/8/K7/4R3/8/8/5k2 w - -", Wdl::Win, 1),
+    ("8/8/R2k4/8/8/K7/8/4r3 b - -", Wdl::Draw, 0),
+    ("8/8/8/8/8/1K2B3/b6k/8 w - -", Wdl::Draw, 0),
+    ("8/8/K4k2/7q/8/8/7Q/8 b - -", Wdl::Win, 1),
+    ("8/8/K7/5k2/3R4/8/7b/8 w - -", Wdl::Draw, 0),
+    ("8/8/8/8/7r/6q1/2K5/6k1 w - -", Wdl::Loss, -4),
+    ("8/8/1k6/8/2R2R2/8/6K1/8 w - -", Wdl::Win, 5),
+    ("8/8/8/1B6/5K2/3n4/8/5k2 w - -", Wdl::Draw, 0),
+    ("8/5p2/6k1/K7/8/8/8/8 w - -", Wdl::Loss, -2),
+    ("K5n1/8/8/7n/k7/8/8/8 w - -", Wdl::Draw, 0),
+    ("8/8/8/3Kn3/8/8/4k3/N7 b - -", Wdl::Draw, 0),
+    ("8/8/8/2K5/5kp1/8/8/8 b - -", Wdl::Win, 1),
+    ("5k2/8/4K3/8/3r1B2/8/8/8 b - -", Wdl::Win, 1),
+    ("8/8/8/8/4k3/8/8/1R2R1K1 b - -", Wdl::Loss, -8),
+    ("8/8/2q1k3/8/5K2/8/3r4/8 w - -", Wdl::Loss, -6),
+    ("8/8/2r5/8/k7/3K4/6r1/8 w - -", Wdl::Loss, -6),
+    ("6N1/8/8/2k5/7N/1K6/8/8 b - -", Wdl::Draw, 0),
+    ("7k/5K2/6q1/8/8/8/8/2Q5 w - -", Wdl::Win, 1),
+    ("8/8/8/2R5/1K6/8/5k2/8 w - -", Wdl::Win, 21),
+    ("8/3k4/8/8/8/8/4P3/3K4 w - -", Wdl::Draw, 0),
+];
+
+fn engine_with_tables() -> ShakmatEngine {
+    init_evaluation();
+    let config = EngineConfig::builder().use_opening_book(false).syzygy_path(Some(SYZYGY_PATH.to_owned())).build();
+    ShakmatEngine::new(config).unwrap()
+}
+
+fn search(engine: &ShakmatEngine, board: &Board, max_depth: u8) -> shakmat_engine::SearchResult {
+    engine.find_best_move(board, &[], SearchOptions { max_depth: Some(max_depth), ..Default::default() })
+}
+
+#[test]
+fn probe_positions() {
+    let tb = Tablebase::load(SYZYGY_PATH).unwrap();
+    assert_eq!(4, tb.max_pieces());
+
+    for (epd, wdl, dtz) in TB_POSITIONS {
+        let board = Board::from_fen(&format!("{epd} 0 1")).unwrap();
+        assert_eq!(Some(wdl), tb.probe_wdl(&board), "{epd}");
+        assert_eq!(Some(dtz), tb.probe_dtz(&board), "{epd}");
+    }
+}
+
+#[test]
+fn probe_missing_positions() {
+    let tb = Tablebase::load(SYZYGY_PATH).unwrap();
+
+    // There are no KQvKR tables, or too many pieces
+    for fen in ["8/8/8/3k4/8/8/3r4/KQ6 w - - 0 1", "8/8/8/3k4/8/8/3r4/KQR5 w - - 0 1"] {
+        let board = Board::from_fen(fen).unwrap();
+        assert_eq!(None, tb.probe_wdl(&board));
+        assert_eq!(None, tb.probe_dtz(&board));
+        assert_eq!(None, tb.best_root_moves(&board));
+    }
+
+    // Positions with castling rights aren't in the tables, but
+    // every move gives them up, so the moves can be rated
+    let board = Board::from_fen("4k3/8/8/8/8/8/8/R3K3 w Q - 0 1").unwrap();
+    assert_eq!(None, tb.probe_wdl(&board));
+    assert_eq!(None, tb.probe_dtz(&board));
+    assert!(tb.best_root_moves(&board).is_some_and(|moves| !moves.is_empty()));
+}
+
+#[test]
+fn fifty_move_counter() {
+    let tb = Tablebase::load(SYZYGY_PATH).unwrap();
+
+    // The rook needs 21 plies to capture or mate, which is too late after 80 plies
+    let board = Board::from_fen("8/8/8/2R5/1K6/8/5k2/8 w - - 0 1").unwrap();
+    assert_eq!(Some(Wdl::Win), tb.probe_wdl(&board));
+    let board = Board::from_fen("8/8/8/2R5/1K6/8/5k2/8 w - - 80 41").unwrap();
+    assert_eq!(Some(Wdl::CursedWin), tb.probe_wdl(&board));
+}
+
+#[test]
+fn root_moves_keep_the_win() {
+    let tb = Tablebase::load(SYZYGY_PATH).unwrap();
+    let board = Board::from_fen("8/8/8/2R5/1K6/8/5k2/8 w - - 0 1").unwrap();
+    let best_moves = tb.best_root_moves(&board).unwrap();
+    assert!(!best_moves.is_empty());
+
+    // Every move makes progress towards the mate
+    for mv in best_moves {
+        let next_board = board.make_move(&mv);
+        assert_eq!(Some(-20), tb.probe_dtz(&next_board), "{mv}");
+    }
+
+    // The search plays one of them even without seeing the mate
+    let engine = engine_with_tables();
+    let result = search(&engine, &board, 3);
+    let next_board = board.make_move(&result.best_move.unwrap());
+    assert_eq!(Some(-20), tb.probe_dtz(&next_board));
+}
+
+#[test]
+fn search_probes_after_captures() {
+    // Taking the rook leads to a KRRvK win, which is only found by probing the tables
+    let board = Board::from_fen("8/8/8/4k3/8/8/r7/RR2K3 w - - 0 1").unwrap();
+    let result = search(&engine_with_tables(), &board, 1);
+    assert_eq!("a1a2", result.best_move.unwrap().to_string());
+    assert!(result.score.score() > 19000 && !result.score.is_mate());
+
+    let engine = ShakmatEngine::new(EngineConfig::builder().use_opening_book(false).build()).unwrap();
+    assert!(search(&engine, &board, 1).score.score() < 19000);
+}
+
+#[test]
+fn search_without_tables() {
+    // Positions without tables are searched as usual
+    let board = Board::from_fen("8/8/8/3k4/8/8/3r4/KQ6 w - - 0 1").unwrap();
+    let result = search(&engine_with_tables(), &board, 4);
+    assert!(result.best_move.is_some());
+    assert!(result.score.score() < 19000);
+
+    // A directory without any tables can be used, but not a missing one
+    let path = std::env::temp_dir().join("shakmat_test_empty_syzygy");
+    std::fs::create_dir_all(&path).unwrap();
+    let config = EngineConfig::builder().syzygy_path(Some(path.to_string_lossy().into_owned())).build();
+    assert!(ShakmatEngine::new(config).is_ok());
+
+    let missing = EngineConfig::builder().syzygy_path(Some("missing_syzygy_dir".to_owned())).build();
+    assert!(ShakmatEngine::new(missing).is_err());
+
+    let mut engine = engine_with_tables();
+    let missing = EngineConfig::builder().use_opening_book(false).syzygy_path(Some("missing_syzygy_dir".to_owned())).build();
+    assert!(engine.update_config(missing).is_err());
+    assert!(search(&engine, &Board::from_fen("8/8/8/4k3/8/8/r7/RR2K3 w - - 0 1").unwrap(), 1).score.score() > 19000);
+}
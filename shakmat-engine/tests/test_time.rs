@@ -0,0 +1,31 @@
+use shakmat_engine::{SearchOptions, TimeManager};
+
+fn allocated_ms(total_ms: u64, moves_to_go: Option<u64>, increment_ms: Option<u64>) -> u64 {
+    let options = SearchOptions {
+        total_time_remaining: Some(total_ms),
+        moves_until_control: moves_to_go,
+        increment_ms,
+        move_overhead_ms: 0,
+        ..Default::default()
+    };
+
+    TimeManager::new(&options).allocated_micros() / 1000
+}
+
+#[test]
+fn moves_until_control() {
+    // Without the moves until the time control, 40 more moves are assumed
+    assert_eq!(allocated_ms(40_000, None, None), 800);
+    assert_eq!(allocated_ms(40_000, Some(40), None), 800);
+
+    // The time per move grows as the time control gets closer
+    assert_eq!(allocated_ms(40_000, Some(10), None), 3_200);
+    assert_eq!(allocated_ms(40_000, Some(2), None), 16_000);
+
+    // But never more than half of the time left, even with a single move to go
+    assert_eq!(allocated_ms(40_000, Some(1), None), 20_000);
+    assert_eq!(allocated_ms(40_000, Some(0), None), 20_000);
+
+    // Most of the increment can be spent too
+    assert_eq!(allocated_ms(40_000, Some(10), Some(1_000)), 3_950);
+}
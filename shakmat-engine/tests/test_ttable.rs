@@ -0,0 +1,79 @@
+use shakmat_core::Board;
+use shakmat_engine::{Evaluation, NodeType, TTable, TTEntry};
+
+// The tables in these tests have a single slot, so all keys collide
+fn write(tt: &TTable, zobrist: u64, depth: u8) {
+    tt.write_entry(TTEntry::new(zobrist, depth, Evaluation::new(depth as i16), NodeType::Exact, None, 0));
+}
+
+fn stored_depth(tt: &TTable, zobrist: u64) -> Option<u8> {
+    tt.get_entry(zobrist, 0, &mut None).map(|data| data.depth)
+}
+
+#[test]
+fn deep_entries_survive_collisions() {
+    let tt = TTable::new(1);
+    tt.new_search();
+    write(&tt, 1, 10);
+    write(&tt, 2, 0);
+    assert_eq!(Some(10), stored_depth(&tt, 1));
+    assert_eq!(None, stored_depth(&tt, 2));
+
+    // Writes that are at least as deep replace the entry
+    write(&tt, 2, 10);
+    assert_eq!(None, stored_depth(&tt, 1));
+    assert_eq!(Some(10), stored_depth(&tt, 2));
+}
+
+#[test]
+fn old_entries_are_replaced() {
+    let tt = TTable::new(1);
+    tt.new_search();
+    write(&tt, 1, 10);
+    tt.new_search();
+    write(&tt, 2, 0);
+    assert_eq!(None, stored_depth(&tt, 1));
+    assert_eq!(Some(0), stored_depth(&tt, 2));
+}
+
+#[test]
+fn old_entries_keep_deeper_data() {
+    let tt = TTable::new(1);
+    tt.new_search();
+    write(&tt, 1, 10);
+    tt.new_search();
+
+    // A shallower write for the same position keeps the deeper data,
+    // which then belongs to the current search
+    write(&tt, 1, 2);
+    assert_eq!(Some(10), stored_depth(&tt, 1));
+    write(&tt, 2, 0);
+    assert_eq!(Some(10), stored_depth(&tt, 1));
+
+    // Shallower writes for the same position in the current search are ignored
+    write(&tt, 1, 5);
+    assert_eq!(Some(10), stored_depth(&tt, 1));
+}
+
+#[test]
+fn better_bounds_replace_entries() {
+    let tt = TTable::new(1);
+    tt.new_search();
+    let best_move = Board::default().legal_moves()[0];
+
+    // A re-search at the same depth finds an exact score and a best
+    // move for the position where it previously failed low
+    tt.write_entry(TTEntry::new(1, 5, Evaluation::new(-50), NodeType::Upperbound, None, 0));
+    tt.write_entry(TTEntry::new(1, 5, Evaluation::new(20), NodeType::Exact, Some(best_move), 0));
+    let mut tt_move = None;
+    let data = tt.get_entry(1, 5, &mut tt_move).unwrap();
+    assert!(matches!(data.node_type, NodeType::Exact));
+    assert_eq!(20, data.eval.score());
+    assert_eq!(Some(best_move), tt_move);
+
+    // Exact scores are not replaced by bounds of the same depth
+    tt.write_entry(TTEntry::new(1, 5, Evaluation::new(40), NodeType::Lowerbound, None, 0));
+    let data = tt.get_entry(1, 5, &mut None).unwrap();
+    assert!(matches!(data.node_type, NodeType::Exact));
+    assert_eq!(20, data.eval.score());
+}
@@ -0,0 +1,17 @@
+use shakmat_engine::{init_evaluation, read_tuning_dataset, tune, tuning_loss, EvalParams};
+
+#[test]
+fn tuning_reduces_loss() {
+    init_evaluation();
+    let dataset = read_tuning_dataset(include_str!("../../etc/tuning_sample.txt")).unwrap();
+    assert_eq!(24, dataset.len());
+
+    let initial_loss = tuning_loss(&dataset);
+    let params = tune(&dataset, 2);
+    let final_loss = tuning_loss(&dataset);
+    assert!(final_loss < initial_loss);
+
+    // The tuned parameters can be saved and loaded back
+    assert_eq!(Ok(params.clone()), params.to_string().parse::<EvalParams>());
+    assert!(read_tuning_dataset("8/8/8/8/8/8/8/K6k w - - 0 1; 2-0").is_err());
+}
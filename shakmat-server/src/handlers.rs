@@ -1,20 +1,24 @@
-use std::sync::Mutex;
-use std::mem::drop;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
 
-use shakmat_core::Move;
-use shakmat_engine::{ShakmatEngine, SearchOptions, EngineConfig};
+use shakmat_core::{Board, Move, Square};
+use shakmat_engine::{ShakmatEngine, SearchOptions, SearchResult, EngineConfig};
 use rocket::serde::json::Json;
+use rocket::serde::json::serde_json::json;
+use rocket::futures::{SinkExt, StreamExt};
+use rocket::tokio::{select, sync::mpsc, task};
 use rocket::{Route, State};
+use rocket_ws::{WebSocket, Channel, Message, stream::DuplexStream, result::Result as WsResult};
 
-use crate::messages::{ApiResponse, FenData, MoveData, ConfigOptions};
+use crate::messages::{ApiResponse, FenData, MoveData, ConfigOptions, TimeControl, search_info_message, move_suggestion_message};
 use crate::state::ServerState;
 
 type StateMutex<T> = State<Mutex<T>>;
 type GamesState = StateMutex<ServerState>;
-type EngineState = StateMutex<ShakmatEngine>;
+type EngineState = State<Arc<Mutex<ShakmatEngine>>>; // Shared with the streaming searches
 
 pub fn get_routes() -> Vec<Route> {
-    routes![create_game, get_turn_info, make_move, get_computer_move, delete_game, config_engine, _all_options]
+    routes![create_game, get_turn_info, get_moves_from, make_move, undo_move, get_computer_move, get_book_moves, analyze, analyze_stream, get_hash, delete_game, config_engine, clear_table, get_pgn, _all_options]
 }
 
 // Catches all OPTION requests in order to get the CORS related Fairing triggered.
@@ -22,19 +26,22 @@ pub fn get_routes() -> Vec<Route> {
 #[options("/<_..>")]
 fn _all_options() { /* Intentionally left empty */ }
 
-#[post("/games", data = "<fen>")]
-pub fn create_game(state: &GamesState, fen: Option<Json<FenData>>) -> ApiResponse {
+#[post("/games?<variant>", data = "<fen>")]
+pub fn create_game(state: &GamesState, variant: Option<&str>, fen: Option<Json<FenData>>) -> ApiResponse {
     let mut state_lock = state.inner().lock().unwrap();
 
-    let key = if let Some(fen_data) = fen {
+    let key = match (variant, fen) {
+        // Create a game from a random Chess960 starting position
+        (Some("chess960"), None) => state_lock.create_game_chess960(),
+        (Some("chess960"), Some(_)) => return ApiResponse::bad_request("A FEN can't be provided for Chess960 games".to_owned()),
+        (Some(other), _) => return ApiResponse::bad_request(format!("Unknown variant: {other}")),
         // Create a new game from the supplied FEN
-        match state_lock.create_game_from_fen(&fen_data.fen) {
+        (None, Some(fen_data)) => match state_lock.create_game_from_fen(&fen_data.fen) {
             Ok(key) => key,
             Err(msg) => return ApiResponse::bad_request(msg),
-        }
-    } else {
+        },
         // Create a default game
-        state_lock.create_game_default()
+        (None, None) => state_lock.create_game_default(),
     };
 
     // We can unwrap the option because we know the key exists, since we
@@ -52,6 +59,20 @@ pub fn get_turn_info(state: &GamesState, game_id: &str) -> ApiResponse {
     }
 }
 
+#[get("/games/<game_id>/moves?<from>")]
+pub fn get_moves_from(state: &GamesState, game_id: &str, from: &str) -> ApiResponse {
+    let square = match Square::from_notation(from) {
+        Ok(sq) => sq,
+        Err(msg) => return ApiResponse::bad_request(msg),
+    };
+
+    let state_lock = state.inner().lock().unwrap();
+    match state_lock.get_moves_from(game_id, square) {
+        Some(moves) => ApiResponse::moves(moves),
+        None => ApiResponse::not_found("Game not found".to_owned()),
+    }
+}
+
 #[post("/games/<game_id>/move", data = "<move>")]
 pub fn make_move(state: &GamesState, game_id: &str, r#move: Json<MoveData>) -> ApiResponse {
     let mut state_lock = state.inner().lock().unwrap();
@@ -66,38 +87,119 @@ pub fn make_move(state: &GamesState, game_id: &str, r#move: Json<MoveData>) -> A
     }
 }
 
-#[get("/games/<game_id>/move_suggestion?<depth>&<move_ms>&<total_ms>")]
+#[delete("/games/<game_id>/move")]
+pub fn undo_move(state: &GamesState, game_id: &str) -> ApiResponse {
+    let mut state_lock = state.inner().lock().unwrap();
+    match state_lock.undo_move(game_id) {
+        Ok(()) => ApiResponse::turn_info(state_lock.get_turn_info(game_id).unwrap()),
+        Err(msg) => ApiResponse::bad_request(msg),
+    }
+}
+
+#[get("/games/<game_id>/move_suggestion?<depth>&<time..>")]
 pub fn get_computer_move(state: &GamesState, engine: &EngineState, game_id: &str,
-depth: Option<u8>, move_ms: Option<u64>, total_ms: Option<u64>) -> ApiResponse {
-    let state_lock = state.inner().lock().unwrap();
-    let board = match state_lock.get_board(game_id) {
-        Some(board) => *board,
-        None => return ApiResponse::not_found("Game not found".to_owned()),
+depth: Option<u8>, time: TimeControl) -> ApiResponse {
+    // Create the search options struct with the data from the query string
+    let search_options = SearchOptions { 
+        total_time_remaining: time.total_ms,
+        moves_until_control: time.moves_to_go,
+        increment_ms: time.inc_ms,
+        time_for_move: time.move_ms,
+        max_depth: depth,
+        ..Default::default()
     };
-    
-    // Get the list of past positions (cloning it, since we drop the lock
-    // in the next step). We can assume that the game ID exists, otherwise
-    // we would have returned a not_found response.
-    let past_positions = state_lock.get_history(game_id).unwrap().clone();
 
-    // We drop the lock here so the rather slow process of finding the best
-    // move doesn't block all other requests
-    drop(state_lock);
+    match search_game(state, engine, game_id, search_options, ShakmatEngine::find_best_move) {
+        Ok(search_result) => ApiResponse::move_suggestion(&search_result),
+        Err(response) => response,
+    }
+}
+
+// The moves of the opening book for the current position, even if the
+// engine isn't configured to use it when suggesting moves
+#[get("/games/<game_id>/book")]
+pub fn get_book_moves(state: &GamesState, engine: &EngineState, game_id: &str) -> ApiResponse {
+    let (board, _) = match get_position(state, game_id) {
+        Ok(position) => position,
+        Err(response) => return response,
+    };
 
-    // Create the search options struct with the data from the query string
-    let search_options = SearchOptions { 
-        total_time_remaining: total_ms,
-        moves_until_control: None, //TO-DO
+    let moves = engine.inner().lock().unwrap().book_moves(&board);
+    match moves.is_empty() {
+        true => ApiResponse::not_found("Position not in the opening book".to_owned()),
+        false => ApiResponse::book_moves(&moves),
+    }
+}
+
+#[get("/games/<game_id>/analyze?<depth>&<move_ms>")]
+pub fn analyze(state: &GamesState, engine: &EngineState, game_id: &str,
+depth: Option<u8>, move_ms: Option<u64>) -> ApiResponse {
+    let search_options = SearchOptions {
         time_for_move: move_ms,
         max_depth: depth,
+        ..Default::default()
     };
 
-    let engine_lock = engine.inner().lock().unwrap();
-    let search_result = engine_lock.find_best_move(&board, &past_positions, search_options);
+    // Book moves are searched too, to get their score and PV
+    match search_game(state, engine, game_id, search_options, ShakmatEngine::analyze) {
+        Ok(search_result) => ApiResponse::analysis(&search_result),
+        Err(response) => response,
+    }
+}
 
-    match search_result.best_move {
-        Some(_) => ApiResponse::move_suggestion(&search_result),
-        None => ApiResponse::bad_request("No moves available".to_owned()),
+// Streams the progress of the search through a WebSocket: an "info" message
+// for every completed iteration, and a final "bestmove" message
+#[get("/games/<game_id>/analyze/stream?<depth>&<move_ms>")]
+pub fn analyze_stream(ws: WebSocket, state: &GamesState, engine: &EngineState, game_id: &str,
+depth: Option<u8>, move_ms: Option<u64>) -> Result<Channel<'static>, ApiResponse> {
+    let (board, past_positions) = get_position(state, game_id)?;
+    let stop = Arc::new(AtomicBool::new(false));
+    let search_options = SearchOptions {
+        time_for_move: move_ms,
+        max_depth: depth,
+        stop: Some(Arc::clone(&stop)),
+        ..Default::default()
+    };
+
+    // The search runs in a blocking thread, sending the messages through the channel.
+    // If the client disconnects, the search is stopped so that it doesn't keep
+    // the engine locked for the rest of the requests.
+    let engine = Arc::clone(engine.inner());
+    let search_stop = Arc::clone(&stop);
+    let (sender, mut receiver) = mpsc::unbounded_channel();
+
+    task::spawn_blocking(move || {
+        let engine_lock = engine.lock().unwrap();
+        let search_result = engine_lock.analyze_with_info(&board, &past_positions, search_options, |info| {
+            if sender.send(json!({"info": search_info_message(&info)}).to_string()).is_err() {
+                search_stop.store(true, Ordering::Relaxed);
+            }
+        });
+
+        sender.send(json!({"bestmove": move_suggestion_message(&search_result)}).to_string()).ok();
+    });
+
+    Ok(ws.channel(move |mut stream| Box::pin(async move {
+        let result = stream_search(&mut stream, &mut receiver).await;
+        stop.store(true, Ordering::Relaxed);
+        result
+    })))
+}
+
+#[get("/games/<game_id>/hash")]
+pub fn get_hash(state: &GamesState, game_id: &str) -> ApiResponse {
+    match get_position(state, game_id) {
+        Ok((board, past_positions)) => ApiResponse::hash(board.zobrist_key(), &past_positions),
+        Err(response) => response,
+    }
+}
+
+#[get("/games/<game_id>/pgn")]
+pub fn get_pgn(state: &GamesState, game_id: &str) -> Result<String, ApiResponse> {
+    let state_lock = state.inner().lock().unwrap();
+    match state_lock.get_pgn(game_id) {
+        Some(pgn) => Ok(pgn),
+        None => Err(ApiResponse::not_found("Game not found".to_owned())),
     }
 }
 
@@ -114,11 +216,80 @@ pub fn delete_game(state: &GamesState, game_id: &str) -> ApiResponse {
 pub fn config_engine(engine: &EngineState, config: Json<ConfigOptions>) -> ApiResponse {
     let mut state_lock = engine.inner().lock().unwrap();
 
-    let config_engine = EngineConfig {
-        use_opening_book: config.use_book,
-        only_best_book_moves: config.always_top_line,
-    };
+    let mut builder = EngineConfig::builder()
+        .use_opening_book(config.use_book)
+        .only_best_book_moves(config.always_top_line)
+        .book_path(config.book_path.clone())
+        .nnue_path(config.nnue_path.clone())
+        .syzygy_path(config.syzygy_path.clone())
+        .contempt(config.contempt);
+
+    if let Some(hash_mb) = config.hash_mb {
+        builder = builder.hash_mb(hash_mb);
+    }
 
-    state_lock.update_config(config_engine);
+    match state_lock.update_config(builder.build()) {
+        Ok(()) => ApiResponse::no_content(),
+        Err(msg) => ApiResponse::bad_request(msg),
+    }
+}
+
+// The engine and its trasposition table are shared by all the games, so the table
+// isn't cleared when a game is created. Entries from previous searches are the
+// first ones to be replaced, but they can also be forgotten on demand.
+#[delete("/config/table")]
+pub fn clear_table(engine: &EngineState) -> ApiResponse {
+    engine.inner().lock().unwrap().new_game();
     ApiResponse::no_content()
-}
\ No newline at end of file
+}
+
+// Searches the current position of a game with the given search function
+// of the engine, without modifying it
+fn search_game(state: &GamesState, engine: &EngineState, game_id: &str, search_options: SearchOptions,
+search: fn(&ShakmatEngine, &Board, &[u64], SearchOptions) -> SearchResult) -> Result<SearchResult, ApiResponse> {
+    let (board, past_positions) = get_position(state, game_id)?;
+    let engine_lock = engine.inner().lock().unwrap();
+    let search_result = search(&engine_lock, &board, &past_positions, search_options);
+
+    match search_result.best_move {
+        Some(_) => Ok(search_result),
+        None => Err(ApiResponse::bad_request("No moves available".to_owned())),
+    }
+}
+
+// Gets a copy of the current board of a game and its past positions. The lock
+// is released afterwards, so the rather slow process of searching the position
+// doesn't block all other requests.
+fn get_position(state: &GamesState, game_id: &str) -> Result<(Board, Vec<u64>), ApiResponse> {
+    let state_lock = state.inner().lock().unwrap();
+    let board = match state_lock.get_board(game_id) {
+        Some(board) => *board,
+        None => return Err(ApiResponse::not_found("Game not found".to_owned())),
+    };
+
+    // We can assume that the game ID exists, otherwise
+    // we would have returned a not_found response.
+    let past_positions = state_lock.get_history(game_id).unwrap().clone();
+    Ok((board, past_positions))
+}
+
+// Sends the messages of a search through a WebSocket until the search is over,
+// and then closes it. The client is read from at the same time, so that its
+// pings are answered and it can close the connection before the search ends.
+async fn stream_search(stream: &mut DuplexStream, receiver: &mut mpsc::UnboundedReceiver<String>) -> WsResult<()> {
+    loop {
+        select! {
+            msg = receiver.recv() => match msg {
+                Some(msg) => stream.send(Message::Text(msg)).await?,
+                None => return stream.close(None).await,
+            },
+            msg = stream.next() => match msg {
+                // The reply to the close frame is sent when flushing
+                Some(Ok(Message::Close(_))) => return stream.flush().await,
+                Some(Ok(_)) => {},
+                Some(Err(err)) => return Err(err),
+                None => return Ok(()),
+            },
+        }
+    }
+}
@@ -11,7 +11,7 @@ mod messages;
 
 use state::ServerState;
 use std::env::args;
-use std::sync::Mutex;
+use std::sync::{Arc, Mutex};
 use shakmat_engine::ShakmatEngine;
 
 const DEFAULT_PORT: u16 = 8000;
@@ -33,7 +33,7 @@ fn run() -> _ {
         .configure(config)
         .mount("/", handlers::get_routes())
         .manage(Mutex::from(ServerState::new()))
-        .manage(Mutex::from(ShakmatEngine::default()))
+        .manage(Arc::new(Mutex::from(ShakmatEngine::default())))
         .attach(CORS)
 }
 
@@ -6,7 +6,7 @@ use rocket::response;
 use rocket::response::{Responder, Response};
 use rocket::request::Request;
 
-use shakmat_engine::SearchResult;
+use shakmat_engine::{SearchResult, SearchInfo};
 use shakmat_core::{Move, Color, Board};
 
 // Generic API response with an arbitraty HTTP status code and json payload
@@ -42,18 +42,66 @@ impl ApiResponse {
         Self { status: Status::Ok, payload: json!({"turn_info": turn_info}) }
     }
 
+    pub fn moves(moves: Vec<Move>) -> Self {
+        Self { status: Status::Ok, payload: json!({"moves": moves}) }
+    }
+
+    pub fn book_moves(moves: &[(Move, u16)]) -> Self {
+        let moves: Vec<_> = moves.iter().map(|(mv, weight)| json!({"move": mv, "weight": weight})).collect();
+        Self { status: Status::Ok, payload: json!({"moves": moves}) }
+    }
+
     pub fn move_suggestion(sr: &SearchResult) -> Self {
+        Self { status: Status::Ok, payload: move_suggestion_message(sr) }
+    }
+
+    // The score is from the point of view of the side to move, either in
+    // centipawns or as the number of moves until mate (negative if getting mated)
+    pub fn analysis(sr: &SearchResult) -> Self {
+        let mate = sr.score.moves_to_mate();
+        let score_cp = if mate.is_none() { Some(sr.score.score()) } else { None };
+
         Self { status: Status::Ok, payload: json!({
-            "move": sr.best_move.unwrap().to_string(),
-            "eval": sr.score.to_string(), 
+            "eval": sr.score.to_string(),
+            "score_cp": score_cp,
+            "mate": mate,
+            "pv": sr.pv.iter().map(|mv| mv.to_string()).collect::<Vec<_>>(),
+            "nodes": sr.nodes,
+            "depth": sr.depth_reached,
+            "elapsed_ms": sr.elapsed_ms,
         }) }
     }
 
+    // The previous positions include the current one, and are the ones used to detect repetitions
+    pub fn hash(hash: u64, previous_positions: &[u64]) -> Self {
+        Self { status: Status::Ok, payload: json!({"hash": hash, "previous_positions": previous_positions}) }
+    }
+
     pub fn no_content() -> Self {
         Self { status: Status::NoContent, payload: json!({}) }
     }
 }
 
+pub fn move_suggestion_message(sr: &SearchResult) -> Value {
+    json!({
+        "move": sr.best_move.map(|mv| mv.to_string()),
+        "eval": sr.score.to_string(),
+        "pv": sr.pv.iter().map(|mv| mv.to_string()).collect::<Vec<_>>(),
+        "from_book": sr.from_book,
+    })
+}
+
+// Progress of an iteration of the search, for live analysis
+pub fn search_info_message(info: &SearchInfo) -> Value {
+    json!({
+        "depth": info.depth,
+        "eval": info.score.to_string(),
+        "nodes": info.nodes,
+        "elapsed_ms": info.elapsed_ms,
+        "pv": info.pv.iter().map(|mv| mv.to_string()).collect::<Vec<_>>(),
+    })
+}
+
 // Info for the current turn
 #[derive(Serialize)]
 #[serde(rename = "turn_info")]
@@ -91,6 +139,15 @@ pub struct FenData {
     pub fen: String
 }
 
+// Time control of the side to move, from the query string, in millis
+#[derive(FromForm)]
+pub struct TimeControl {
+    pub move_ms: Option<u64>, // Fixed time for this move, overrides the rest
+    pub total_ms: Option<u64>,
+    pub inc_ms: Option<u64>,
+    pub moves_to_go: Option<u64>, // Moves until the next time control
+}
+
 #[derive(Deserialize, Serialize)]
 pub struct MoveData {
     pub r#move: String,
@@ -99,7 +156,13 @@ pub struct MoveData {
 #[derive(Deserialize, Serialize)]
 pub struct ConfigOptions {
     pub use_book: bool,
-    pub always_top_line: bool
+    pub always_top_line: bool,
+    pub book_path: Option<String>,
+    pub nnue_path: Option<String>,
+    pub syzygy_path: Option<String>,
+    #[serde(default)]
+    pub contempt: i16,
+    pub hash_mb: Option<usize>, // Trasposition table size, 64 MB by default
 }
 
 ///////////////////////////////////////////////////////////////////////////////
@@ -1,4 +1,4 @@
-use shakmat_core::{Board, Move, DEFAULT_FEN};
+use shakmat_core::{Board, Move, Square, DEFAULT_FEN, chess960_fen, create_pgn};
 use super::messages::TurnInfo;
 
 use rand::Rng;
@@ -13,6 +13,8 @@ pub struct ServerState {
 struct GameData {
     pub board: Board,
     pub previous_positions: Vec<u64>,
+    pub initial_board: Board,
+    pub moves: Vec<Move>,
 }
 
 impl ServerState {
@@ -33,6 +35,13 @@ impl ServerState {
         Ok(key)
     }
 
+    // Starts a game from one of the 960 starting positions, chosen at random
+    pub fn create_game_chess960(&mut self) -> String {
+        let number = rand::thread_rng().gen_range(0..960);
+        // Any number below 960 is a valid position
+        self.create_game_from_fen(&chess960_fen(number).unwrap()).unwrap()
+    }
+
     pub fn delete_game(&mut self, key: &str) -> Result<(), String> {
         match self.games.remove_entry(key) {
             Some(_) => Ok(()),
@@ -51,33 +60,58 @@ impl ServerState {
     pub fn get_history(&self, key: &str) -> Option<&Vec<u64>> {
         self.games.get(key).map(|gd| &gd.previous_positions)
     }
+
+    // Legal moves in the current position that start on the provided square.
+    // Castling moves are considered to start on the king's square.
+    pub fn get_moves_from(&self, key: &str, square: Square) -> Option<Vec<Move>> {
+        self.games.get(key).map(|gd| {
+            let king_square = gd.board.get_pieces(gd.board.turn_color()).king.first_piece_index();
+            gd.board.legal_moves().into_iter().filter(|mv| {
+                let from = match mv {
+                    Move::ShortCastle | Move::LongCastle => king_square,
+                    _ => mv.from(),
+                };
+                from == square.square()
+            }).collect()
+        })
+    }
+
+    pub fn get_pgn(&self, key: &str) -> Option<String> {
+        self.games.get(key).map(|gd| create_pgn(&gd.initial_board, &gd.moves))
+    }
     
     // It is assumed that the key always exists, since it is needed to get
     // the game data in the first place
     pub fn make_move(&mut self, key: &str, movement: Move) -> Result<(), String> {
-        let game = match self.games.get(key) {
+        let game = match self.games.get_mut(key) {
             Some(g) => g,
             None => return Err("Game not found".to_owned()),
         };
 
-        // Check whether the move is legal
-        if !game.board.is_legal_move(&movement) {
-            return Err("Illegal move".to_owned());
-        }
-
-        // If it is, apply the new move and replace the current board
-        let new_board = game.board.make_move(&movement);
-        let mut game_state = self.get_game_mut(key);
-        game_state.board = new_board;
-        game_state.previous_positions.push(new_board.zobrist_key());
+        // The board is only updated if the move is legal
+        game.board.make_move_checked(&movement)?;
+        game.previous_positions.push(game.board.zobrist_key());
+        game.moves.push(movement);
 
-        println!("{new_board}");
+        println!("{}", game.board);
         Ok(())
     }
 
-    // Mutably gets the GameData entry associated to a key that is assumed to exist
-    fn get_game_mut(&mut self, key: &str) -> &mut GameData {
-        self.games.get_mut(key).unwrap()
+    // Takes back the last move, restoring the previous board and position history
+    pub fn undo_move(&mut self, key: &str) -> Result<(), String> {
+        let game = match self.games.get_mut(key) {
+            Some(g) => g,
+            None => return Err("Game not found".to_owned()),
+        };
+
+        if game.moves.pop().is_none() {
+            return Err("There are no moves to undo".to_owned());
+        }
+
+        // Boards can't be unmade, so we replay the remaining moves from the start
+        game.board = game.moves.iter().fold(game.initial_board, |board, mv| board.make_move(mv));
+        game.previous_positions.pop();
+        Ok(())
     }
 }
 
@@ -87,7 +121,7 @@ impl GameData {
         let mut previous_positions = Vec::with_capacity(250);
         previous_positions.push(board.zobrist_key());
 
-        Ok(Self { board, previous_positions})
+        Ok(Self { board, previous_positions, initial_board: board, moves: vec![] })
     }
 }
 
@@ -16,15 +16,12 @@ pub struct TurnInfo {
 pub struct SearchResult {
     pub best_move: Option<String>,
     pub eval: String,
+    pub from_book: bool, /* The eval is meaningless for book moves */
 }
 
 impl TurnInfo {
     pub fn from_board(board: &Board, history: &[u64]) -> Self {
-        let moves = if shakmat_engine::is_draw_by_repetition(board, 0, history) {
-            vec![]
-        } else {
-            board.legal_moves().into_iter().map(|mv| mv.to_string().into()).collect()
-        };
+        let moves = playable_moves(board, history);
 
         Self {
             turn_number: board.turn_number(),
@@ -35,3 +32,13 @@ impl TurnInfo {
         }
     }
 }
+
+// The legal moves as strings, or none if the game is drawn by repetition
+// (the other draws are already taken into account by legal_moves())
+pub fn playable_moves(board: &Board, history: &[u64]) -> Vec<JsValue> {
+    if shakmat_engine::is_draw_by_repetition(board, 0, history) {
+        vec![]
+    } else {
+        board.legal_moves().into_iter().map(|mv| mv.to_string().into()).collect()
+    }
+}
@@ -1,27 +1,45 @@
 mod data_structures;
 
-use data_structures::{TurnInfo, SearchResult};
+use data_structures::{TurnInfo, SearchResult, playable_moves};
+use std::sync::Once;
+
 use shakmat_engine::{ShakmatEngine, EngineConfig, SearchOptions};
-use shakmat_core::{Board, Move};
+use shakmat_core::{Board, Color, Move};
 use wasm_bindgen::prelude::*;
 
 //extern crate console_error_panic_hook;
 
+// The evaluation masks must be generated before evaluating any position
+static INIT_EVALUATION: Once = Once::new();
+
 /** 
    Obtains the current turn information for a provided FEN and move history.
 
-   **It is assumed that the FEN is valid.**
+   Throws an error if the FEN is invalid.
 */
 #[wasm_bindgen]
-pub fn get_turn_data(fen: &str, history: Box<[u64]>) -> TurnInfo {
-    let board = Board::from_fen(fen).unwrap();
-    TurnInfo::from_board(&board, &history)
+pub fn get_turn_data(fen: &str, history: Box<[u64]>) -> Result<TurnInfo, JsValue> {
+    let board = parse_fen(fen)?;
+    Ok(TurnInfo::from_board(&board, &history))
+}
+
+/** 
+   Lists the legal moves in a position in coordinate notation (e2e4), without
+   the rest of the turn information. There are none if the game is over, and
+   draws by repetition are only detected if the move history is provided.
+
+   Throws an error if the FEN is invalid.
+*/
+#[wasm_bindgen]
+pub fn legal_moves(fen: &str, history: Option<Box<[u64]>>) -> Result<Box<[JsValue]>, JsValue> {
+    let board = parse_fen(fen)?;
+    Ok(playable_moves(&board, &history.unwrap_or_default()).into_boxed_slice())
 }
 
 /** 
    Instructs the engine to look for the best move in a given position by a FEN.
 
-   **It is assumed that the FEN is valid.**
+   Throws an error if the FEN is invalid.
 */
 #[wasm_bindgen]
 pub fn get_computer_move(
@@ -29,24 +47,29 @@ pub fn get_computer_move(
     history: Box<[u64]>,
     move_ms: u32,
     use_opening_book: bool,
-    only_best_book_moves: bool
-) -> SearchResult {
-    let board = Board::from_fen(fen).unwrap();
-    let engine_config = EngineConfig { use_opening_book, only_best_book_moves };
+    only_best_book_moves: bool,
+    min_book_weight: u16,
+) -> Result<SearchResult, JsValue> {
+    INIT_EVALUATION.call_once(shakmat_engine::init_evaluation);
+    let board = parse_fen(fen)?;
+    let engine_config = EngineConfig::builder().use_opening_book(use_opening_book).only_best_book_moves(only_best_book_moves).build();
     let search_options = SearchOptions { 
         max_depth: None, 
         moves_until_control: None, 
         total_time_remaining: None, 
-        time_for_move: Some(move_ms as u64) 
+        time_for_move: Some(move_ms as u64),
+        min_book_weight,
+        ..Default::default()
     };
 
-    let engine = ShakmatEngine::new(engine_config);
+    let engine = ShakmatEngine::new(engine_config).map_err(|err| JsValue::from_str(&err))?;
     let search_data = engine.find_best_move(&board, &history, search_options);
 
-    SearchResult { 
+    Ok(SearchResult { 
         best_move: search_data.best_move.map(|mv| mv.to_string()), 
-        eval: search_data.score.to_string()
-    }
+        eval: search_data.score.to_string(),
+        from_book: search_data.from_book,
+    })
 }
 
 /** 
@@ -54,23 +77,81 @@ pub fn get_computer_move(
    This is used to help the client keep track of the previous positions,
    since they must be provided in every request to check for draws by repetition.
 
-   **It is assumed that the FEN is valid.**
+   Throws an error if the FEN is invalid.
 */
 #[wasm_bindgen]
-pub fn fen2hash(fen: &str) -> u64 {
-    Board::from_fen(fen).unwrap().zobrist_key()
+pub fn fen2hash(fen: &str) -> Result<u64, JsValue> {
+    Ok(parse_fen(fen)?.zobrist_key())
 }
 
 /** 
     Applies a given move to a given position, returning the FEN string
-    for the new position.
+    for the new position. Throws an error if the FEN or the move are
+    invalid, or if the move is illegal.
+*/
+#[wasm_bindgen]
+pub fn make_move(fen: &str, movement: &str) -> Result<String, JsValue> {
+    let parsed_move = Move::from_notation(movement).map_err(|err| JsValue::from_str(&err))?;
+    let mut board = parse_fen(fen)?;
+    board.make_move_checked(&parsed_move).map_err(|err| JsValue::from_str(&err))?;
+    Ok(board.fen())
+}
+
+/** 
+    Counts the leaf nodes of the move tree of a position up to a given depth,
+    which can be compared against known values to verify the move generator.
 
-    **It is assumed that the move is valid and legal for the position.**
+    Throws an error if the FEN is invalid.
 */
 #[wasm_bindgen]
-pub fn make_move(fen: &str, movement: &str) -> String {
-    let parsed_move = Move::from_notation(movement).unwrap();
-    let board = Board::from_fen(fen).unwrap();
-    let new_board = board.make_move(&parsed_move);
-    new_board.fen()
+pub fn perft(fen: &str, depth: u32) -> Result<u64, JsValue> {
+    Ok(parse_fen(fen)?.perft(depth as usize))
+}
+
+/** 
+    Static evaluation of a position in centipawns, always from white's
+    point of view so it can be shown directly in an evaluation bar.
+
+    Throws an error if the FEN is invalid.
+*/
+#[wasm_bindgen]
+pub fn evaluate(fen: &str) -> Result<i32, JsValue> {
+    INIT_EVALUATION.call_once(shakmat_engine::init_evaluation);
+    let board = parse_fen(fen)?;
+    let score = shakmat_engine::evaluate_position(&board).score() as i32;
+    Ok(if board.turn_color() == Color::White { score } else { -score })
+}
+
+/** 
+    Converts a move in coordinate notation (e2e4) to Standard Algebraic
+    Notation (e4) in a given position. Throws an error if the FEN or the
+    move are invalid, or if the move is illegal.
+*/
+#[wasm_bindgen]
+pub fn to_san(fen: &str, mv: &str) -> Result<String, JsValue> {
+    let board = parse_fen(fen)?;
+    let parsed_move = Move::from_notation(mv).map_err(|err| JsValue::from_str(&err))?;
+
+    if !board.is_legal_move(&parsed_move) {
+        return Err(JsValue::from_str(&format!("Illegal move: {mv}")));
+    }
+
+    Ok(parsed_move.to_san(&board))
+}
+
+/** 
+    Converts a move in Standard Algebraic Notation (Nf3) to coordinate
+    notation (g1f3) in a given position. Throws an error if the FEN is
+    invalid, or if the move is invalid, illegal or ambiguous.
+*/
+#[wasm_bindgen]
+pub fn from_san(fen: &str, san: &str) -> Result<String, JsValue> {
+    let board = parse_fen(fen)?;
+    Move::from_san(san, &board).map(|mv| mv.to_string()).map_err(|err| JsValue::from_str(&err))
+}
+
+// Reads a FEN from JS, turning the parsing errors into JS errors
+// instead of panicking, which would bring down the whole module
+fn parse_fen(fen: &str) -> Result<Board, JsValue> {
+    Board::from_fen(fen).map_err(|err| JsValue::from_str(&err))
 }